@@ -1,8 +1,8 @@
 use std::{
- collections::{
+ cell::RefCell, collections::{
     HashMap,
     VecDeque
-  }, path::PathBuf, sync::{
+  }, path::PathBuf, rc::Rc, sync::{
     Arc,
     Mutex
   }
@@ -11,13 +11,18 @@ use std::{
 use ds_emulator::{
   apu::Sample,
   cpu::{
-    bus::Bus,
+    bus::{
+      power_manager::PowerManager,
+      slot2::{GuitarGripButton, RumbleCallback},
+      Bus
+    },
     registers::{
       external_key_input_register::ExternalKeyInputRegister,
       key_input_register::KeyInputRegister
     }
   },
   gpu::{
+    layer_debug::{DebugLayer, GpuEngine},
     registers::power_control_register1::PowerControlRegister1,
     GPU,
     SCREEN_HEIGHT,
@@ -57,8 +62,9 @@ use sdl2::{
     Button,
     GameController
   },
-  event::Event,
+  event::{Event, WindowEvent},
   keyboard::Keycode,
+  sys::SDL_WindowFlags,
   video::{
     GLContext, GLProfile, Window
   },
@@ -66,12 +72,23 @@ use sdl2::{
   Sdl
 };
 
-use crate::cloud_service::CloudService;
+use crate::{cloud_service::CloudService, graphics_viewer::GraphicsViewer, player_window::PlayerWindow};
+
+const PADDLE_WHEEL_STEP: i32 = 0x40;
+const PADDLE_STICK_DIVISOR: i32 = 0x400;
+// a duration of 0 keeps the controller rumbling until the motor is switched off again
+const RUMBLE_DURATION: u32 = 0;
 
 pub enum UIAction {
   None,
   Reset(bool),
-  LoadGame(PathBuf)
+  LoadGame(PathBuf),
+  LiveMic,
+  WavMic(PathBuf),
+  SilentMic,
+  SetLayerDisabled(GpuEngine, DebugLayer, bool),
+  SoloLayer(GpuEngine, Option<DebugLayer>),
+  ResetLayers
 }
 
 struct DsAudioCallback {
@@ -133,7 +150,9 @@ impl AudioCallback for DsAudioRecording {
 }
 pub struct Frontend {
   event_pump: EventPump,
-  _controller: Option<GameController>,
+  // shared with the rumble callback
+  controller: Option<Rc<RefCell<GameController>>>,
+  guitar_grip_key_map: HashMap<Keycode, GuitarGripButton>,
   button_map: HashMap<Button, KeyInputRegister>,
   ext_button_map: HashMap<Button, ExternalKeyInputRegister>,
   ext_key_map: HashMap<Keycode, ExternalKeyInputRegister>,
@@ -152,7 +171,11 @@ pub struct Frontend {
   textures: Textures<NativeTexture>,
   _gl_context: GLContext,
   pub cloud_service: Arc<Mutex<CloudService>>,
-  capture_device: Option<AudioDevice<DsAudioRecording>>
+  capture_device: Option<AudioDevice<DsAudioRecording>>,
+  top_buffer: Vec<u8>,
+  bottom_buffer: Vec<u8>,
+  graphics_viewer: GraphicsViewer,
+  pub mic_blowing: bool
 }
 
 impl Frontend {
@@ -240,11 +263,11 @@ impl Frontend {
         .num_joysticks()
         .map_err(|e| format!("can't enumerate joysticks: {}", e)).unwrap();
 
-    let _controller = (0..available)
+    let controller = (0..available)
       .find_map(|id| {
         match game_controller_subsystem.open(id) {
           Ok(c) => {
-            Some(c)
+            Some(Rc::new(RefCell::new(c)))
           }
           Err(_) => {
             None
@@ -314,6 +337,13 @@ impl Frontend {
     ext_key_map.insert(Keycode::N, ExternalKeyInputRegister::BUTTON_Y);
     ext_key_map.insert(Keycode::M, ExternalKeyInputRegister::BUTTON_X);
 
+    let mut guitar_grip_key_map = HashMap::new();
+
+    guitar_grip_key_map.insert(Keycode::Num1, GuitarGripButton::Green);
+    guitar_grip_key_map.insert(Keycode::Num2, GuitarGripButton::Red);
+    guitar_grip_key_map.insert(Keycode::Num3, GuitarGripButton::Yellow);
+    guitar_grip_key_map.insert(Keycode::Num4, GuitarGripButton::Blue);
+
     let mut button_map = HashMap::new();
 
     button_map.insert(Button::B, KeyInputRegister::ButtonA);
@@ -338,7 +368,8 @@ impl Frontend {
     Self {
       event_pump,
       window,
-      _controller,
+      controller,
+      guitar_grip_key_map,
       button_map,
       ext_button_map,
       key_map,
@@ -356,7 +387,11 @@ impl Frontend {
       textures,
       _gl_context: gl_context,
       cloud_service: Arc::new(Mutex::new(CloudService::new())),
-      capture_device
+      capture_device,
+      top_buffer: Vec::new(),
+      bottom_buffer: Vec::new(),
+      graphics_viewer: GraphicsViewer::new(),
+      mic_blowing: false
     }
   }
 
@@ -374,6 +409,11 @@ impl Frontend {
   }
 
   pub fn handle_touchscreen(&mut self, bus: &mut Bus) {
+    // the mouse might be over the second player's window instead
+    if self.window.window_flags() & SDL_WindowFlags::SDL_WINDOW_MOUSE_FOCUS as u32 == 0 {
+      return;
+    }
+
     if !self.use_control_stick {
       let state = self.event_pump.mouse_state();
 
@@ -382,7 +422,8 @@ impl Frontend {
 
       if state.left() && y >= SCREEN_HEIGHT as i32 * 2 && x >= 0 {
         bus.arm7.extkeyin.remove(ExternalKeyInputRegister::PEN_DOWN);
-        bus.touchscreen.touch_screen(x as u16 / 2, y as u16 / 2 - SCREEN_HEIGHT);
+        // the window is scaled 2x, so the mouse lands on half pixels
+        bus.touchscreen.touch_screen_subpixel(x as f32 / 2.0, y as f32 / 2.0 - SCREEN_HEIGHT as f32);
       } else if !state.left() {
         bus.touchscreen.release_screen();
         bus.arm7.extkeyin.insert(ExternalKeyInputRegister::PEN_DOWN);
@@ -400,11 +441,20 @@ impl Frontend {
     }
   }
 
-  pub fn handle_events(&mut self, bus: &mut Bus) {
+  pub fn handle_events(&mut self, bus: &mut Bus, mut player_two: Option<(&mut PlayerWindow, &mut Bus)>) {
     for event in self.event_pump.poll_iter() {
+      if let Some((player_window, player_bus)) = &mut player_two {
+        if event.get_window_id() == Some(player_window.id()) {
+          player_window.handle_event(&event, player_bus, &self.key_map, &self.ext_key_map);
+          continue;
+        }
+      }
+
       self.platform.handle_event(&mut self.imgui, &event);
       match event {
         Event::Quit { .. } => std::process::exit(0),
+        // with a second player's window open, closing this one doesn't send a quit by itself
+        Event::Window { win_event: WindowEvent::Close, .. } => std::process::exit(0),
         Event::KeyDown { keycode, .. } => {
           if let Some(button) = self.key_map.get(&keycode.unwrap_or(Keycode::Return)) {
             self.show_menu = false;
@@ -412,6 +462,10 @@ impl Frontend {
           } else if let Some(button) = self.ext_key_map.get(&keycode.unwrap()) {
             self.show_menu = false;
             bus.arm7.extkeyin.set(*button, false);
+          } else if let Some(button) = self.guitar_grip_key_map.get(&keycode.unwrap()) {
+            if let Some(device) = &mut bus.slot2_device {
+              device.set_button(*button, true);
+            }
           } else if keycode.unwrap() == Keycode::G {
             bus.debug_on = !bus.debug_on
           } else if keycode.unwrap() == Keycode::F {
@@ -421,6 +475,10 @@ impl Frontend {
           } else if keycode.unwrap() == Keycode::T {
             self.use_control_stick = !self.use_control_stick;
             bus.arm7.extkeyin.set(ExternalKeyInputRegister::PEN_DOWN, !self.use_control_stick);
+          } else if keycode.unwrap() == Keycode::H {
+            bus.set_lid_closed(!bus.lid_closed());
+          } else if keycode.unwrap() == Keycode::B {
+            self.mic_blowing = true;
           } else if keycode.unwrap() == Keycode::Escape {
             self.show_menu = !self.show_menu;
           }
@@ -430,6 +488,12 @@ impl Frontend {
             bus.key_input_register.set(*button, true);
           } else if let Some(button) = self.ext_key_map.get(&keycode.unwrap()) {
             bus.arm7.extkeyin.set(*button, true);
+          } else if let Some(button) = self.guitar_grip_key_map.get(&keycode.unwrap()) {
+            if let Some(device) = &mut bus.slot2_device {
+              device.set_button(*button, false);
+            }
+          } else if keycode.unwrap() == Keycode::B {
+            self.mic_blowing = false;
           }
         }
         Event::ControllerButtonDown { button, .. } => {
@@ -462,9 +526,19 @@ impl Frontend {
             Axis::LeftY => {
               self.controller_y = value;
             }
+            Axis::RightX => {
+              if let Some(device) = &mut bus.slot2_device {
+                device.move_paddle(value as i32 / PADDLE_STICK_DIVISOR);
+              }
+            }
             _ => ()
           }
         }
+        Event::MouseWheel { y, .. } => {
+          if let Some(device) = &mut bus.slot2_device {
+            device.move_paddle(y * PADDLE_WHEEL_STEP);
+          }
+        }
         _ => ()
       }
       if self.use_control_stick {
@@ -473,7 +547,36 @@ impl Frontend {
     }
   }
 
-  pub fn render(&mut self, gpu: &mut GPU) {
+  // drives the controller's rumble from a rumble pak's motor
+  pub fn rumble_callback(&self) -> Option<RumbleCallback> {
+    let controller = self.controller.clone()?;
+
+    Some(Box::new(move |motor_on| {
+      let strength = if motor_on { 0xffff } else { 0 };
+
+      controller.borrow_mut().set_rumble(strength, strength, RUMBLE_DURATION).unwrap_or(());
+    }))
+  }
+
+  // scales the rgb channels by the screen's backlight, leaving the pixels untouched at full brightness
+  pub fn apply_backlight<'a>(pixels: &'a [u8], brightness: f32, buffer: &'a mut Vec<u8>) -> &'a [u8] {
+    if brightness >= 1.0 {
+      return pixels;
+    }
+
+    buffer.clear();
+
+    for pixel in pixels.chunks_exact(4) {
+      buffer.push((pixel[0] as f32 * brightness) as u8);
+      buffer.push((pixel[1] as f32 * brightness) as u8);
+      buffer.push((pixel[2] as f32 * brightness) as u8);
+      buffer.push(pixel[3]);
+    }
+
+    buffer
+  }
+
+  pub fn render(&mut self, gpu: &mut GPU, power_manager: &PowerManager) {
 
     let (top, bottom) = if gpu.powcnt1.contains(PowerControlRegister1::TOP_A) {
       (&gpu.engine_a.pixels, &gpu.engine_b.pixels)
@@ -481,6 +584,9 @@ impl Frontend {
       (&gpu.engine_b.pixels, &gpu.engine_a.pixels)
     };
 
+    let top = Self::apply_backlight(top, power_manager.top_brightness(), &mut self.top_buffer);
+    let bottom = Self::apply_backlight(bottom, power_manager.bottom_brightness(), &mut self.bottom_buffer);
+
     unsafe {
       self.gl.clear(glow::COLOR_BUFFER_BIT);
       self.gl.bind_texture(TEXTURE_2D, Some(self.texture));
@@ -529,7 +635,11 @@ impl Frontend {
     self.window.gl_swap_window();
   }
 
-  pub fn render_ui(&mut self) -> UIAction {
+  pub fn render_ui(&mut self, gpu: &GPU) -> UIAction {
+    if self.graphics_viewer.open {
+      self.graphics_viewer.update(&self.gl, &mut self.textures, gpu);
+    }
+
     self.platform.prepare_frame(&mut self.imgui, &mut self.window, &self.event_pump);
 
     let ui = self.imgui.new_frame();
@@ -542,6 +652,7 @@ impl Frontend {
           if ui.menu_item("Open") {
             match FileDialog::new()
               .add_filter("NDS Rom file", &["nds"])
+              .add_filter("Homebrew ELF file", &["elf"])
               .show_open_single_file() {
                 Ok(path) => if let Some(path) = path {
                   action = UIAction::LoadGame(path);
@@ -557,6 +668,25 @@ impl Frontend {
           }
           menu.end();
         }
+        if let Some(menu) = ui.begin_menu("Microphone") {
+          if ui.menu_item("Live input") {
+            action = UIAction::LiveMic;
+          }
+          if ui.menu_item("Loop WAV file") {
+            match FileDialog::new()
+              .add_filter("WAV file", &["wav"])
+              .show_open_single_file() {
+                Ok(path) => if let Some(path) = path {
+                  action = UIAction::WavMic(path);
+                }
+                Err(_) => ()
+              }
+          }
+          if ui.menu_item("Silence") {
+            action = UIAction::SilentMic;
+          }
+          menu.end();
+        }
         if let Some(menu) = ui.begin_menu("Cloud saves") {
           let mut cloud_service = self.cloud_service.lock().unwrap();
 
@@ -568,11 +698,68 @@ impl Frontend {
             cloud_service.logout();
           }
 
+          menu.end();
+        }
+        if let Some(menu) = ui.begin_menu("Layers") {
+          for (engine, label) in [(GpuEngine::A, "Engine A"), (GpuEngine::B, "Engine B")] {
+            if let Some(engine_menu) = ui.begin_menu(label) {
+              let layer_debug = gpu.layer_debug(engine);
+
+              for layer in DebugLayer::ALL {
+                // engine B has no 3d layer
+                if engine == GpuEngine::B && layer == DebugLayer::Bg3d {
+                  continue;
+                }
+
+                let disabled = layer_debug.is_disabled(layer);
+
+                if ui.menu_item_config(layer.name()).selected(!disabled).build() {
+                  action = UIAction::SetLayerDisabled(engine, layer, !disabled);
+                }
+              }
+
+              ui.separator();
+
+              if let Some(solo_menu) = ui.begin_menu("Solo") {
+                for layer in DebugLayer::ALL.into_iter().filter(|layer| layer.can_solo()) {
+                  if engine == GpuEngine::B && layer == DebugLayer::Bg3d {
+                    continue;
+                  }
+
+                  let is_solo = layer_debug.solo() == Some(layer);
+
+                  if ui.menu_item_config(layer.name()).selected(is_solo).build() {
+                    action = UIAction::SoloLayer(engine, if is_solo { None } else { Some(layer) });
+                  }
+                }
+
+                solo_menu.end();
+              }
+
+              engine_menu.end();
+            }
+          }
+
+          ui.separator();
+
+          if ui.menu_item("Show all layers") {
+            action = UIAction::ResetLayers;
+          }
+
+          menu.end();
+        }
+        if let Some(menu) = ui.begin_menu("Debug") {
+          if ui.menu_item_config("Graphics viewer").selected(self.graphics_viewer.open).build() {
+            self.graphics_viewer.open = !self.graphics_viewer.open;
+          }
+
           menu.end();
         }
       });
     }
 
+    self.graphics_viewer.draw(ui);
+
     let draw_data = self.imgui.render();
 
     self.renderer.render(&self.gl, &mut self.textures, draw_data).unwrap();
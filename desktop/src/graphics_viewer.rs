@@ -0,0 +1,402 @@
+use std::collections::HashMap;
+
+use ds_emulator::gpu::{
+  layer_debug::GpuEngine,
+  viewer::{BankMapping, BgKind, ObjectInfo, TilePalette, ViewerImage},
+  vram::Bank,
+  GPU
+};
+use imgui::{Condition, TextureId, Textures, Ui};
+use imgui_glow_renderer::glow::{
+  self,
+  HasContext,
+  NativeTexture,
+  NEAREST,
+  RGBA,
+  RGBA8,
+  TEXTURE_2D,
+  TEXTURE_MAG_FILTER,
+  TEXTURE_MIN_FILTER,
+  UNSIGNED_BYTE
+};
+
+const BANK_NAMES: [&str; 9] = ["A", "B", "C", "D", "E", "F", "G", "H", "I"];
+const PALETTE_NAMES: [&str; 3] = ["Grayscale", "BG palette", "OBJ palette"];
+const BG_NAMES: [&str; 4] = ["BG0", "BG1", "BG2", "BG3"];
+
+// texture slots, the objects take one each starting at OBJECTS_TEXTURE
+const TILES_TEXTURE: usize = 0;
+const TILEMAP_TEXTURE: usize = 1;
+const BG_PALETTE_TEXTURE: usize = 2;
+const OBJ_PALETTE_TEXTURE: usize = 3;
+const OBJECTS_TEXTURE: usize = 4;
+
+const TILE_SCALE: f32 = 2.0;
+const PALETTE_SCALE: f32 = 12.0;
+const OBJECT_PREVIEW_SIZE: f32 = 32.0;
+
+#[derive(Copy, Clone, PartialEq)]
+enum ViewerTab {
+  Tiles,
+  Tilemap,
+  Objects,
+  Palettes,
+  Banks
+}
+
+struct TilemapInfo {
+  kind: BgKind,
+  width: usize,
+  height: usize,
+  scroll_x: i32,
+  scroll_y: i32,
+  screen_width: usize,
+  screen_height: usize
+}
+
+/*
+  window for poking at vram, oam and the palettes. the images are decoded before the imgui frame starts,
+  using the settings picked on the previous frame, since the textures have to be uploaded by then.
+ */
+pub struct GraphicsViewer {
+  pub open: bool,
+  tab: ViewerTab,
+  engine: usize,
+  bank: usize,
+  bpp8: bool,
+  palette: usize,
+  palette_bank: u32,
+  bg_index: usize,
+  show_hidden: bool,
+  tiles_size: (usize, usize),
+  tilemap: Option<TilemapInfo>,
+  objects: Vec<ObjectInfo>,
+  bank_mappings: Vec<BankMapping>,
+  textures: HashMap<usize, (NativeTexture, TextureId)>
+}
+
+impl GraphicsViewer {
+  pub fn new() -> Self {
+    Self {
+      open: false,
+      tab: ViewerTab::Tiles,
+      engine: 0,
+      bank: 0,
+      bpp8: false,
+      palette: 0,
+      palette_bank: 0,
+      bg_index: 0,
+      show_hidden: false,
+      tiles_size: (0, 0),
+      tilemap: None,
+      objects: Vec::new(),
+      bank_mappings: Vec::new(),
+      textures: HashMap::new()
+    }
+  }
+
+  fn gpu_engine(&self) -> GpuEngine {
+    if self.engine == 0 { GpuEngine::A } else { GpuEngine::B }
+  }
+
+  fn upload(&mut self, gl: &glow::Context, textures: &mut Textures<NativeTexture>, slot: usize, image: &ViewerImage) {
+    let (texture, _) = *self.textures.entry(slot).or_insert_with(|| unsafe {
+      let texture = gl.create_texture().unwrap();
+
+      gl.bind_texture(TEXTURE_2D, Some(texture));
+      gl.tex_parameter_i32(TEXTURE_2D, TEXTURE_MIN_FILTER, NEAREST as i32);
+      gl.tex_parameter_i32(TEXTURE_2D, TEXTURE_MAG_FILTER, NEAREST as i32);
+
+      (texture, textures.insert(texture))
+    });
+
+    unsafe {
+      gl.bind_texture(TEXTURE_2D, Some(texture));
+
+      gl.tex_image_2d(
+        TEXTURE_2D,
+        0,
+        RGBA8 as i32,
+        image.width as i32,
+        image.height as i32,
+        0,
+        RGBA,
+        UNSIGNED_BYTE,
+        Some(&image.pixels)
+      );
+    }
+  }
+
+  fn texture_id(&self, slot: usize) -> Option<TextureId> {
+    self.textures.get(&slot).map(|(_, id)| *id)
+  }
+
+  pub fn update(&mut self, gl: &glow::Context, textures: &mut Textures<NativeTexture>, gpu: &GPU) {
+    let engine = self.gpu_engine();
+
+    match self.tab {
+      ViewerTab::Tiles => {
+        let palette = match self.palette {
+          0 => TilePalette::Grayscale,
+          1 => TilePalette::Bg(self.palette_bank as usize),
+          _ => TilePalette::Obj(self.palette_bank as usize)
+        };
+
+        let image = gpu.bank_tiles(Bank::new(self.bank), self.bpp8, engine, palette);
+
+        self.upload(gl, textures, TILES_TEXTURE, &image);
+
+        self.tiles_size = (image.width, image.height);
+      }
+      ViewerTab::Tilemap => {
+        self.tilemap = gpu.bg_tilemap(engine, self.bg_index).map(|view| {
+          self.upload(gl, textures, TILEMAP_TEXTURE, &view.image);
+
+          TilemapInfo {
+            kind: view.kind,
+            width: view.image.width,
+            height: view.image.height,
+            scroll_x: view.scroll_x,
+            scroll_y: view.scroll_y,
+            screen_width: view.screen_width,
+            screen_height: view.screen_height
+          }
+        });
+      }
+      ViewerTab::Objects => {
+        let objects = gpu.objects(engine);
+
+        for object in &objects {
+          self.upload(gl, textures, OBJECTS_TEXTURE + object.index, &object.preview);
+        }
+
+        self.objects = objects;
+      }
+      ViewerTab::Palettes => {
+        self.upload(gl, textures, BG_PALETTE_TEXTURE, &gpu.palette_image(engine, false));
+        self.upload(gl, textures, OBJ_PALETTE_TEXTURE, &gpu.palette_image(engine, true));
+      }
+      ViewerTab::Banks => self.bank_mappings = gpu.bank_mappings()
+    }
+  }
+
+  pub fn draw(&mut self, ui: &Ui) {
+    if !self.open {
+      return;
+    }
+
+    let mut open = self.open;
+
+    ui.window("Graphics viewer")
+      .opened(&mut open)
+      .size([560.0, 480.0], Condition::FirstUseEver)
+      .build(|| {
+        ui.combo_simple_string("Engine", &mut self.engine, &["Engine A", "Engine B"]);
+
+        if let Some(tab_bar) = ui.tab_bar("viewer_tabs") {
+          if let Some(tab) = ui.tab_item("Tiles") {
+            self.tab = ViewerTab::Tiles;
+            self.draw_tiles(ui);
+            tab.end();
+          }
+          if let Some(tab) = ui.tab_item("Tilemap") {
+            self.tab = ViewerTab::Tilemap;
+            self.draw_tilemap(ui);
+            tab.end();
+          }
+          if let Some(tab) = ui.tab_item("OAM") {
+            self.tab = ViewerTab::Objects;
+            self.draw_objects(ui);
+            tab.end();
+          }
+          if let Some(tab) = ui.tab_item("Palettes") {
+            self.tab = ViewerTab::Palettes;
+            self.draw_palettes(ui);
+            tab.end();
+          }
+          if let Some(tab) = ui.tab_item("Banks") {
+            self.tab = ViewerTab::Banks;
+            self.draw_banks(ui);
+            tab.end();
+          }
+
+          tab_bar.end();
+        }
+      });
+
+    self.open = open;
+  }
+
+  fn draw_tiles(&mut self, ui: &Ui) {
+    ui.combo_simple_string("Bank", &mut self.bank, &BANK_NAMES);
+    ui.checkbox("256 colors", &mut self.bpp8);
+    ui.combo_simple_string("Palette", &mut self.palette, &PALETTE_NAMES);
+
+    if self.palette != 0 && !self.bpp8 {
+      ui.slider("Palette bank", 0, 15, &mut self.palette_bank);
+    }
+
+    if let Some(texture_id) = self.texture_id(TILES_TEXTURE) {
+      ui.child_window("tiles").build(|| {
+        imgui::Image::new(texture_id, [self.tiles_size.0 as f32 * TILE_SCALE, self.tiles_size.1 as f32 * TILE_SCALE]).build(ui);
+      });
+    }
+  }
+
+  fn draw_tilemap(&mut self, ui: &Ui) {
+    ui.combo_simple_string("Background", &mut self.bg_index, &BG_NAMES);
+
+    let (Some(tilemap), Some(texture_id)) = (&self.tilemap, self.texture_id(TILEMAP_TEXTURE)) else {
+      ui.text("Not available in the current mode");
+      return;
+    };
+
+    ui.text(format!("{}, {}x{}, scroll {}, {}", tilemap.kind.name(), tilemap.width, tilemap.height, tilemap.scroll_x, tilemap.scroll_y));
+
+    ui.child_window("tilemap").horizontal_scrollbar(true).build(|| {
+      let origin = ui.cursor_screen_pos();
+      let (width, height) = (tilemap.width as f32, tilemap.height as f32);
+
+      imgui::Image::new(texture_id, [width, height]).build(ui);
+
+      let draw_list = ui.get_window_draw_list();
+
+      let x = tilemap.scroll_x.rem_euclid(tilemap.width as i32) as f32;
+      let y = tilemap.scroll_y.rem_euclid(tilemap.height as i32) as f32;
+
+      // the screen wraps around the bg, so draw it again from the other side when it runs off the edge
+      draw_list.with_clip_rect_intersect(origin, [origin[0] + width, origin[1] + height], || {
+        for (offset_x, offset_y) in [(0.0, 0.0), (-width, 0.0), (0.0, -height), (-width, -height)] {
+          let top_left = [origin[0] + x + offset_x, origin[1] + y + offset_y];
+          let bottom_right = [top_left[0] + tilemap.screen_width as f32, top_left[1] + tilemap.screen_height as f32];
+
+          draw_list.add_rect(top_left, bottom_right, [1.0, 0.0, 0.0, 1.0]).thickness(2.0).build();
+        }
+      });
+    });
+  }
+
+  fn draw_objects(&mut self, ui: &Ui) {
+    ui.checkbox("Show hidden objects", &mut self.show_hidden);
+
+    ui.columns(8, "objects", true);
+
+    for header in ["#", "Preview", "Position", "Size", "Mode", "Tile", "Palette", "Flags"] {
+      ui.text(header);
+      ui.next_column();
+    }
+
+    ui.separator();
+
+    for object in &self.objects {
+      if object.hidden && !self.show_hidden {
+        continue;
+      }
+
+      ui.text(format!("{}", object.index));
+      ui.next_column();
+
+      if let Some(texture_id) = self.texture_id(OBJECTS_TEXTURE + object.index) {
+        // keep the shape but fit everything in the same box
+        let scale = OBJECT_PREVIEW_SIZE / object.width.max(object.height) as f32;
+
+        imgui::Image::new(texture_id, [object.width as f32 * scale, object.height as f32 * scale]).build(ui);
+      }
+      ui.next_column();
+
+      ui.text(format!("{}, {}", object.x, object.y));
+      ui.next_column();
+
+      ui.text(format!("{}x{}", object.width, object.height));
+      ui.next_column();
+
+      ui.text(object.mode.name());
+      ui.next_column();
+
+      ui.text(format!("{:x} (prio {})", object.tile_number, object.priority));
+      ui.next_column();
+
+      if object.bpp8 {
+        ui.text("256 colors");
+      } else {
+        ui.text(format!("16 colors, {}", object.palette_number));
+      }
+      ui.next_column();
+
+      let mut flags = Vec::new();
+
+      if object.affine {
+        flags.push(format!("affine {}", object.affine_group));
+      }
+      if object.double_size {
+        flags.push("double size".to_string());
+      }
+      if object.horizontal_flip {
+        flags.push("h flip".to_string());
+      }
+      if object.vertical_flip {
+        flags.push("v flip".to_string());
+      }
+      if object.mosaic {
+        flags.push("mosaic".to_string());
+      }
+      if object.hidden {
+        flags.push("hidden".to_string());
+      }
+
+      ui.text(flags.join(", "));
+      ui.next_column();
+    }
+
+    ui.columns(1, "", false);
+  }
+
+  fn draw_palettes(&mut self, ui: &Ui) {
+    for (label, slot) in [("BG", BG_PALETTE_TEXTURE), ("OBJ", OBJ_PALETTE_TEXTURE)] {
+      ui.group(|| {
+        ui.text(label);
+
+        if let Some(texture_id) = self.texture_id(slot) {
+          imgui::Image::new(texture_id, [16.0 * PALETTE_SCALE, 16.0 * PALETTE_SCALE]).build(ui);
+        }
+      });
+
+      ui.same_line();
+    }
+
+    ui.new_line();
+  }
+
+  fn draw_banks(&mut self, ui: &Ui) {
+    ui.columns(6, "banks", true);
+
+    for header in ["Bank", "Enabled", "MST", "Offset", "Size", "Mapped to"] {
+      ui.text(header);
+      ui.next_column();
+    }
+
+    ui.separator();
+
+    for mapping in &self.bank_mappings {
+      ui.text(BANK_NAMES[mapping.bank as usize]);
+      ui.next_column();
+
+      ui.text(if mapping.enabled { "yes" } else { "no" });
+      ui.next_column();
+
+      ui.text(format!("{}", mapping.mst));
+      ui.next_column();
+
+      ui.text(format!("{}", mapping.offset));
+      ui.next_column();
+
+      ui.text(format!("{}K", mapping.size / 1024));
+      ui.next_column();
+
+      ui.text(&mapping.destination);
+      ui.next_column();
+    }
+
+    ui.columns(1, "", false);
+  }
+}
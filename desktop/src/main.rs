@@ -8,14 +8,32 @@ use std::{
   }, time::{SystemTime, UNIX_EPOCH},
 };
 
-use ds_emulator::{cpu::bus::cartridge::{BackupType, Header}, nds::Nds};
+use ds_emulator::{
+  cpu::bus::{
+    cartridge::{BackupType, Header},
+    slot2::{
+      guitar_grip::GuitarGrip,
+      memory_expansion_pak::MemoryExpansionPak,
+      paddle::Paddle,
+      rumble_pak::RumblePak,
+      Slot2Device
+    }
+  },
+  local_wireless::{firmware_mac_address, set_firmware_mac_address, LocalWireless},
+  mic::{LiveMic, SilentMic, WavMic},
+  nds::Nds
+};
 
 use frontend::{Frontend, UIAction};
+use player_window::PlayerWindow;
+use sdl2::Sdl;
 
 extern crate ds_emulator;
 
 pub mod frontend;
 pub mod cloud_service;
+pub mod player_window;
+pub mod graphics_viewer;
 
 fn detect_backup_type(frontend: &mut Frontend, nds: &mut Nds, rom_path: String, bytes: Option<Vec<u8>>) {
   if frontend.cloud_service.lock().unwrap().logged_in {
@@ -51,6 +69,62 @@ fn detect_backup_type(frontend: &mut Frontend, nds: &mut Nds, rom_path: String,
   }
 }
 
+/*
+  a second console with its own mac address. for local multiplayer it runs the same game with its own save file,
+  for download play it has no card and boots into the firmware menu so the game can be downloaded from player 1
+ */
+fn create_player_two(
+  sdl_context: &Sdl,
+  rom: Option<(&str, &Vec<u8>)>,
+  firmware_path: &Path,
+  bios7_bytes: Vec<u8>,
+  bios9_bytes: Vec<u8>,
+  skip_bios: bool
+) -> (Nds, PlayerWindow) {
+  let mut firmware = fs::read(firmware_path).unwrap();
+
+  let mut mac_address = firmware_mac_address(&firmware);
+  mac_address[5] ^= 1;
+
+  set_firmware_mac_address(&mut firmware, mac_address);
+
+  let mut nds = Nds::new(
+    None,
+    Some(firmware),
+    bios7_bytes,
+    bios9_bytes,
+    Arc::new(Mutex::new(VecDeque::new())),
+    Arc::new(Mutex::new([0; 2048]))
+  );
+
+  nds.set_mic_source(Box::new(SilentMic));
+  nds.set_debug_print_callback(Box::new(|is_arm9, message| print_debug_message("P2 ", is_arm9, message)));
+
+  if let Some((rom_path, rom_bytes)) = rom {
+    nds.init(rom_bytes, skip_bios);
+
+    let ref mut bus = *nds.bus.borrow_mut();
+
+    if let Some(entry) = bus.cartridge.detect_backup_type() {
+      bus.cartridge.set_backup(Path::new(rom_path).with_extension("p2.sav"), entry);
+    }
+  }
+
+  (nds, PlayerWindow::new(sdl_context, "NDS Plus - Player 2"))
+}
+
+fn print_debug_message(prefix: &str, is_arm9: bool, message: &str) {
+  println!("[{}{}] {}", prefix, if is_arm9 { "ARM9" } else { "ARM7" }, message);
+}
+
+fn is_gba_rom(rom_path: &str) -> bool {
+  Path::new(rom_path).extension().is_some_and(|extension| extension.eq_ignore_ascii_case("gba"))
+}
+
+fn is_elf(rom_path: &str) -> bool {
+  Path::new(rom_path).extension().is_some_and(|extension| extension.eq_ignore_ascii_case("elf"))
+}
+
 fn handle_frontend(
   frontend: &mut Frontend,
   rom_path: &mut String,
@@ -59,17 +133,41 @@ fn handle_frontend(
   rom_loaded: &mut bool,
   logged_in: &mut bool
 ) -> bool {
-  match frontend.render_ui() {
+  let action = frontend.render_ui(&nds.bus.borrow().gpu);
+
+  match action {
     UIAction::None => (),
     UIAction::LoadGame(path) => {
       *rom_path = path.clone().to_string_lossy().to_string();
       let rom = fs::read(rom_path.clone()).unwrap();
+
+      if is_gba_rom(rom_path) {
+        if !nds.has_gba_bios() {
+          return false;
+        }
+
+        nds.load_gba_rom(rom, Some(path.with_extension("sav")), None);
+        nds.reset_gba(true);
+
+        *has_backup = false;
+        *rom_loaded = true;
+
+        return true;
+      }
+
+      if is_elf(rom_path) {
+        *has_backup = false;
+        *rom_loaded = nds.reset_elf(&rom, None);
+
+        return true;
+      }
+
       nds.reset(&rom);
       detect_backup_type(frontend, nds, rom_path.clone(), None);
 
       *has_backup = {
         match &nds.bus.borrow().cartridge.backup {
-          BackupType::Eeprom(_) | BackupType::Flash(_) => true,
+          BackupType::Eeprom(_) | BackupType::Flash(_) | BackupType::Nand(_) | BackupType::Infrared(_) => true,
           BackupType::None => false
         }
       };
@@ -78,6 +176,20 @@ fn handle_frontend(
 
       return true;
     }
+    UIAction::LiveMic => nds.set_mic_source(Box::new(LiveMic::new(nds.mic_samples.clone()))),
+    UIAction::WavMic(path) => match WavMic::open(&path) {
+      Some(wav_mic) => nds.set_mic_source(Box::new(wav_mic)),
+      None => println!("[WARN] couldn't load {} as a PCM wav file", path.display())
+    }
+    UIAction::SilentMic => nds.set_mic_source(Box::new(SilentMic)),
+    UIAction::SetLayerDisabled(engine, layer, disabled) => nds.bus.borrow_mut().gpu.set_layer_disabled(engine, layer, disabled),
+    UIAction::SoloLayer(engine, layer) => nds.bus.borrow_mut().gpu.set_solo_layer(engine, layer),
+    UIAction::ResetLayers => nds.bus.borrow_mut().gpu.reset_layer_debug(),
+    UIAction::Reset(_) if nds.bus.borrow().gba_mode => {
+      nds.reset_gba(true);
+
+      return true;
+    }
     UIAction::Reset(get_bytes) => {
       // this is so that it doesn't have to fetch the save from the cloud all over again, which adds considerable lag
       let bytes = if frontend.cloud_service.lock().unwrap().logged_in && get_bytes {
@@ -86,6 +198,8 @@ fn handle_frontend(
         match &bus.cartridge.backup {
           BackupType::Eeprom(eeprom) => Some(eeprom.backup_file.buffer.clone()),
           BackupType::Flash(flash)=> Some(flash.backup_file.buffer.clone()),
+          BackupType::Infrared(infrared) => Some(infrared.flash.backup_file.buffer.clone()),
+          BackupType::Nand(nand) => Some(nand.backup_file.buffer.clone()),
           BackupType::None => None
         }
       } else {
@@ -101,7 +215,7 @@ fn handle_frontend(
 
       *has_backup = {
         match &nds.bus.borrow().cartridge.backup {
-          BackupType::Eeprom(_) | BackupType::Flash(_) => true,
+          BackupType::Eeprom(_) | BackupType::Flash(_) | BackupType::Nand(_) | BackupType::Infrared(_) => true,
           BackupType::None => false
         }
       };
@@ -124,9 +238,53 @@ fn main() {
   }
 
   let mut skip_bios = true;
-
-  if args.len() == 3 && args[2] == "--start-bios" {
-    skip_bios = false;
+  let mut local_multiplayer = false;
+  let mut download_play = false;
+  let mut gba_rom_path: Option<String> = None;
+  let mut arm7_elf_path: Option<String> = None;
+  let mut sd_card_path: Option<String> = None;
+  let mut homebrew_args: Vec<String> = Vec::new();
+  let mut slot2_device: Option<Box<dyn Slot2Device>> = None;
+
+  let mut i = 2;
+  while i < args.len() {
+    match args[i].as_str() {
+      "--start-bios" => skip_bios = false,
+      "--local-multiplayer" => local_multiplayer = true,
+      "--download-play" => download_play = true,
+      "--arm7" if i + 1 < args.len() => {
+        arm7_elf_path = Some(args[i + 1].to_string());
+        i += 1;
+      }
+      // everything after this goes to the homebrew as its argv
+      "--" => {
+        homebrew_args = args[i + 1..].to_vec();
+        break;
+      }
+      "--sd" if i + 1 < args.len() => {
+        sd_card_path = Some(args[i + 1].to_string());
+        i += 1;
+      }
+      "--gba" if i + 1 < args.len() => {
+        gba_rom_path = Some(args[i + 1].to_string());
+        i += 1;
+      }
+      "--slot2" if i + 1 < args.len() => {
+        slot2_device = match args[i + 1].as_str() {
+          "rumble" => Some(Box::new(RumblePak::new())),
+          "expansion" => Some(Box::new(MemoryExpansionPak::new())),
+          "guitar" => Some(Box::new(GuitarGrip::new())),
+          "paddle" => Some(Box::new(Paddle::new())),
+          device => {
+            println!("warning: unknown slot 2 device {}", device);
+            None
+          }
+        };
+        i += 1;
+      }
+      _ => println!("warning: unknown argument {}", args[i])
+    }
+    i += 1;
   }
 
   let mut rom_loaded = false;
@@ -150,15 +308,62 @@ fn main() {
   let mut nds = Nds::new(
     Some(firmware_path.to_path_buf()),
     None,
-    bios7_bytes,
-    bios9_bytes,
+    bios7_bytes.clone(),
+    bios9_bytes.clone(),
     audio_buffer,
     mic_samples
   );
 
+  nds.set_debug_print_callback(Box::new(|is_arm9, message| print_debug_message("", is_arm9, message)));
+
+  if let Some(callback) = frontend.rumble_callback() {
+    nds.set_rumble_callback(callback);
+  }
+
+  if let Some(sd_card_path) = &sd_card_path {
+    nds.insert_sd_card(Path::new(sd_card_path));
+  }
+
+  if !homebrew_args.is_empty() {
+    let program_name = Path::new(&rom_path).file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+
+    let mut argv = vec![format!("fat:/{}", program_name)];
+
+    argv.extend(homebrew_args);
+
+    nds.set_argv(argv);
+  }
+
   let mut has_backup = false;
-  if rom_path != "" {
+  // gba games need the gba bios, without it they can only be used as slot 2 data carts
+  if let Ok(gba_bios_bytes) = fs::read("./gba_bios.bin") {
+    nds.load_gba_bios(gba_bios_bytes);
+  }
+
+  if is_gba_rom(&rom_path) {
+    let rom_bytes = fs::read(&rom_path).unwrap();
+    let save_path = Path::new(&rom_path).with_extension("sav");
+
+    rom_loaded = nds.init_gba(rom_bytes, Some(save_path), None, skip_bios);
+  } else if is_elf(&rom_path) {
+    let arm9_elf = fs::read(&rom_path).unwrap();
+    let arm7_elf = arm7_elf_path.map(|path| fs::read(path).unwrap());
+
+    rom_loaded = nds.init_elf(&arm9_elf, arm7_elf.as_deref());
+  } else if rom_path != "" {
     let rom_bytes = fs::read(&rom_path).unwrap();
+
+    if let Some(gba_rom_path) = &gba_rom_path {
+      let gba_rom = fs::read(gba_rom_path).unwrap();
+      let gba_save_path = Path::new(gba_rom_path).with_extension("sav");
+
+      nds.load_gba_rom(gba_rom, Some(gba_save_path), None);
+    }
+
+    if let Some(device) = slot2_device.take() {
+      nds.bus.borrow_mut().insert_slot2_device(device);
+    }
+
     nds.init(&rom_bytes, skip_bios);
 
     rom_loaded = true;
@@ -166,25 +371,50 @@ fn main() {
     detect_backup_type(&mut frontend, &mut nds, rom_path.clone(), None);
   }
 
+  let mut player_two = if (local_multiplayer || download_play) && rom_loaded && !is_gba_rom(&rom_path) {
+    if download_play {
+      Some(create_player_two(&sdl_context, None, firmware_path, bios7_bytes, bios9_bytes, skip_bios))
+    } else {
+      let rom_bytes = nds.bus.borrow().cartridge.rom.clone();
+
+      Some(create_player_two(&sdl_context, Some((&rom_path, &rom_bytes)), firmware_path, bios7_bytes, bios9_bytes, skip_bios))
+    }
+  } else {
+    if local_multiplayer || download_play {
+      println!("warning: local multiplayer needs a DS rom passed on the command line");
+    }
+
+    None
+  };
+
+  let mut wireless = LocalWireless::new();
+
   let mut frame_finished = false;
 
   let mut logged_in = frontend.cloud_service.lock().unwrap().logged_in;
   if rom_loaded {
     has_backup = match &nds.bus.borrow().cartridge.backup {
-      BackupType::Eeprom(_) | BackupType::Flash(_) => true,
+      BackupType::Eeprom(_) | BackupType::Flash(_) | BackupType::Nand(_) | BackupType::Infrared(_) => true,
       BackupType::None => false
     }
   }
 
   loop {
     if rom_loaded {
-      let frame_start = nds.arm7_cpu.cycles;
+      if let Some((player_two_nds, _)) = &mut player_two {
+        wireless.run_frame(&mut [&mut nds, player_two_nds]);
+      } else {
+        let frame_start = nds.arm7_cpu.cycles;
 
-      while !frame_finished {
-        frame_finished = nds.step();
-        nds.bus.borrow_mut().frame_cycles = nds.arm7_cpu.cycles - frame_start;
+        while !frame_finished {
+          frame_finished = nds.step();
+          nds.bus.borrow_mut().frame_cycles = nds.arm7_cpu.cycles - frame_start;
+        }
       }
 
+      nds.set_mic_blowing(frontend.mic_blowing);
+      nds.update_mic();
+
       // need to do this or else will rust complain about borrowing and ownership
       {
         let ref mut bus = *nds.bus.borrow_mut();
@@ -192,13 +422,22 @@ fn main() {
         bus.gpu.frame_finished = false;
         bus.gpu.cap_fps();
 
-        let mic_samples = nds.mic_samples.lock().unwrap();
+        frame_finished = false;
+
+        frontend.render(&mut bus.gpu, &bus.power_manager);
+      }
 
-        bus.touchscreen.update_mic_buffer(&mic_samples.to_vec());
+      if let Some((player_two_nds, player_window)) = &mut player_two {
+        player_two_nds.update_mic();
 
-        frame_finished = false;
+        let ref mut bus = *player_two_nds.bus.borrow_mut();
+
+        bus.gpu.frame_finished = false;
+
+        // only the first console gets played back
+        bus.arm7.apu.audio_buffer.lock().unwrap().clear();
 
-        frontend.render(&mut bus.gpu);
+        player_window.render(bus);
       }
 
       frontend.resume_audio();
@@ -218,12 +457,27 @@ fn main() {
 
       frontend.end_frame();
 
-      frontend.handle_events(bus);
+      match &mut player_two {
+        Some((player_two_nds, player_window)) => {
+          let ref mut player_bus = *player_two_nds.bus.borrow_mut();
+
+          frontend.handle_events(bus, Some((player_window, player_bus)));
+        }
+        None => frontend.handle_events(bus, None)
+      }
       frontend.handle_touchscreen(bus);
+
+      // the game asked the power manager to switch the system off
+      if bus.power_manager.take_power_off_request() {
+        rom_loaded = false;
+      }
+
       if logged_in && has_backup {
         let file = match &mut bus.cartridge.backup {
           BackupType::Eeprom(eeprom) => &mut eeprom.backup_file,
           BackupType::Flash(flash) => &mut flash.backup_file,
+          BackupType::Infrared(infrared) => &mut infrared.flash.backup_file,
+          BackupType::Nand(nand) => &mut nand.backup_file,
           BackupType::None => unreachable!()
         };
 
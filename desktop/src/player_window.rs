@@ -0,0 +1,136 @@
+use std::collections::HashMap;
+
+use ds_emulator::{
+  cpu::{
+    bus::Bus,
+    registers::{
+      external_key_input_register::ExternalKeyInputRegister,
+      key_input_register::KeyInputRegister
+    }
+  },
+  gpu::{
+    registers::power_control_register1::PowerControlRegister1,
+    SCREEN_HEIGHT,
+    SCREEN_WIDTH
+  }
+};
+
+use sdl2::{
+  event::{Event, WindowEvent},
+  keyboard::Keycode,
+  mouse::MouseButton,
+  pixels::PixelFormatEnum,
+  rect::Rect,
+  render::Canvas,
+  video::Window,
+  Sdl
+};
+
+use crate::frontend::Frontend;
+
+/*
+  The window for the second console in local multiplayer. It's a plain sdl canvas rather than
+  another imgui window, since sdl only hands out one event pump and imgui only likes one context.
+  The main frontend forwards any events aimed at this window here.
+ */
+pub struct PlayerWindow {
+  canvas: Canvas<Window>,
+  top_buffer: Vec<u8>,
+  bottom_buffer: Vec<u8>
+}
+
+impl PlayerWindow {
+  pub fn new(sdl_context: &Sdl, title: &str) -> Self {
+    let video_subsystem = sdl_context.video().unwrap();
+
+    let window = video_subsystem
+      .window(title, SCREEN_WIDTH as u32 * 2, SCREEN_HEIGHT as u32 * 4)
+      .position_centered()
+      .build()
+      .unwrap();
+
+    // a software renderer keeps this window from touching the main window's gl context
+    let canvas = window.into_canvas().software().build().unwrap();
+
+    Self {
+      canvas,
+      top_buffer: Vec::new(),
+      bottom_buffer: Vec::new()
+    }
+  }
+
+  pub fn id(&self) -> u32 {
+    self.canvas.window().id()
+  }
+
+  pub fn handle_event(
+    &mut self,
+    event: &Event,
+    bus: &mut Bus,
+    key_map: &HashMap<Keycode, KeyInputRegister>,
+    ext_key_map: &HashMap<Keycode, ExternalKeyInputRegister>
+  ) {
+    match event {
+      Event::Window { win_event: WindowEvent::Close, .. } => std::process::exit(0),
+      Event::KeyDown { keycode: Some(keycode), .. } => {
+        if let Some(button) = key_map.get(keycode) {
+          bus.key_input_register.set(*button, false);
+        } else if let Some(button) = ext_key_map.get(keycode) {
+          bus.arm7.extkeyin.set(*button, false);
+        } else if *keycode == Keycode::H {
+          bus.set_lid_closed(!bus.lid_closed());
+        }
+      }
+      Event::KeyUp { keycode: Some(keycode), .. } => {
+        if let Some(button) = key_map.get(keycode) {
+          bus.key_input_register.set(*button, true);
+        } else if let Some(button) = ext_key_map.get(keycode) {
+          bus.arm7.extkeyin.set(*button, true);
+        }
+      }
+      Event::MouseButtonDown { mouse_btn: MouseButton::Left, x, y, .. } => Self::touch(bus, *x, *y),
+      Event::MouseMotion { mousestate, x, y, .. } if mousestate.left() => Self::touch(bus, *x, *y),
+      Event::MouseButtonUp { mouse_btn: MouseButton::Left, .. } => {
+        bus.touchscreen.release_screen();
+        bus.arm7.extkeyin.insert(ExternalKeyInputRegister::PEN_DOWN);
+      }
+      _ => ()
+    }
+  }
+
+  fn touch(bus: &mut Bus, x: i32, y: i32) {
+    if y >= SCREEN_HEIGHT as i32 * 2 && x >= 0 {
+      bus.arm7.extkeyin.remove(ExternalKeyInputRegister::PEN_DOWN);
+      bus.touchscreen.touch_screen_subpixel(x as f32 / 2.0, y as f32 / 2.0 - SCREEN_HEIGHT as f32);
+    }
+  }
+
+  pub fn render(&mut self, bus: &Bus) {
+    let gpu = &bus.gpu;
+
+    let (top, bottom) = if gpu.powcnt1.contains(PowerControlRegister1::TOP_A) {
+      (&gpu.engine_a.pixels, &gpu.engine_b.pixels)
+    } else {
+      (&gpu.engine_b.pixels, &gpu.engine_a.pixels)
+    };
+
+    let top = Frontend::apply_backlight(top, bus.power_manager.top_brightness(), &mut self.top_buffer);
+    let bottom = Frontend::apply_backlight(bottom, bus.power_manager.bottom_brightness(), &mut self.bottom_buffer);
+
+    let texture_creator = self.canvas.texture_creator();
+
+    // the gpu writes out rgba bytes, which is abgr when read as little endian words
+    let mut texture = texture_creator
+      .create_texture_static(PixelFormatEnum::ABGR8888, SCREEN_WIDTH as u32, SCREEN_HEIGHT as u32 * 2)
+      .unwrap();
+
+    let pitch = SCREEN_WIDTH as usize * 4;
+
+    texture.update(Rect::new(0, 0, SCREEN_WIDTH as u32, SCREEN_HEIGHT as u32), top, pitch).unwrap();
+    texture.update(Rect::new(0, SCREEN_HEIGHT as i32, SCREEN_WIDTH as u32, SCREEN_HEIGHT as u32), bottom, pitch).unwrap();
+
+    self.canvas.clear();
+    self.canvas.copy(&texture, None, None).unwrap();
+    self.canvas.present();
+  }
+}
@@ -56,6 +56,12 @@ mod ffi {
     #[swift_bridge(swift_name = "releaseScreen")]
     fn release_screen(&mut self);
 
+    #[swift_bridge(swift_name = "touchScreenSubpixel")]
+    fn touch_screen_subpixel(&mut self, x: f32, y: f32);
+
+    #[swift_bridge(swift_name = "setTouchPressure")]
+    fn set_touch_pressure(&mut self, pressure: f32);
+
     #[swift_bridge(swift_name = "updateInput")]
     fn update_input(&mut self, button_event: ButtonEvent, value: bool);
 
@@ -88,6 +94,18 @@ mod ffi {
 
     #[swift_bridge(swift_name="updateAudioBuffer")]
     fn update_audio_buffer(&mut self, buffer: &[f32]);
+
+    #[swift_bridge(swift_name="setBatteryLow")]
+    fn set_battery_low(&mut self, low: bool);
+
+    #[swift_bridge(swift_name="topBrightness")]
+    fn top_brightness(&self) -> f32;
+
+    #[swift_bridge(swift_name="bottomBrightness")]
+    fn bottom_brightness(&self) -> f32;
+
+    #[swift_bridge(swift_name="powerOffRequested")]
+    fn power_off_requested(&self) -> bool;
   }
 }
 
@@ -188,12 +206,39 @@ impl MobileEmulator {
     bus.arm7.extkeyin.remove(ExternalKeyInputRegister::PEN_DOWN);
   }
 
+  pub fn touch_screen_subpixel(&mut self, x: f32, y: f32) {
+    let ref mut bus = *self.nds.bus.borrow_mut();
+
+    bus.touchscreen.touch_screen_subpixel(x, y);
+    bus.arm7.extkeyin.remove(ExternalKeyInputRegister::PEN_DOWN);
+  }
+
+  pub fn set_touch_pressure(&mut self, pressure: f32) {
+    self.nds.bus.borrow_mut().touchscreen.set_pressure(pressure);
+  }
+
   pub fn release_screen(&mut self) {
     let ref mut bus = *self.nds.bus.borrow_mut();
 
     bus.arm7.extkeyin.insert(ExternalKeyInputRegister::PEN_DOWN);
   }
 
+  pub fn set_battery_low(&mut self, low: bool) {
+    self.nds.bus.borrow_mut().power_manager.set_battery_low(low);
+  }
+
+  pub fn top_brightness(&self) -> f32 {
+    self.nds.bus.borrow().power_manager.top_brightness()
+  }
+
+  pub fn bottom_brightness(&self) -> f32 {
+    self.nds.bus.borrow().power_manager.bottom_brightness()
+  }
+
+  pub fn power_off_requested(&self) -> bool {
+    self.nds.bus.borrow_mut().power_manager.take_power_off_request()
+  }
+
   pub fn audio_buffer_length(&self) -> usize {
     self.nds.bus.borrow().arm7.apu.audio_buffer.lock().unwrap().len()
   }
@@ -228,6 +273,8 @@ impl MobileEmulator {
       BackupType::None => unreachable!(),
       BackupType::Eeprom(eeprom) => eeprom.backup_file.buffer.as_ptr(),
       BackupType::Flash(flash) => flash.backup_file.buffer.as_ptr(),
+      BackupType::Infrared(infrared) => infrared.flash.backup_file.buffer.as_ptr(),
+      BackupType::Nand(nand) => nand.backup_file.buffer.as_ptr(),
     }
   }
 
@@ -237,6 +284,8 @@ impl MobileEmulator {
       BackupType::None => unreachable!(),
       BackupType::Eeprom(eeprom) => eeprom.backup_file.has_written = val,
       BackupType::Flash(flash) => flash.backup_file.has_written = val,
+      BackupType::Infrared(infrared) => infrared.flash.backup_file.has_written = val,
+      BackupType::Nand(nand) => nand.backup_file.has_written = val,
     }
   }
 
@@ -247,6 +296,8 @@ impl MobileEmulator {
       BackupType::None => false,
       BackupType::Eeprom(eeprom) => eeprom.backup_file.has_written,
       BackupType::Flash(flash) => flash.backup_file.has_written,
+      BackupType::Infrared(infrared) => infrared.flash.backup_file.has_written,
+      BackupType::Nand(nand) => nand.backup_file.has_written,
     }
   }
 
@@ -257,6 +308,8 @@ impl MobileEmulator {
       BackupType::None => unreachable!(),
       BackupType::Eeprom(eeprom) => eeprom.backup_file.buffer.len(),
       BackupType::Flash(flash) => flash.backup_file.buffer.len(),
+      BackupType::Infrared(infrared) => infrared.flash.backup_file.buffer.len(),
+      BackupType::Nand(nand) => nand.backup_file.buffer.len(),
     }
   }
 
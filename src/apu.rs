@@ -7,6 +7,7 @@ use std::{
 };
 
 use channel::Channel;
+use gba_sound::GbaSound;
 use registers::{
   sound_capture_control_register::SoundCaptureControlRegister,
   sound_control_register::{
@@ -22,6 +23,7 @@ use crate::scheduler::{
 
 pub mod registers;
 pub mod channel;
+pub mod gba_sound;
 
 pub const NUM_SAMPLES: usize = 8192*2;
 pub const DS_SAMPLE_RATE: usize = 32768;
@@ -113,6 +115,9 @@ pub struct APU {
   pub audio_buffer: Arc<Mutex<VecDeque<f32>>>,
   pub phase: f32,
   pub debug_on: bool,
+  pub gba_mode: bool,
+  pub gba_sound: GbaSound,
+  pub speakers_enabled: bool
 }
 
 impl APU {
@@ -124,7 +129,10 @@ impl APU {
       sndcapcnt: [SoundCaptureControlRegister::new(), SoundCaptureControlRegister::new()],
       audio_buffer,
       phase: 0.0,
-      debug_on: false
+      debug_on: false,
+      gba_mode: false,
+      gba_sound: GbaSound::new(),
+      speakers_enabled: true
     };
 
     scheduler.schedule(
@@ -136,6 +144,13 @@ impl APU {
   }
 
   fn resample(&mut self, sample: Sample<f32>) {
+    // the power manager can switch off or mute the sound amplifier
+    let sample = if self.speakers_enabled {
+      sample
+    } else {
+      Sample { left: 0.0, right: 0.0 }
+    };
+
     while self.phase < 1.0 {
       self.push_sample(sample);
 
@@ -163,6 +178,14 @@ impl APU {
   pub fn generate_samples(&mut self, scheduler: &mut Scheduler, cycles_left: usize) {
     scheduler.schedule(EventType::GenerateSample, CYCLES_PER_SAMPLE - cycles_left);
 
+    if self.gba_mode {
+      let sample = self.gba_sound.generate_sample();
+
+      self.resample(sample);
+
+      return;
+    }
+
     let mut mixer = Sample { left: 0.0, right: 0.0 };
     let mut ch1 = Sample { left: 0.0, right: 0.0 };
     let mut ch3 = Sample { left: 0.0, right: 0.0 };
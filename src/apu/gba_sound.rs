@@ -0,0 +1,712 @@
+use std::collections::VecDeque;
+
+use crate::cpu::dma::dma_channels::DmaChannels;
+
+use super::Sample;
+
+/*
+  The sound hardware of the GBA, which the ARM7 gets access to in GBA mode: the four
+  PSG channels inherited from the gameboy plus the two Direct Sound FIFOs. Samples are
+  generated at the DS sample rate (32768Hz), which is also the highest rate the
+  PSG channels can change at.
+ */
+
+const SAMPLE_RATE: f32 = 32768.0;
+
+// the frame sequencer ticks at 512Hz
+const SAMPLES_PER_FRAME_STEP: u32 = 64;
+
+const FIFO_CAPACITY: usize = 32;
+const FIFO_ADDRESSES: [u32; 2] = [0x400_00a0, 0x400_00a4];
+
+const DUTY_TABLE: [[bool; 8]; 4] = [
+  [false, false, false, false, false, false, false, true],
+  [true, false, false, false, false, false, false, true],
+  [true, false, false, false, false, true, true, true],
+  [false, true, true, true, true, true, true, false]
+];
+
+#[derive(Copy, Clone)]
+struct Envelope {
+  initial_volume: u8,
+  increase: bool,
+  step_time: u8,
+  volume: u8,
+  timer: u8
+}
+
+impl Envelope {
+  fn new() -> Self {
+    Self {
+      initial_volume: 0,
+      increase: false,
+      step_time: 0,
+      volume: 0,
+      timer: 0
+    }
+  }
+
+  fn write(&mut self, value: u8) {
+    self.step_time = value & 0x7;
+    self.increase = value & 0x8 != 0;
+    self.initial_volume = value >> 4;
+  }
+
+  fn restart(&mut self) {
+    self.volume = self.initial_volume;
+    self.timer = self.step_time;
+  }
+
+  fn step(&mut self) {
+    if self.step_time == 0 {
+      return;
+    }
+
+    self.timer = self.timer.saturating_sub(1);
+
+    if self.timer == 0 {
+      self.timer = self.step_time;
+
+      if self.increase && self.volume < 15 {
+        self.volume += 1;
+      } else if !self.increase && self.volume > 0 {
+        self.volume -= 1;
+      }
+    }
+  }
+
+  // the channel is muted entirely when it can't produce any sound
+  fn dac_enabled(&self) -> bool {
+    self.initial_volume != 0 || self.increase
+  }
+}
+
+#[derive(Copy, Clone)]
+struct LengthCounter {
+  counter: u16,
+  enabled: bool
+}
+
+impl LengthCounter {
+  fn new() -> Self {
+    Self {
+      counter: 0,
+      enabled: false
+    }
+  }
+
+  // returns false once the channel should be turned off
+  fn step(&mut self) -> bool {
+    if self.enabled && self.counter > 0 {
+      self.counter -= 1;
+
+      return self.counter != 0;
+    }
+
+    true
+  }
+}
+
+struct SquareChannel {
+  enabled: bool,
+  frequency: u16,
+  duty: usize,
+  phase: f32,
+  envelope: Envelope,
+  length: LengthCounter,
+  sweep_shift: u8,
+  sweep_decrease: bool,
+  sweep_time: u8,
+  sweep_timer: u8
+}
+
+impl SquareChannel {
+  fn new() -> Self {
+    Self {
+      enabled: false,
+      frequency: 0,
+      duty: 0,
+      phase: 0.0,
+      envelope: Envelope::new(),
+      length: LengthCounter::new(),
+      sweep_shift: 0,
+      sweep_decrease: false,
+      sweep_time: 0,
+      sweep_timer: 0
+    }
+  }
+
+  fn write_sweep(&mut self, value: u16) {
+    self.sweep_shift = (value & 0x7) as u8;
+    self.sweep_decrease = value & 0x8 != 0;
+    self.sweep_time = ((value >> 4) & 0x7) as u8;
+  }
+
+  fn write_duty_envelope(&mut self, value: u16) {
+    self.length.counter = 64 - (value & 0x3f);
+    self.duty = ((value >> 6) & 0x3) as usize;
+    self.envelope.write((value >> 8) as u8);
+
+    if !self.envelope.dac_enabled() {
+      self.enabled = false;
+    }
+  }
+
+  fn write_frequency(&mut self, value: u16) {
+    self.frequency = value & 0x7ff;
+    self.length.enabled = value & (1 << 14) != 0;
+
+    if value & (1 << 15) != 0 {
+      self.enabled = self.envelope.dac_enabled();
+      self.envelope.restart();
+      self.sweep_timer = self.sweep_time;
+
+      if self.length.counter == 0 {
+        self.length.counter = 64;
+      }
+    }
+  }
+
+  fn step_sweep(&mut self) {
+    if self.sweep_time == 0 {
+      return;
+    }
+
+    self.sweep_timer = self.sweep_timer.saturating_sub(1);
+
+    if self.sweep_timer == 0 {
+      self.sweep_timer = self.sweep_time;
+
+      let delta = self.frequency >> self.sweep_shift;
+
+      if self.sweep_decrease {
+        self.frequency = self.frequency.saturating_sub(delta);
+      } else if self.frequency + delta > 0x7ff {
+        self.enabled = false;
+      } else {
+        self.frequency += delta;
+      }
+    }
+  }
+
+  fn generate_sample(&mut self) -> i16 {
+    if !self.enabled {
+      return 0;
+    }
+
+    // frequency is 131072/(2048-n) Hz, with 8 duty steps per period
+    self.phase += 32.0 * 32768.0 / SAMPLE_RATE / (2048 - self.frequency) as f32;
+    self.phase %= 8.0;
+
+    let volume = self.envelope.volume as i16;
+
+    if DUTY_TABLE[self.duty][self.phase as usize] {
+      volume
+    } else {
+      -volume
+    }
+  }
+}
+
+struct WaveChannel {
+  enabled: bool,
+  playback: bool,
+  two_banks: bool,
+  bank: usize,
+  frequency: u16,
+  volume_shift: u8,
+  force_75: bool,
+  length: LengthCounter,
+  position: f32,
+  wave_ram: [u8; 0x20]
+}
+
+impl WaveChannel {
+  fn new() -> Self {
+    Self {
+      enabled: false,
+      playback: false,
+      two_banks: false,
+      bank: 0,
+      frequency: 0,
+      volume_shift: 0,
+      force_75: false,
+      length: LengthCounter::new(),
+      position: 0.0,
+      wave_ram: [0; 0x20]
+    }
+  }
+
+  fn write_select(&mut self, value: u16) {
+    self.two_banks = value & (1 << 5) != 0;
+    self.bank = ((value >> 6) & 0b1) as usize;
+    self.playback = value & (1 << 7) != 0;
+
+    if !self.playback {
+      self.enabled = false;
+    }
+  }
+
+  fn write_length_volume(&mut self, value: u16) {
+    self.length.counter = 256 - (value & 0xff);
+    self.volume_shift = ((value >> 13) & 0x3) as u8;
+    self.force_75 = value & (1 << 15) != 0;
+  }
+
+  fn write_frequency(&mut self, value: u16) {
+    self.frequency = value & 0x7ff;
+    self.length.enabled = value & (1 << 14) != 0;
+
+    if value & (1 << 15) != 0 {
+      self.enabled = self.playback;
+      self.position = 0.0;
+
+      if self.length.counter == 0 {
+        self.length.counter = 256;
+      }
+    }
+  }
+
+  // the cpu can only access the bank that isn't currently being played
+  fn wave_ram_offset(&self, address: u32) -> usize {
+    (1 - self.bank) * 0x10 + (address & 0xf) as usize
+  }
+
+  fn generate_sample(&mut self) -> i16 {
+    if !self.enabled {
+      return 0;
+    }
+
+    let num_samples = if self.two_banks { 64.0 } else { 32.0 };
+
+    // each sample plays for 2097152/(2048-n) Hz
+    self.position += 64.0 * 32768.0 / SAMPLE_RATE / (2048 - self.frequency) as f32;
+    self.position %= num_samples;
+
+    let index = self.position as usize;
+
+    let bank = if self.two_banks {
+      (self.bank + index / 32) & 0b1
+    } else {
+      self.bank
+    };
+
+    let byte = self.wave_ram[bank * 0x10 + (index % 32) / 2];
+
+    let sample = if index & 0b1 == 0 {
+      byte >> 4
+    } else {
+      byte & 0xf
+    } as i16;
+
+    // center the 4 bit samples around 0
+    let sample = sample * 2 - 15;
+
+    if self.force_75 {
+      sample * 3 / 4
+    } else {
+      match self.volume_shift {
+        0 => 0,
+        1 => sample,
+        2 => sample / 2,
+        3 => sample / 4,
+        _ => unreachable!()
+      }
+    }
+  }
+}
+
+struct NoiseChannel {
+  enabled: bool,
+  envelope: Envelope,
+  length: LengthCounter,
+  lfsr: u16,
+  narrow: bool,
+  divider: u8,
+  shift: u8,
+  timer: f32
+}
+
+impl NoiseChannel {
+  fn new() -> Self {
+    Self {
+      enabled: false,
+      envelope: Envelope::new(),
+      length: LengthCounter::new(),
+      lfsr: 0x7fff,
+      narrow: false,
+      divider: 0,
+      shift: 0,
+      timer: 0.0
+    }
+  }
+
+  fn write_length_envelope(&mut self, value: u16) {
+    self.length.counter = 64 - (value & 0x3f);
+    self.envelope.write((value >> 8) as u8);
+
+    if !self.envelope.dac_enabled() {
+      self.enabled = false;
+    }
+  }
+
+  fn write_frequency(&mut self, value: u16) {
+    self.divider = (value & 0x7) as u8;
+    self.narrow = value & 0x8 != 0;
+    self.shift = ((value >> 4) & 0xf) as u8;
+    self.length.enabled = value & (1 << 14) != 0;
+
+    if value & (1 << 15) != 0 {
+      self.enabled = self.envelope.dac_enabled();
+      self.envelope.restart();
+      self.lfsr = if self.narrow { 0x7f } else { 0x7fff };
+
+      if self.length.counter == 0 {
+        self.length.counter = 64;
+      }
+    }
+  }
+
+  fn generate_sample(&mut self) -> i16 {
+    if !self.enabled {
+      return 0;
+    }
+
+    // 524288 Hz / r / 2^(s+1), with r=0 treated as r=0.5
+    let divider = if self.divider == 0 { 0.5 } else { self.divider as f32 };
+    let frequency = 524288.0 / divider / (1 << (self.shift + 1)) as f32;
+
+    self.timer += frequency / SAMPLE_RATE;
+
+    while self.timer >= 1.0 {
+      self.timer -= 1.0;
+
+      let carry = self.lfsr & 0b1;
+
+      self.lfsr >>= 1;
+
+      if carry != 0 {
+        self.lfsr ^= if self.narrow { 0x60 } else { 0x6000 };
+      }
+    }
+
+    let volume = self.envelope.volume as i16;
+
+    if self.lfsr & 0b1 == 0 {
+      volume
+    } else {
+      -volume
+    }
+  }
+}
+
+struct DirectSoundFifo {
+  samples: VecDeque<i8>,
+  current_sample: i8,
+  full_volume: bool,
+  enable_right: bool,
+  enable_left: bool,
+  timer_id: usize
+}
+
+impl DirectSoundFifo {
+  fn new() -> Self {
+    Self {
+      samples: VecDeque::with_capacity(FIFO_CAPACITY),
+      current_sample: 0,
+      full_volume: false,
+      enable_right: false,
+      enable_left: false,
+      timer_id: 0
+    }
+  }
+
+  fn write(&mut self, value: u32, num_bytes: usize) {
+    for byte in value.to_le_bytes().iter().take(num_bytes) {
+      if self.samples.len() < FIFO_CAPACITY {
+        self.samples.push_back(*byte as i8);
+      }
+    }
+  }
+
+  fn write_control(&mut self, value: u16) {
+    self.full_volume = value & 0b1 != 0;
+    self.enable_right = value & 0x2 != 0;
+    self.enable_left = value & 0x4 != 0;
+    self.timer_id = ((value >> 3) & 0b1) as usize;
+
+    if value & 0x10 != 0 {
+      self.samples.clear();
+      self.current_sample = 0;
+    }
+  }
+
+  fn get_sample(&self) -> f32 {
+    let volume = if self.full_volume { 1.0 } else { 0.5 };
+
+    self.current_sample as f32 / 128.0 * volume
+  }
+}
+
+pub struct GbaSound {
+  square: [SquareChannel; 2],
+  wave: WaveChannel,
+  noise: NoiseChannel,
+  fifos: [DirectSoundFifo; 2],
+  registers: [u16; 0x30],
+  master_enable: bool,
+  frame_step: u32,
+  frame_samples: u32
+}
+
+impl Default for GbaSound {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl GbaSound {
+  pub fn new() -> Self {
+    Self {
+      square: [SquareChannel::new(), SquareChannel::new()],
+      wave: WaveChannel::new(),
+      noise: NoiseChannel::new(),
+      fifos: [DirectSoundFifo::new(), DirectSoundFifo::new()],
+      registers: [0; 0x30],
+      master_enable: false,
+      frame_step: 0,
+      frame_samples: 0
+    }
+  }
+
+  pub fn read(&self, address: u32) -> u16 {
+    match address {
+      0x400_0084 => {
+        (self.master_enable as u16) << 7
+          | self.square[0].enabled as u16
+          | (self.square[1].enabled as u16) << 1
+          | (self.wave.enabled as u16) << 2
+          | (self.noise.enabled as u16) << 3
+      }
+      0x400_0090..=0x400_009f => {
+        let offset = self.wave.wave_ram_offset(address);
+
+        self.wave.wave_ram[offset] as u16 | (self.wave.wave_ram[offset + 1] as u16) << 8
+      }
+      0x400_0060..=0x400_008f => {
+        let value = self.registers[((address - 0x400_0060) / 2) as usize];
+
+        // lengths, frequencies and the restart bits are write only
+        match address {
+          0x400_0062 | 0x400_0068 | 0x400_0078 => value & 0xffc0,
+          0x400_0064 | 0x400_006c | 0x400_0074 | 0x400_007c => value & 0x4000,
+          0x400_0072 => value & 0xe000,
+          _ => value
+        }
+      }
+      _ => 0
+    }
+  }
+
+  pub fn write_8(&mut self, address: u32, value: u8) {
+    match address {
+      0x400_0090..=0x400_009f => {
+        let offset = self.wave.wave_ram_offset(address & !(0b1)) + (address & 0b1) as usize;
+
+        self.wave.wave_ram[offset] = value;
+      }
+      0x400_00a0..=0x400_00a7 => self.write_fifo(address, value as u32, 1),
+      0x400_0060..=0x400_008f => {
+        let shift = (address & 0b1) * 8;
+        let register = self.registers[((address & !(0b1)) - 0x400_0060) as usize / 2];
+
+        let mut value = register & !(0xff << shift) | (value as u16) << shift;
+
+        // only a write to the upper byte can restart a channel
+        if shift == 0 {
+          value &= 0x7fff;
+        }
+
+        self.write(address & !(0b1), value);
+      }
+      _ => ()
+    }
+  }
+
+  pub fn write(&mut self, address: u32, value: u16) {
+    // every register apart from the master enable is locked while the sound hardware is off
+    if !self.master_enable && address != 0x400_0084 && !(0x400_0088..=0x400_00a7).contains(&address) {
+      return;
+    }
+
+    if (0x400_0060..=0x400_008f).contains(&address) {
+      self.registers[((address - 0x400_0060) / 2) as usize] = value;
+    }
+
+    match address {
+      0x400_0060 => self.square[0].write_sweep(value),
+      0x400_0062 => self.square[0].write_duty_envelope(value),
+      0x400_0064 => self.square[0].write_frequency(value),
+      0x400_0068 => self.square[1].write_duty_envelope(value),
+      0x400_006c => self.square[1].write_frequency(value),
+      0x400_0070 => self.wave.write_select(value),
+      0x400_0072 => self.wave.write_length_volume(value),
+      0x400_0074 => self.wave.write_frequency(value),
+      0x400_0078 => self.noise.write_length_envelope(value),
+      0x400_007c => self.noise.write_frequency(value),
+      0x400_0082 => {
+        self.fifos[0].write_control(((value >> 2) & 0x1) | ((value >> 7) & 0x1e));
+        self.fifos[1].write_control(((value >> 3) & 0x1) | ((value >> 11) & 0x1e));
+      }
+      0x400_0084 => {
+        self.master_enable = value & 0x80 != 0;
+
+        if !self.master_enable {
+          self.reset_psg();
+        }
+      }
+      0x400_0090..=0x400_009f => {
+        let offset = self.wave.wave_ram_offset(address);
+
+        self.wave.wave_ram[offset] = value as u8;
+        self.wave.wave_ram[offset + 1] = (value >> 8) as u8;
+      }
+      0x400_00a0..=0x400_00a7 => self.write_fifo(address, value as u32, 2),
+      _ => ()
+    }
+  }
+
+  pub fn write_fifo(&mut self, address: u32, value: u32, num_bytes: usize) {
+    match address {
+      0x400_00a0..=0x400_00a3 => self.fifos[0].write(value, num_bytes),
+      0x400_00a4..=0x400_00a7 => self.fifos[1].write(value, num_bytes),
+      _ => unreachable!()
+    }
+  }
+
+  fn reset_psg(&mut self) {
+    let wave_ram = self.wave.wave_ram;
+
+    self.square = [SquareChannel::new(), SquareChannel::new()];
+    self.wave = WaveChannel::new();
+    self.noise = NoiseChannel::new();
+
+    // wave ram survives the sound hardware being turned off
+    self.wave.wave_ram = wave_ram;
+
+    // the psg registers and SOUNDCNT_L are cleared as well
+    self.registers[0..=0x10].fill(0);
+  }
+
+  // the fifos play back a new sample every time their timer overflows
+  pub fn handle_timer_overflow(&mut self, timer_id: usize, dma: &mut DmaChannels) {
+    if !self.master_enable {
+      return;
+    }
+
+    for (fifo, address) in self.fifos.iter_mut().zip(FIFO_ADDRESSES) {
+      if fifo.timer_id != timer_id {
+        continue;
+      }
+
+      if let Some(sample) = fifo.samples.pop_front() {
+        fifo.current_sample = sample;
+      }
+
+      if fifo.samples.len() <= FIFO_CAPACITY / 2 {
+        dma.notify_apu_event(address);
+      }
+    }
+  }
+
+  /*
+    the frame sequencer clocks the length counters at 256Hz, the sweep at 128Hz
+    and the envelopes at 64Hz
+   */
+  fn step_frame_sequencer(&mut self) {
+    self.frame_samples += 1;
+
+    if self.frame_samples < SAMPLES_PER_FRAME_STEP {
+      return;
+    }
+
+    self.frame_samples = 0;
+
+    if self.frame_step & 0b1 == 0 {
+      self.square[0].enabled &= self.square[0].length.step();
+      self.square[1].enabled &= self.square[1].length.step();
+      self.wave.enabled &= self.wave.length.step();
+      self.noise.enabled &= self.noise.length.step();
+    }
+
+    if self.frame_step == 2 || self.frame_step == 6 {
+      self.square[0].step_sweep();
+    }
+
+    if self.frame_step == 7 {
+      self.square[0].envelope.step();
+      self.square[1].envelope.step();
+      self.noise.envelope.step();
+    }
+
+    self.frame_step = (self.frame_step + 1) % 8;
+  }
+
+  pub fn generate_sample(&mut self) -> Sample<f32> {
+    if !self.master_enable {
+      return Sample { left: 0.0, right: 0.0 };
+    }
+
+    self.step_frame_sequencer();
+
+    let psg_samples = [
+      self.square[0].generate_sample(),
+      self.square[1].generate_sample(),
+      self.wave.generate_sample(),
+      self.noise.generate_sample()
+    ];
+
+    let soundcnt_l = self.registers[0x10];
+    let soundcnt_h = self.registers[0x11];
+
+    let mut psg_right = 0;
+    let mut psg_left = 0;
+
+    for (i, sample) in psg_samples.iter().enumerate() {
+      if (soundcnt_l >> (8 + i)) & 0b1 == 1 {
+        psg_right += sample;
+      }
+      if (soundcnt_l >> (12 + i)) & 0b1 == 1 {
+        psg_left += sample;
+      }
+    }
+
+    let psg_ratio = match soundcnt_h & 0x3 {
+      0 => 0.25,
+      1 => 0.5,
+      _ => 1.0
+    };
+
+    // 4 channels at a max volume of 15, times the master volume of up to 8
+    let psg_scale = psg_ratio / (4.0 * 15.0 * 8.0);
+
+    let mut sample = Sample {
+      right: psg_right as f32 * ((soundcnt_l & 0x7) + 1) as f32 * psg_scale,
+      left: psg_left as f32 * (((soundcnt_l >> 4) & 0x7) + 1) as f32 * psg_scale
+    };
+
+    for fifo in &self.fifos {
+      if fifo.enable_right {
+        sample.right += fifo.get_sample();
+      }
+      if fifo.enable_left {
+        sample.left += fifo.get_sample();
+      }
+    }
+
+    sample.left = (sample.left / 2.0).clamp(-1.0, 1.0);
+    sample.right = (sample.right / 2.0).clamp(-1.0, 1.0);
+
+    sample
+  }
+}
@@ -7,7 +7,11 @@
 
 use std::{cell::RefCell, collections::HashMap, rc::Rc};
 
-use bus::{Bus, HaltMode};
+use bus::{
+  debug_output::{DebugContext, DEBUG_STRING_PARAMS},
+  Bus,
+  HaltMode
+};
 
 pub mod arm_instructions;
 pub mod thumb_instructions;
@@ -227,6 +231,16 @@ impl<const IS_ARM9: bool> CPU<IS_ARM9> {
     }
   }
 
+  // boots a gba cartridge directly, with the state the gba bios leaves behind
+  pub fn skip_gba_bios(&mut self) {
+    self.pc = 0x800_0000;
+    self.cpsr = PSRRegister::from_bits_retain(0x1f);
+
+    self.r[13] = 0x0300_7f00;
+    self.r13_banks[2] = 0x0300_7fa0;
+    self.r13_banks[3] = 0x0300_7fe0;
+  }
+
   pub fn execute_thumb(&mut self, instr: u16) -> Option<MemoryAccess> {
     let handler_fn = self.thumb_lut[(instr >> 8) as usize];
 
@@ -254,7 +268,7 @@ impl<const IS_ARM9: bool> CPU<IS_ARM9> {
     {
       if self.bus.borrow().debug_on {
         if !self.found.contains_key(&instruction_pc) {
-          println!("attempting to execute instruction {:032b} at address {:X}", instruction, instruction_pc);
+          println!("attempting to execute instruction {:032b} at address {:X}{}", instruction, instruction_pc, self.bus.borrow().describe_address(instruction_pc, IS_ARM9));
           self.found.insert(self.pc.wrapping_sub(8), true);
         }
       }
@@ -302,6 +316,13 @@ impl<const IS_ARM9: bool> CPU<IS_ARM9> {
 
     if interrupt_master_enable && (interrupt_request.bits() & interrupt_enable.bits()) != 0 {
       self.trigger_irq();
+    } else if !IS_ARM9 && (interrupt_request.bits() & interrupt_enable.bits()) != 0 {
+      // the arm7 wakes up from halt even when interrupts are disabled through IME, in ds and gba mode alike
+      let ref mut bus = *self.bus.borrow_mut();
+
+      if bus.arm7.haltcnt == HaltMode::Halt {
+        bus.arm7.haltcnt = HaltMode::None;
+      }
     }
   }
 
@@ -388,7 +409,7 @@ impl<const IS_ARM9: bool> CPU<IS_ARM9> {
   }
 
   pub fn store_16(&mut self, address: u32, value: u16, access: MemoryAccess) {
-    self.update_cycles(address, access, MemoryWidth::Width8);
+    self.update_cycles(address, access, MemoryWidth::Width16);
     let ref mut bus = *self.bus.borrow_mut();
 
     if !IS_ARM9 {
@@ -399,7 +420,12 @@ impl<const IS_ARM9: bool> CPU<IS_ARM9> {
   }
 
   pub fn store_32(&mut self, address: u32, value: u32, access: MemoryAccess) {
-    self.update_cycles(address, access, MemoryWidth::Width8);
+    self.update_cycles(address, access, MemoryWidth::Width32);
+
+    if address == DEBUG_STRING_PARAMS {
+      self.print_debug_message(value);
+    }
+
     let ref mut bus = *self.bus.borrow_mut();
 
     if !IS_ARM9 {
@@ -409,19 +435,50 @@ impl<const IS_ARM9: bool> CPU<IS_ARM9> {
     }
   }
 
-  fn update_cycles(&mut self, _address: u32,  _access: MemoryAccess, _width: MemoryWidth) {
-    // TODO: fix this
-    // let page = ((address >> 24) & 0xf) as usize;
-    // let cycles = match width {
-    //   MemoryWidth::Width8 | MemoryWidth::Width16 => match access {
-    //     MemoryAccess::NonSequential => self.bus.cycle_luts.n_cycles_16[page],
-    //     MemoryAccess::Sequential => self.bus.cycle_luts.s_cycles_16[page]
-    //   }
-    //   MemoryWidth::Width32 => match access {
-    //     MemoryAccess::NonSequential => self.bus.cycle_luts.n_cycles_32[page],
-    //     MemoryAccess::Sequential => self.bus.cycle_luts.s_cycles_32[page],
-    //   }
-    // };
+  // the %params% in the message refer to this cpu's state at the time of the write
+  fn print_debug_message(&mut self, address: u32) {
+    let ref mut bus = *self.bus.borrow_mut();
+
+    let text = bus.read_debug_string(address, IS_ARM9);
+
+    let mut registers = [0; 16];
+
+    for (r, register) in registers.iter_mut().enumerate() {
+      *register = self.get_register(r);
+    }
+
+    let instruction_size = if self.cpsr.contains(PSRRegister::STATE_BIT) { 2 } else { 4 };
+
+    let context = DebugContext {
+      registers,
+      pc: self.pc.wrapping_sub(instruction_size * 2),
+      scanline: bus.gpu.vcount,
+      frame: bus.gpu.frame_count,
+      cycles: self.cycles
+    };
+
+    let message = bus.debug_output.expand_params(IS_ARM9, &text, &context);
+
+    bus.debug_output.print(IS_ARM9, &message);
+  }
+
+  fn update_cycles(&mut self, address: u32,  access: MemoryAccess, width: MemoryWidth) {
+    if !IS_ARM9 && self.bus.borrow().gba_mode {
+      let cycles = self.bus.borrow().get_gba_cycles(address, access, matches!(width, MemoryWidth::Width32));
+
+      self.add_cycles(cycles);
+
+      return;
+    }
+
+    // the gba slot is slow enough that its waitstates matter, everything else is still 1 cycle for now
+    if (0x800_0000..=0xaff_ffff).contains(&address) {
+      let cycles = self.bus.borrow().get_gba_slot_cycles(address, access, matches!(width, MemoryWidth::Width32), IS_ARM9);
+
+      self.add_cycles(cycles);
+
+      return;
+    }
 
     self.add_cycles(1);
   }
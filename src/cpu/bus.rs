@@ -8,15 +8,21 @@ use std::{
   }
 };
 
-use crate::{apu::Sample, gpu::color::Color, number::Number};
+use crate::{apu::Sample, elf::SymbolTable, gpu::color::Color, number::Number};
 use backup_file::BackupFile;
 use cartridge::{
   Cartridge, Header, CHIP_ID
 };
 use cp15::CP15;
+use debug_output::{DebugOutput, DEBUG_CHAR_OUT, DEBUG_STRING_RAW, MAX_STRING_LENGTH};
+use dldi::{DldiCommand, SdCard, DLDI_BUFFER, DLDI_COMMAND, DLDI_COUNT, DLDI_RESULT, DLDI_SECTOR};
+use gba_cartridge::GbaCartridge;
+use slot2::{RumbleCallback, Slot2Device};
 use num_integer::Roots;
+use power_manager::PowerManager;
 use spi::SPI;
 use touchscreen::Touchscreen;
+use wifi::Wifi;
 
 use crate::{
   apu::{
@@ -25,13 +31,13 @@ use crate::{
     APU
   },
   gpu::GPU,
-  scheduler::Scheduler
+  scheduler::{EventType, Scheduler}
 };
 
 use super::{
   dma::{
     dma_channel::{
-      registers::dma_control_register::DmaControlRegister,
+      registers::dma_control_register::{DmaControlRegister, DmaTiming},
       DmaParams
     },
     dma_channels::DmaChannels
@@ -54,6 +60,7 @@ use super::{
     },
     ipc_sync_register::IPCSyncRegister,
     key_input_register::KeyInputRegister,
+    key_interrupt_control_register::KeyInterruptControlRegister,
     real_time_clock_register::RealTimeClockRegister,
     spi_control_register::{
       DeviceSelect,
@@ -63,8 +70,10 @@ use super::{
       BitMode,
       SquareRootControlRegister
     },
+    waitstate_control_register::WaitstateControlRegister,
     wram_control_register::WRAMControlRegister
   },
+  cycle_lookup_tables::CycleLookupTables,
   timers::Timers,
   MemoryAccess
 };
@@ -76,8 +85,17 @@ pub mod spi;
 pub mod flash;
 pub mod cartridge;
 pub mod touchscreen;
+pub mod power_manager;
 pub mod eeprom;
+pub mod nand;
+pub mod infrared;
+pub mod gba_cartridge;
+pub mod slot2;
+pub mod gba;
 pub mod backup_file;
+pub mod wifi;
+pub mod debug_output;
+pub mod dldi;
 
 pub const ITCM_SIZE: usize = 0x8000;
 pub const DTCM_SIZE: usize = 0x4000;
@@ -85,6 +103,10 @@ const MAIN_MEMORY_SIZE: usize = 0x40_0000;
 const WRAM_SIZE: usize = 0x1_0000;
 const SHARED_WRAM_SIZE: usize = 0x8000;
 
+// devkitPro's __system_argv struct, along with the magic saying a loader filled it in
+const ARGV_ADDRESS: u32 = 0x2ff_fe70;
+const ARGV_MAGIC: u32 = 0x5f61_7267;
+
 
 #[derive(PartialEq, Copy, Clone)]
 pub enum HaltMode {
@@ -113,7 +135,9 @@ pub struct Arm9Bus {
   div_remainder: u64,
   sqrt_param: u64,
   sqrt_result: u32,
-  pub dma_fill: [u32; 4]
+  pub dma_fill: [u32; 4],
+  pub keycnt: KeyInterruptControlRegister,
+  pub symbols: SymbolTable
 }
 
 pub struct Arm7Bus {
@@ -131,7 +155,10 @@ pub struct Arm7Bus {
   pub extkeyin: ExternalKeyInputRegister,
   pub haltcnt: HaltMode,
   pub apu: APU,
-  pub rtc: RealTimeClockRegister
+  pub rtc: RealTimeClockRegister,
+  pub keycnt: KeyInterruptControlRegister,
+  pub wifi: Wifi,
+  pub symbols: SymbolTable
 }
 
 pub struct Bus {
@@ -144,14 +171,27 @@ pub struct Bus {
   shared_wram: Box<[u8]>,
   pub spi: SPI,
   pub cartridge: Cartridge,
+  pub gba_cartridge: Option<GbaCartridge>,
+  pub slot2_device: Option<Box<dyn Slot2Device>>,
+  rumble_callback: Option<RumbleCallback>,
+  motor_on: bool,
   pub wramcnt: WRAMControlRegister,
   pub key_input_register: KeyInputRegister,
+  previous_key_input: u16,
   pub scheduler: Scheduler,
   exmem: ExternalMemory,
   pub touchscreen: Touchscreen,
+  pub power_manager: PowerManager,
+  pub debug_output: DebugOutput,
+  pub sd_card: Option<SdCard>,
   pub debug_on: bool,
   pub game_icon: Box<[u8]>,
-  pub frame_cycles: usize
+  pub frame_cycles: usize,
+  pub gba_mode: bool,
+  pub gba_bios: Vec<u8>,
+  pub argv: Vec<String>,
+  pub waitcnt: WaitstateControlRegister,
+  pub cycle_luts: CycleLookupTables
 }
 
 impl Bus {
@@ -176,6 +216,9 @@ impl Bus {
       panic!("Please provide either firmware bytes or a path to the firmware");
     };
 
+    let spi = SPI::new(BackupFile::new(firmware_path, firmware_bytes, capacity as usize, false));
+    let touchscreen = Touchscreen::new(&spi.firmware.backup_file.buffer);
+
     Self {
       arm9: Arm9Bus {
         timers: Timers::new(true),
@@ -197,18 +240,28 @@ impl Bus {
         div_result: 0,
         div_remainder: 0,
         dma_fill: [0; 4],
+        keycnt: KeyInterruptControlRegister::new(),
+        symbols: SymbolTable::default()
       },
       shared_wram: vec![0; SHARED_WRAM_SIZE].into_boxed_slice(),
       main_memory: vec![0; MAIN_MEMORY_SIZE].into_boxed_slice(),
       itcm: vec![0; ITCM_SIZE].into_boxed_slice(),
       dtcm: vec![0; DTCM_SIZE].into_boxed_slice(),
-      spi: SPI::new(BackupFile::new(firmware_path, firmware_bytes, capacity as usize, false)),
+      spi,
       cartridge: Cartridge::new(&bios7_bytes),
+      gba_cartridge: None,
+      slot2_device: None,
+      rumble_callback: None,
+      motor_on: false,
       wramcnt: WRAMControlRegister::new(),
       gpu: GPU::new(&mut scheduler),
       key_input_register: KeyInputRegister::from_bits_truncate(0x3ff),
+      previous_key_input: 0x3ff,
       exmem: ExternalMemory::new(),
-      touchscreen: Touchscreen::new(),
+      touchscreen,
+      power_manager: PowerManager::new(),
+      debug_output: DebugOutput::new(),
+      sd_card: None,
       frame_cycles: 0,
       arm7: Arm7Bus {
         timers: Timers::new(false),
@@ -225,16 +278,32 @@ impl Bus {
         extkeyin: ExternalKeyInputRegister::new(),
         haltcnt: HaltMode::None,
         apu: APU::new(&mut scheduler, audio_buffer),
-        rtc: RealTimeClockRegister::new()
+        rtc: RealTimeClockRegister::new(&mut scheduler),
+        keycnt: KeyInterruptControlRegister::new(),
+        wifi: Wifi::new(&mut scheduler),
+        symbols: SymbolTable::default()
       },
       scheduler,
       debug_on: false,
-      game_icon: vec![0; 32 * 32 * 4].into_boxed_slice()
+      game_icon: vec![0; 32 * 32 * 4].into_boxed_slice(),
+      gba_mode: false,
+      gba_bios: Vec::new(),
+      argv: Vec::new(),
+      waitcnt: WaitstateControlRegister::new(),
+      cycle_luts: CycleLookupTables::new()
     }
   }
 
   pub fn reset(&mut self) -> Self {
     let mut scheduler = Scheduler::new();
+
+    let spi = SPI::new(self.spi.firmware.backup_file.reset());
+    let touchscreen = Touchscreen::new(&spi.firmware.backup_file.buffer);
+
+    // the rtc is battery backed, so its clock keeps going through a reset
+    let mut rtc = RealTimeClockRegister::new(&mut scheduler);
+    rtc.set_clock(self.arm7.rtc.clock());
+
     Self {
       arm9: Arm9Bus {
         timers: Timers::new(true),
@@ -256,18 +325,32 @@ impl Bus {
         div_result: 0,
         div_remainder: 0,
         dma_fill: [0; 4],
+        keycnt: KeyInterruptControlRegister::new(),
+        // symbols belong to the loaded program, which a reset boots again
+        symbols: std::mem::take(&mut self.arm9.symbols)
       },
       shared_wram: vec![0; SHARED_WRAM_SIZE].into_boxed_slice(),
       main_memory: vec![0; MAIN_MEMORY_SIZE].into_boxed_slice(),
       itcm: vec![0; ITCM_SIZE].into_boxed_slice(),
       dtcm: vec![0; DTCM_SIZE].into_boxed_slice(),
-      spi: SPI::new(self.spi.firmware.backup_file.reset()),
+      spi,
       cartridge: Cartridge::new(&self.arm7.bios7),
+      // the gba cartridge stays inserted across resets
+      gba_cartridge: self.gba_cartridge.take(),
+      slot2_device: self.slot2_device.take(),
+      // the callback belongs to the frontend, and the device keeps its motor state
+      rumble_callback: self.rumble_callback.take(),
+      motor_on: self.motor_on,
       wramcnt: WRAMControlRegister::new(),
       gpu: GPU::new(&mut scheduler),
       key_input_register: KeyInputRegister::from_bits_truncate(0x3ff),
+      previous_key_input: 0x3ff,
       exmem: ExternalMemory::new(),
-      touchscreen: Touchscreen::new(),
+      touchscreen,
+      power_manager: PowerManager::new(),
+      debug_output: self.debug_output.reset(),
+      // the sd card stays inserted across resets
+      sd_card: self.sd_card.take(),
       arm7: Arm7Bus {
         timers: Timers::new(false),
         bios7: self.arm7.bios7.clone(),
@@ -283,12 +366,20 @@ impl Bus {
         extkeyin: ExternalKeyInputRegister::new(),
         haltcnt: HaltMode::None,
         apu: APU::new(&mut scheduler, self.arm7.apu.audio_buffer.clone()),
-        rtc: RealTimeClockRegister::new()
+        rtc,
+        keycnt: KeyInterruptControlRegister::new(),
+        wifi: Wifi::new(&mut scheduler),
+        symbols: std::mem::take(&mut self.arm7.symbols)
       },
       scheduler,
       debug_on: false,
       game_icon: vec![0; 32 * 32 * 4].into_boxed_slice(),
-      frame_cycles: 0
+      frame_cycles: 0,
+      gba_mode: false,
+      gba_bios: std::mem::take(&mut self.gba_bios),
+      argv: std::mem::take(&mut self.argv),
+      waitcnt: WaitstateControlRegister::new(),
+      cycle_luts: CycleLookupTables::new()
     }
   }
 
@@ -303,6 +394,13 @@ impl Bus {
   fn handle_dma(&mut self, params: &mut DmaParams, is_arm9: bool) -> u32 {
     let mut access = MemoryAccess::NonSequential;
     let mut cpu_cycles = 0;
+
+    // a gba cartridge sees dmas both in gba mode and from slot 2 in ds mode
+    if !params.fifo_mode {
+      if let Some(gba_cartridge) = &mut self.gba_cartridge {
+        gba_cartridge.notify_dma(params.destination_address, params.count);
+      }
+    }
     if params.fifo_mode {
       for _ in 0..4 {
         let (value, cycles) = self.load_32(params.source_address & !(0b11), access, is_arm9);
@@ -370,6 +468,11 @@ impl Bus {
             channel.dma_control.remove(DmaControlRegister::DMA_ENABLE);
           }
 
+          // main memory display dmas keep going until the display fifo has the whole line
+          if channel.running && channel.start_timing(channel.dma_control) == DmaTiming::MainMemoryDisplay && self.gpu.display_fifo.wants_data() {
+            channel.pending = true;
+          }
+
           if params.should_trigger_irq {
             self.arm9.interrupt_request.request_dma(i);
           }
@@ -464,6 +567,209 @@ impl Bus {
     cpu_cycles
   }
 
+  // frontends change the key input register directly, so key changes are picked up here once per step
+  pub fn check_keypad_interrupts(&mut self) {
+    let key_input = self.key_input_register.bits();
+
+    if key_input != self.previous_key_input {
+      self.arm9.keycnt.check_interrupt(self.previous_key_input, key_input, &mut self.arm9.interrupt_request);
+      self.arm7.keycnt.check_interrupt(self.previous_key_input, key_input, &mut self.arm7.interrupt_request);
+
+      self.previous_key_input = key_input;
+    }
+  }
+
+  // a condition that's already met when the irq gets enabled fires right away
+  pub fn write_keycnt(&mut self, value: u16, is_arm9: bool) {
+    let (keycnt, interrupt_request) = if is_arm9 {
+      (&mut self.arm9.keycnt, &mut self.arm9.interrupt_request)
+    } else {
+      (&mut self.arm7.keycnt, &mut self.arm7.interrupt_request)
+    };
+
+    keycnt.write(value);
+    keycnt.check_interrupt(0x3ff, self.key_input_register.bits(), interrupt_request);
+  }
+
+  // names the function an address is in for trace output, when the program came with symbols
+  pub fn describe_address(&self, address: u32, is_arm9: bool) -> String {
+    let symbols = if is_arm9 { &self.arm9.symbols } else { &self.arm7.symbols };
+
+    match symbols.describe(address) {
+      Some(name) => format!(" ({})", name),
+      None => String::new()
+    }
+  }
+
+  pub fn read_debug_string(&mut self, address: u32, is_arm9: bool) -> String {
+    let mut bytes = Vec::new();
+
+    for i in 0..MAX_STRING_LENGTH as u32 {
+      let byte = if is_arm9 {
+        self.arm9_mem_read_8(address + i)
+      } else {
+        self.arm7_mem_read_8(address + i)
+      };
+
+      if byte == 0 {
+        break;
+      }
+
+      bytes.push(byte);
+    }
+
+    String::from_utf8_lossy(&bytes).to_string()
+  }
+
+  // strings with %params% are picked up by the cpu instead, since it has the registers
+  pub fn write_debug_register(&mut self, address: u32, value: u32, is_arm9: bool) {
+    match address {
+      DEBUG_STRING_RAW => {
+        let message = self.read_debug_string(value, is_arm9);
+
+        self.debug_output.print(is_arm9, &message);
+      }
+      DEBUG_CHAR_OUT => self.debug_output.write_char(is_arm9, value as u8),
+      _ => ()
+    }
+  }
+
+  // swaps the rom's dldi stub for the driver that talks to the sd card, so libfat can find its files
+  pub fn patch_dldi(&mut self) {
+    if self.sd_card.is_none() {
+      return;
+    }
+
+    let arm9_rom_offset = self.cartridge.header.arm9_rom_offset as usize;
+    let arm9_size = self.cartridge.header.arm9_size as usize;
+
+    if dldi::patch_rom(&mut self.cartridge.rom, arm9_rom_offset, arm9_size) {
+      println!("patched dldi driver");
+    }
+  }
+
+  pub fn read_dldi_register(&self, address: u32) -> u32 {
+    let Some(sd_card) = &self.sd_card else {
+      return 0;
+    };
+
+    match address {
+      DLDI_SECTOR => sd_card.sector,
+      DLDI_COUNT => sd_card.count,
+      DLDI_BUFFER => sd_card.buffer,
+      DLDI_RESULT => sd_card.result,
+      _ => 0
+    }
+  }
+
+  pub fn write_dldi_register(&mut self, address: u32, value: u32) {
+    let Some(sd_card) = &mut self.sd_card else {
+      return;
+    };
+
+    match address {
+      DLDI_SECTOR => sd_card.sector = value,
+      DLDI_COUNT => sd_card.count = value,
+      DLDI_BUFFER => sd_card.buffer = value,
+      DLDI_COMMAND => {
+        let result = match DldiCommand::from(value) {
+          Some(DldiCommand::ReadSectors) => self.read_sd_sectors(),
+          Some(DldiCommand::WriteSectors) => self.write_sd_sectors(),
+          Some(_) => true,
+          None => {
+            println!("[WARN] unknown dldi command: {value}");
+            false
+          }
+        };
+
+        if let Some(sd_card) = &mut self.sd_card {
+          sd_card.result = result as u32;
+        }
+      }
+      _ => ()
+    }
+  }
+
+  fn read_sd_sectors(&mut self) -> bool {
+    let sd_card = self.sd_card.as_mut().unwrap();
+
+    let buffer = sd_card.buffer;
+
+    let Some(bytes) = sd_card.read_sectors(sd_card.sector, sd_card.count) else {
+      println!("[WARN] couldn't read sd card sectors");
+      return false;
+    };
+
+    for (i, byte) in bytes.iter().enumerate() {
+      self.arm9_mem_write_8(buffer.wrapping_add(i as u32), *byte);
+    }
+
+    true
+  }
+
+  fn write_sd_sectors(&mut self) -> bool {
+    let sd_card = self.sd_card.as_ref().unwrap();
+
+    let (sector, buffer) = (sd_card.sector, sd_card.buffer);
+
+    let Some(range) = sd_card.sector_range(sector, sd_card.count) else {
+      println!("[WARN] sd card write past the end of the image");
+      return false;
+    };
+
+    let bytes: Vec<u8> = (0..range.len() as u32).map(|i| self.arm9_mem_read_8(buffer.wrapping_add(i))).collect();
+
+    if !self.sd_card.as_mut().unwrap().write_sectors(sector, &bytes) {
+      println!("[WARN] couldn't write sd card sectors");
+      return false;
+    }
+
+    true
+  }
+
+  pub fn check_wifi_interrupt(&mut self) {
+    if self.arm7.wifi.irq_request {
+      self.arm7.wifi.irq_request = false;
+      self.arm7.interrupt_request.insert(InterruptRequestRegister::WIFI);
+    }
+  }
+
+  pub fn set_lid_closed(&mut self, closed: bool) {
+    let was_closed = self.lid_closed();
+
+    self.arm7.extkeyin.set(ExternalKeyInputRegister::HINGE_CLOSED, closed);
+
+    if was_closed && !closed {
+      self.arm7.interrupt_request.insert(InterruptRequestRegister::SCREENS_UNFOLDING);
+    }
+  }
+
+  pub fn lid_closed(&self) -> bool {
+    self.arm7.extkeyin.contains(ExternalKeyInputRegister::HINGE_CLOSED)
+  }
+
+  // sleep stops the whole system until an interrupt the arm7 has enabled comes in
+  pub fn check_sleep(&mut self) -> bool {
+    if self.arm7.haltcnt != HaltMode::Sleep {
+      return false;
+    }
+
+    // any enabled interrupt wakes the console back up, be it a key, opening the lid or an rtc alarm
+    if self.arm7.interrupt_enable.bits() & self.arm7.interrupt_request.bits() != 0 {
+      self.arm7.haltcnt = HaltMode::None;
+
+      // video, sound, timers and card transfers carry on from where they stopped
+      self.scheduler.resume_events();
+
+      return false;
+    }
+
+    // the main clock is stopped in sleep mode, so only the rtc keeps going
+    self.scheduler.pause_events(&[EventType::RtcTick]);
+
+    true
+  }
+
   pub fn write_haltcnt(&mut self, value: u8) {
     self.arm7.haltcnt = match (value >> 6) & 0x3 {
       0 => HaltMode::None,
@@ -566,6 +872,32 @@ impl Bus {
 
     self.arm9_mem_write_8(0x23FFC80, 0x5);
 
+    self.write_argv(arm9_ram_address + arm9_size);
+
+    self.cartridge.skip_bios();
+
+  }
+
+  /*
+    Passes arguments to homebrew the way devkitPro's loaders do. The arguments go right after the
+    arm9 binary, separated by nul bytes, and the struct at 0x2fffe70 points crt0 to them.
+   */
+  fn write_argv(&mut self, arm9_end: u32) {
+    if self.argv.is_empty() {
+      return;
+    }
+
+    let command_line: Vec<u8> = self.argv.iter().flat_map(|arg| arg.bytes().chain([0])).collect();
+
+    let address = (arm9_end + 3) & !0x3;
+
+    for (i, byte) in command_line.iter().enumerate() {
+      self.arm9_mem_write_8(address + i as u32, *byte);
+    }
+
+    self.arm9_mem_write_32(ARGV_ADDRESS, ARGV_MAGIC);
+    self.arm9_mem_write_32(ARGV_ADDRESS + 0x4, address);
+    self.arm9_mem_write_32(ARGV_ADDRESS + 0x8, command_line.len() as u32);
   }
 
   fn write_mirrored_values(&mut self, base_address: u32) {
@@ -588,9 +920,17 @@ impl Bus {
   pub fn write_spi_data(&mut self, value: u8) {
     if self.arm7.spicnt.spi_bus_enabled {
       match self.arm7.spicnt.device {
-        DeviceSelect::Touchscreen => self.touchscreen.write(value, self.frame_cycles),
+        DeviceSelect::Touchscreen => {
+          let pen_down = !self.arm7.extkeyin.contains(ExternalKeyInputRegister::PEN_DOWN);
+
+          self.touchscreen.write(value, self.frame_cycles, pen_down);
+        }
         DeviceSelect::Firmware => self.spi.firmware.write(value, self.arm7.spicnt.chipselect_hold),
-        _ => ()
+        DeviceSelect::PowerManager => {
+          self.power_manager.write(value, self.arm7.spicnt.chipselect_hold);
+
+          self.arm7.apu.speakers_enabled = self.power_manager.speakers_enabled();
+        }
       }
     }
   }
@@ -600,46 +940,201 @@ impl Bus {
       return match self.arm7.spicnt.device {
         DeviceSelect::Firmware => self.spi.firmware.read(),
         DeviceSelect::Touchscreen => self.touchscreen.read(),
-        _ => 0
+        DeviceSelect::PowerManager => self.power_manager.read()
       }
     }
     0
   }
 
-  pub fn read_gba_rom<T: Number>(&self, address: u32, is_arm9: bool) -> T {
+  fn has_gba_access(&self, is_arm9: bool) -> bool {
+    is_arm9 && self.exmem.gba_access_rights == AccessRights::Arm9 || !is_arm9 && self.exmem.gba_access_rights == AccessRights::Arm7
+  }
+
+  // there's only one slot 2, so inserting a gba cartridge removes any accessory and vice versa
+  pub fn load_gba_rom(&mut self, rom: Vec<u8>, save_path: Option<PathBuf>, save_bytes: Option<Vec<u8>>) {
+    self.slot2_device = None;
+    self.gba_cartridge = Some(GbaCartridge::new(rom, save_path, save_bytes));
+
+    self.update_motor();
+  }
+
+  pub fn eject_gba_rom(&mut self) {
+    self.gba_cartridge = None;
+  }
+
+  pub fn insert_slot2_device(&mut self, device: Box<dyn Slot2Device>) {
+    self.gba_cartridge = None;
+    self.slot2_device = Some(device);
+
+    self.update_motor();
+  }
+
+  pub fn eject_slot2_device(&mut self) {
+    self.slot2_device = None;
+
+    self.update_motor();
+  }
+
+  pub fn set_rumble_callback(&mut self, callback: RumbleCallback) {
+    self.rumble_callback = Some(callback);
+  }
+
+  // lets the frontend know when the motor changes, including when the rumble pak gets pulled out while it's running
+  fn update_motor(&mut self) {
+    let motor_on = self.slot2_device.as_ref().is_some_and(|device| device.motor_on());
+
+    if motor_on != self.motor_on {
+      self.motor_on = motor_on;
+
+      if let Some(callback) = &mut self.rumble_callback {
+        callback(motor_on);
+      }
+    }
+  }
+
+  /*
+    waitstates for the gba slot, in arm7 cycles:
+      sram and rom 1st access: 10, 8, 6 or 18 cycles
+      rom 2nd access: 6 or 4 cycles
+    the rom bus is 16 bits wide, so 32 bit accesses need two of them
+   */
+  pub fn get_gba_slot_cycles(&self, address: u32, access: MemoryAccess, is_32bit: bool, is_arm9: bool) -> usize {
+    let exmemcnt = if is_arm9 {
+      &self.exmem.arm9_exmem
+    } else {
+      &self.exmem.arm7_exmem
+    };
+
+    let access_time = |value: u16| match value {
+      0 => 10,
+      1 => 8,
+      2 => 6,
+      3 => 18,
+      _ => unreachable!()
+    };
+
+    let cycles = if address >= 0xa00_0000 {
+      access_time(exmemcnt.gba_sram_access_time)
+    } else {
+      let first = access_time(exmemcnt.gba_rom_1st_access);
+      let second = if exmemcnt.gba_rom_2nd_access == 0 { 6 } else { 4 };
+
+      match (access, is_32bit) {
+        (MemoryAccess::NonSequential, false) => first,
+        (MemoryAccess::NonSequential, true) => first + second,
+        (MemoryAccess::Sequential, false) => second,
+        (MemoryAccess::Sequential, true) => second * 2
+      }
+    };
+
+    if is_arm9 {
+      cycles * 2
+    } else {
+      cycles
+    }
+  }
+
+  pub fn read_gba_rom<T: Number>(&mut self, address: u32, is_arm9: bool) -> T {
+    if !self.has_gba_access(is_arm9) {
+      // return back 0 for the deselected cpu
+      return num::zero();
+    }
+
+    if let Some(device) = &mut self.slot2_device {
+      let value = match std::mem::size_of::<T>() {
+        1 => (device.read_rom(address) >> ((address & 0b1) * 8)) as u32 & 0xff,
+        2 => device.read_rom(address) as u32,
+        _ => device.read_rom(address) as u32 | (device.read_rom(address + 2) as u32) << 16
+      };
+
+      return num::cast::<u32, T>(value).unwrap_or(num::zero());
+    }
+
+    if let Some(gba_cartridge) = &mut self.gba_cartridge {
+      return gba_cartridge.read_rom(address);
+    }
+
     let exmemcnt = if is_arm9 {
       &self.exmem.arm9_exmem
     } else {
       &self.exmem.arm7_exmem
     };
 
-    if is_arm9 && self.exmem.gba_access_rights == AccessRights::Arm9 || !is_arm9 && self.exmem.gba_access_rights == AccessRights::Arm7 {
-      // return garbage values depending on exmem properties
-      let value = match exmemcnt.gba_rom_1st_access {
-        0 => (address / 2) | 0xfe08, // 10 clocks
-        1 => address / 2, // 8 clocks
-        2 => address / 2, // 6 clocks
-        3 => 0xffff,
-        _ => unreachable!()
-      } & 0xffff;
-
-      let value = match address & 0x3 {
-        0 => value,
-        1 => value >> 8,
-        2 => 0,
-        3 => 0,
-        _ => unreachable!()
-      } as u16;
-
-      // for some reason the unwrap fails sometimes, so as a hack
-      // i'm returning 0 for now, which seems to work ok
-      return num::cast::<u16, T>(value).unwrap_or(num::zero());
-    }
-
-    // return back 0 for the deselected cpu
+    // return garbage values depending on exmem properties
+    let value = match exmemcnt.gba_rom_1st_access {
+      0 => (address / 2) | 0xfe08, // 10 clocks
+      1 => address / 2, // 8 clocks
+      2 => address / 2, // 6 clocks
+      3 => 0xffff,
+      _ => unreachable!()
+    } & 0xffff;
+
+    let value = match address & 0x3 {
+      0 => value,
+      1 => value >> 8,
+      2 => 0,
+      3 => 0,
+      _ => unreachable!()
+    } as u16;
+
+    // for some reason the unwrap fails sometimes, so as a hack
+    // i'm returning 0 for now, which seems to work ok
+    num::cast::<u16, T>(value).unwrap_or(num::zero())
+  }
+
+  pub fn write_gba_rom<T: Number>(&mut self, address: u32, value: T, is_arm9: bool) {
+    if self.has_gba_access(is_arm9) {
+      if let Some(device) = &mut self.slot2_device {
+        let value = num::cast::<T, u32>(value).unwrap();
+
+        match std::mem::size_of::<T>() {
+          // bytes show up on both halves of the 16 bit bus, but only the addressed one is written
+          1 => device.write_rom(address, value as u16 * 0x0101, 0xff << ((address & 0b1) * 8)),
+          2 => device.write_rom(address, value as u16, 0xffff),
+          _ => {
+            device.write_rom(address, value as u16, 0xffff);
+            device.write_rom(address + 2, (value >> 16) as u16, 0xffff);
+          }
+        }
+
+        self.update_motor();
+      } else if let Some(gba_cartridge) = &mut self.gba_cartridge {
+        gba_cartridge.write_rom(address, value);
+      }
+    }
+  }
+
+  pub fn read_gba_sram<T: Number>(&mut self, address: u32, is_arm9: bool) -> T {
+    if self.has_gba_access(is_arm9) {
+      if let Some(device) = &mut self.slot2_device {
+        // the sram bus is only 8 bits wide, so wider reads see the same byte repeated
+        let value = device.read_sram(address) as u32 * 0x0101_0101;
+
+        return num::cast::<u32, T>(value & (u32::MAX >> (32 - 8 * std::mem::size_of::<T>()))).unwrap_or(num::zero());
+      }
+
+      return match &self.gba_cartridge {
+        Some(gba_cartridge) => gba_cartridge.read_sram(address),
+        // nothing inserted reads back as open bus
+        None => num::cast::<u32, T>(0xffff_ffff).unwrap_or(num::zero())
+      };
+    }
+
     num::zero()
   }
 
+  pub fn write_gba_sram<T: Number>(&mut self, address: u32, value: T, is_arm9: bool) {
+    if self.has_gba_access(is_arm9) {
+      if let Some(device) = &mut self.slot2_device {
+        let shift = (address as usize & (std::mem::size_of::<T>() - 1)) * 8;
+
+        device.write_sram(address, (num::cast::<T, u32>(value).unwrap() >> shift) as u8);
+      } else if let Some(gba_cartridge) = &mut self.gba_cartridge {
+        gba_cartridge.write_sram(address, value);
+      }
+    }
+  }
+
   pub fn step_audio(&mut self, channel_id: usize, cycles_left: usize) {
     match self.arm7.apu.channels[channel_id].soundcnt.format {
       SoundFormat::PCM8 => {
@@ -816,7 +1311,7 @@ impl Bus {
       match previous_device {
         DeviceSelect::Firmware => self.spi.firmware.deselect(),
         DeviceSelect::Touchscreen => self.touchscreen.deselect(),
-        _ => ()
+        DeviceSelect::PowerManager => self.power_manager.deselect()
       }
     }
   }
@@ -1,6 +1,7 @@
 use crate::{
   apu::BitLength,
   cpu::registers::{
+    external_key_input_register::ExternalKeyInputRegister,
     external_memory::AccessRights,
     interrupt_enable_register::InterruptEnableRegister,
     interrupt_request_register::InterruptRequestRegister
@@ -10,10 +11,14 @@ use crate::{
 
 use crate::number::Number;
 
-use super::{Bus, MAIN_MEMORY_SIZE, WRAM_SIZE};
+use super::{debug_output::DEBUG_CHAR_OUT, Bus, MAIN_MEMORY_SIZE, WRAM_SIZE};
 
 impl Bus {
   pub fn arm7_mem_read_32(&mut self, address: u32) -> u32 {
+    if self.gba_mode {
+      return self.gba_mem_read_32(address);
+    }
+
     match address {
       0x400_0000..=0x4ff_ffff => self.arm7_io_read_32(address),
       _ => self.arm7_mem_read::<u32>(address)
@@ -31,11 +36,15 @@ impl Bus {
         self.arm7_io_write_16(address, (val >> 16) as u16);
       }
       0x400_0188 => self.send_to_fifo(false, val),
+      0x410_0010 => self.cartridge.write_gamecard_bus(val, &mut self.scheduler, self.exmem.nds_access_rights == AccessRights::Arm7, false),
       0x400_01a4 => {
         self.arm7_io_write_16(address, val as u16);
         self.arm7_io_write_16(address + 2, (val >> 16) as u16);
       }
-      0x400_01b0..=0x400_01ba => println!("ignoring writes to key2 encryption seeds"),
+      0x400_01b0..=0x400_01ba => {
+        self.arm7_io_write_16(address, val as u16);
+        self.arm7_io_write_16(address + 2, (val >> 16) as u16);
+      }
       0x400_0208 => self.arm7.interrupt_master_enable = val & 0b1 != 0,
       0x400_0210 => self.arm7.interrupt_enable = InterruptEnableRegister::from_bits_retain(val),
       0x400_0214 => self.arm7.interrupt_request = InterruptRequestRegister::from_bits_retain(self.arm7.interrupt_request.bits() & !val),
@@ -47,11 +56,20 @@ impl Bus {
       0x400_0120 => (),
       0x400_0128 => (),
       0x400_051c => self.arm7.apu.sndcapcnt[1].write_length(val as u16, None),
+      0x480_4000..=0x480_5fff | 0x480_8000..=0x480_8fff => {
+        self.arm7_io_write_16(address, val as u16);
+        self.arm7_io_write_16(address + 2, (val >> 16) as u16);
+      }
+      0x4ff_fa10..=0x4ff_fa1f => self.write_debug_register(address, val, false),
       _ => println!("[WARN] write to unsupported address: {:X}", address)
     }
   }
 
   pub fn arm7_mem_read_16(&mut self, address: u32) -> u16 {
+    if self.gba_mode {
+      return self.gba_mem_read_16(address);
+    }
+
     match address {
       0x400_0000..=0x4ff_ffff => self.arm7_io_read_16(address),
       _ => self.arm7_mem_read::<u16>(address)
@@ -88,6 +106,7 @@ impl Bus {
       0x600_0000..=0x6ff_ffff => self.gpu.read_arm7_wram(address),
       0x700_0000..=0x7ff_ffff => num::zero(),
       0x800_0000..=0x9ff_ffff => self.read_gba_rom(address, false),
+      0xa00_0000..=0xaff_ffff => self.read_gba_sram(address, false),
       _ => {
         panic!("reading from unsupported address: {:X}", address);
       }
@@ -95,6 +114,10 @@ impl Bus {
   }
 
   pub fn arm7_mem_read_8(&mut self, address: u32) -> u8 {
+    if self.gba_mode {
+      return self.gba_mem_read_8(address);
+    }
+
     match address {
       0x400_0000..=0x4ff_ffff => self.arm7_io_read_8(address),
       _ => self.arm7_mem_read::<u8>(address)
@@ -118,6 +141,8 @@ impl Bus {
       0x400_4000..=0x400_4d08 => 0,
       0x410_0000 => self.receive_from_fifo(false),
       0x410_0010 => self.cartridge.read_gamecard_bus(&mut self.scheduler, self.exmem.nds_access_rights == AccessRights::Arm7, false),
+      0x480_4000..=0x480_5fff | 0x480_8000..=0x480_8fff => self.arm7_io_read_16(address) as u32 | (self.arm7_io_read_16(address + 2) as u32) << 16,
+      0x4ff_fa00..=0x4ff_fa0f => self.arm7_io_read_16(address) as u32 | (self.arm7_io_read_16(address + 2) as u32) << 16,
       _ => {
         println!("[WARN] unhandled io read to address {:x}", address);
         0
@@ -125,7 +150,7 @@ impl Bus {
     }
   }
 
-  fn arm7_io_read_16(&mut self, address: u32) -> u16 {
+  pub fn arm7_io_read_16(&mut self, address: u32) -> u16 {
     // println!("reading from arm7 io address {:x}", address);
     // let address = if address & 0xfffe == 0x8000 {
     //   0x400_0800
@@ -145,8 +170,17 @@ impl Bus {
       0x400_010c => self.arm7.timers.t[3].read_timer_value(&self.scheduler),
       0x400_010e => self.arm7.timers.t[3].timer_ctl.bits(),
       0x400_0130 => self.key_input_register.bits(),
+      0x400_0132 => self.arm7.keycnt.read(),
       0x400_0134 => 0, // RCNT register, some kind of debug thing idk
-      0x400_0136 => self.arm7.extkeyin.bits(),
+      0x400_0136 => {
+        let mut extkeyin = self.arm7.extkeyin;
+
+        if !self.touchscreen.penirq_enabled() {
+          extkeyin.insert(ExternalKeyInputRegister::PEN_DOWN);
+        }
+
+        extkeyin.bits()
+      }
       0x400_0138 => self.arm7.rtc.read() as u16,
       0x400_0180 => self.arm7.ipcsync.read() as u16,
       0x400_0184 => self.arm7.ipcfifocnt.read(&mut self.arm9.ipcfifocnt.fifo) as u16,
@@ -213,8 +247,15 @@ impl Bus {
       0x400_0504 => self.arm7.apu.sound_bias,
       0x400_0508 => self.arm7.apu.sndcapcnt[0].read() as u16 | (self.arm7.apu.sndcapcnt[1].read() as u16) << 8,
       0x400_4000..=0x400_4d08 => 0,
-      0x480_4000..=0x480_5fff => 0, // more wifi register stuff
-      0x480_8000..=0x480_8fff => 0, // TODO: Wifi registers. might need to implement *something* because iirc some games will get stuck in infinite loop
+      0x480_4000..=0x480_5fff => self.arm7.wifi.read_ram(address - 0x480_4000),
+      0x480_8000..=0x480_8fff => {
+        let value = self.arm7.wifi.read(address - 0x480_8000, &self.scheduler);
+
+        self.check_wifi_interrupt();
+
+        value
+      }
+      0x4ff_fa00..=0x4ff_fa0f => self.debug_output.read_id(address) as u16 | (self.debug_output.read_id(address + 1) as u16) << 8,
       _ => {
         println!("[WARN] read from io register not implemented: {:X}", address);
         0
@@ -233,6 +274,10 @@ impl Bus {
   }
 
   pub fn arm7_mem_write_32(&mut self, address: u32, val: u32) {
+    if self.gba_mode {
+      return self.gba_mem_write_32(address, val);
+    }
+
     match address {
       0x400_0000..=0x4ff_ffff => self.arm7_io_write_32(address, val),
       _ => self.arm7_mem_write::<u32>(address, val)
@@ -240,6 +285,10 @@ impl Bus {
   }
 
   pub fn arm7_mem_write_16(&mut self, address: u32, val: u16) {
+    if self.gba_mode {
+      return self.gba_mem_write_16(address, val);
+    }
+
     match address {
       0x400_0000..=0x4ff_ffff => self.arm7_io_write_16(address, val),
       _ => self.arm7_mem_write::<u16>(address, val)
@@ -268,7 +317,8 @@ impl Bus {
         unsafe { *(&mut self.arm7.wram[(address & ((WRAM_SIZE as u32) - 1)) as usize] as *mut u8 as *mut T) = val }
       }
       0x600_0000..=0x6ff_ffff => self.gpu.vram.write_arm7_wram(address, val),
-      0x800_0000..=0x8ff_ffff => (),
+      0x800_0000..=0x9ff_ffff => self.write_gba_rom(address, val, false),
+      0xa00_0000..=0xaff_ffff => self.write_gba_sram(address, val, false),
       _ => {
         panic!("writing to unsupported address: {:X}", address);
       }
@@ -277,6 +327,10 @@ impl Bus {
 
 
   pub fn arm7_mem_write_8(&mut self, address: u32, val: u8) {
+    if self.gba_mode {
+      return self.gba_mem_write_8(address, val);
+    }
+
     if (0..self.arm7.bios7.len()).contains(&(address as usize)) {
       return;
     }
@@ -341,6 +395,7 @@ impl Bus {
       0x400_010a => self.arm7.timers.t[2].write_timer_control(value, &mut self.scheduler),
       0x400_010c => self.arm7.timers.t[3].reload_timer_value(value),
       0x400_010e => self.arm7.timers.t[3].write_timer_control(value, &mut self.scheduler),
+      0x400_0132 => self.write_keycnt(value, false),
       0x400_0128 => (), // debug register
       0x400_0134 => (), // RCNT
       0x400_0138 => self.arm7.rtc.write(value),
@@ -354,7 +409,7 @@ impl Bus {
         self.arm7_io_write_8(address, value as u8);
         self.arm7_io_write_8(address + 1, (value >> 8) as u8);
       }
-      0x400_01b0..=0x400_01ba => println!("ignoring writes to key2 encryption seed io"),
+      0x400_01b0..=0x400_01ba => self.cartridge.write_key2_seed(address - 0x400_01b0, value, self.exmem.nds_access_rights == AccessRights::Arm7),
       0x400_01c0 => self.write_spicnt(value),
       0x400_01c2 => self.write_spi_data(value as u8), // upper 8 bits are always ignored, even in bugged spi 16 bit mode. per the docs
       0x400_0204 => self.exmem.write(false, value),
@@ -393,8 +448,13 @@ impl Bus {
       0x400_051a => self.arm7.apu.sndcapcnt[1].write_destination((value as u32) << 16, Some(0xff)),
       0x400_051c => self.arm7.apu.sndcapcnt[1].write_length(value, None),
       0x400_1080 => (), // some kind of ds lite related register, can be safely ignored
-      0x480_4000..=0x480_5fff => (),
-      0x480_8000..=0x480_8fff => (),
+      0x480_4000..=0x480_5fff => self.arm7.wifi.write_ram(address - 0x480_4000, value),
+      0x480_8000..=0x480_8fff => {
+        self.arm7.wifi.write(address - 0x480_8000, value, &self.scheduler);
+        self.check_wifi_interrupt();
+      }
+      DEBUG_CHAR_OUT => self.debug_output.write_char(false, value as u8),
+      0x4ff_fa10..=0x4ff_fa1f => (),
       _ => {
         println!("[WARN] write to io register not implemented: {:X}", address)
       }
@@ -428,6 +488,8 @@ impl Bus {
       0x400_0505 => self.arm7.apu.write_sound_bias(((value & 0x3) as u16) << 8, Some(0xff)),
       0x400_0508 => self.arm7.apu.sndcapcnt[0].write(value),
       0x400_0509 => self.arm7.apu.sndcapcnt[1].write(value),
+      DEBUG_CHAR_OUT => self.debug_output.write_char(false, value),
+      0x4ff_fa10..=0x4ff_fa1f => (),
       _ => println!("[WARN] 8-bit write to unsupported io address: {:x}", address)
     }
   }
@@ -10,7 +10,7 @@ use crate::{
   }, number::Number
 };
 
-use super::{cp15::cp15_control_register::CP15ControlRegister, Bus, DTCM_SIZE, ITCM_SIZE, MAIN_MEMORY_SIZE};
+use super::{cp15::cp15_control_register::CP15ControlRegister, debug_output::DEBUG_CHAR_OUT, dldi::{DLDI_RESULT, DLDI_SECTOR}, Bus, DTCM_SIZE, ITCM_SIZE, MAIN_MEMORY_SIZE};
 
 impl Bus {
   pub fn arm9_mem_read_32(&mut self, address: u32) -> u32 {
@@ -67,6 +67,7 @@ impl Bus {
         unsafe { *(&self.gpu.engine_b.oam[(address & 0x3ff) as usize] as *const u8 as *const T) }
       }
       0x800_0000..=0x9ff_ffff => self.read_gba_rom(address, true),
+      0xa00_0000..=0xaff_ffff => self.read_gba_sram(address, true),
       _ => {
         panic!("reading from unsupported address: {:X}", address);
       }
@@ -108,6 +109,8 @@ impl Bus {
       0x400_4000..=0x400_4010 => 0, // DSi I/O ports
       0x410_0000 => self.receive_from_fifo(true),
       0x410_0010 => self.cartridge.read_gamecard_bus(&mut self.scheduler, self.exmem.nds_access_rights == AccessRights::Arm9, true),
+      0x4ff_fa00..=0x4ff_fa0f => self.arm9_io_read_16(address) as u32 | (self.arm9_io_read_16(address + 2) as u32) << 16,
+      DLDI_SECTOR..=DLDI_RESULT => self.read_dldi_register(address),
       _ => {
         println!("[WARN] unsupported io address received: {:X}", address);
         0
@@ -202,6 +205,7 @@ impl Bus {
       0x400_010c => self.arm9.timers.t[3].read_timer_value(&self.scheduler),
       0x400_010e => self.arm9.timers.t[3].timer_ctl.bits(),
       0x400_0130 => self.key_input_register.bits(),
+      0x400_0132 => self.arm9.keycnt.read(),
       0x400_0300 => self.arm9.postflg as u16,
       0x400_0180 => self.arm9.ipcsync.read() as u16,
       0x400_0182 => (self.arm9.ipcsync.read() >> 16) as u16,
@@ -236,6 +240,7 @@ impl Bus {
       0x400_1008..=0x400_105f => self.gpu.engine_b.read_register(address),
       0x400_106c => self.gpu.engine_b.master_brightness.read(),
       0x400_4000..=0x400_4fff => 0,
+      0x4ff_fa00..=0x4ff_fa0f => self.debug_output.read_id(address) as u16 | (self.debug_output.read_id(address + 1) as u16) << 8,
       _ => {
         println!("[WARN] read register not implemented: {:X}", address);
         0
@@ -324,7 +329,8 @@ impl Bus {
       0x700_0000..=0x7ff_ffff => {
         unsafe { *(&mut self.gpu.engine_b.oam[(address & 0x3ff) as usize] as *mut u8 as *mut T) = val };
       }
-      0x800_0000..=0x8ff_ffff => (),
+      0x800_0000..=0x9ff_ffff => self.write_gba_rom(address, val, true),
+      0xa00_0000..=0xaff_ffff => self.write_gba_sram(address, val, true),
       _ => {
         panic!("writing to unsupported address: {:X}", address);
       }
@@ -351,7 +357,7 @@ impl Bus {
       }
       0x400_0060 => self.gpu.engine3d.disp3dcnt = Display3dControlRegister::from_bits_retain(value),
       0x400_0064 => self.gpu.dispcapcnt.write(value),
-      0x400_0068 => (), // ignoring writes to DISP_MMEM_FIFO
+      0x400_0068 => self.gpu.display_fifo.write(value),
       0x400_006c => self.gpu.engine_a.master_brightness.write(value as u16),
       0x400_00b0..=0x400_00ba => self.arm9.dma.write(0, (address - 0x400_00b0) as usize, value, None, &mut self.scheduler),
       0x400_00bc..=0x400_00c6 => self.arm9.dma.write(1, (address - 0x400_00bc) as usize, value, None, &mut self.scheduler),
@@ -371,7 +377,12 @@ impl Bus {
         self.arm9_io_write_16(address, value as u16);
         self.arm9_io_write_16(address + 2, (value >> 16) as u16);
       }
+      0x400_01b0..=0x400_01ba => {
+        self.arm9_io_write_16(address, value as u16);
+        self.arm9_io_write_16(address + 2, (value >> 16) as u16);
+      }
       0x400_0188 => self.send_to_fifo(true, value),
+      0x410_0010 => self.cartridge.write_gamecard_bus(value, &mut self.scheduler, self.exmem.nds_access_rights == AccessRights::Arm9, true),
       0x400_0208 => self.arm9.interrupt_master_enable = value & 0b1 != 0,
       0x400_0210 => self.arm9.interrupt_enable = InterruptEnableRegister::from_bits_retain(value),
       0x400_0214 => {
@@ -449,6 +460,8 @@ impl Bus {
       0x400_1060..=0x400_1068 => (),
       0x400_4000..=0x400_4fff => (),
       0x400_106c => self.gpu.engine_b.master_brightness.write(value as u16),
+      0x4ff_fa10..=0x4ff_fa1f => self.write_debug_register(address, value, true),
+      DLDI_SECTOR..=DLDI_RESULT => self.write_dldi_register(address, value),
       _ => println!("[WARN] write to unsupported io address: {:X}", address)
     }
   }
@@ -506,6 +519,7 @@ impl Bus {
       0x400_010a => self.arm9.timers.t[2].write_timer_control(value, &mut self.scheduler),
       0x400_010c => self.arm9.timers.t[3].reload_timer_value(value),
       0x400_010e => self.arm9.timers.t[3].write_timer_control(value, &mut self.scheduler),
+      0x400_0132 => self.write_keycnt(value, true),
       0x400_0180 => self.arm9.ipcsync.write(&mut self.arm7.ipcsync, &mut self.arm7.interrupt_request, value),
       0x400_0184 => {
         self.arm9.ipcfifocnt.write(&mut self.arm9.interrupt_request,&mut self.arm7.ipcfifocnt.fifo, value);
@@ -518,6 +532,7 @@ impl Bus {
         self.arm9_io_write_8(address, value as u8);
         self.arm9_io_write_8(address + 1, (value >> 8) as u8);
       }
+      0x400_01b0..=0x400_01ba => self.cartridge.write_key2_seed(address - 0x400_01b0, value, self.exmem.nds_access_rights == AccessRights::Arm9),
       0x400_0204 => self.exmem.write(true, value),
       0x400_0208 => self.arm9.interrupt_master_enable = value & 0b1 != 0,
       0x400_0240..=0x400_0249 => {
@@ -543,6 +558,8 @@ impl Bus {
       0x400_1002 => self.gpu.engine_b.dispcnt.write((value as u32) << 16, Some(0xffff)),
       0x400_1008..=0x400_105f => self.gpu.engine_b.write_register(address, value, None),
       0x400_106c => self.gpu.engine_b.master_brightness.write(value),
+      DEBUG_CHAR_OUT => self.debug_output.write_char(true, value as u8),
+      0x4ff_fa10..=0x4ff_fa1f => (),
       _ => {
         println!("[WARN] write to register not implemented: {:X}", address)
       }
@@ -551,8 +568,8 @@ impl Bus {
 
   pub fn arm9_io_write_8(&mut self, address: u32, value: u8) {
     match address {
-      0x400_004c => self.gpu.mosaic.write(value as u16, 0xff00),
-      0x400_004d => self.gpu.mosaic.write((value as u16) << 8, 0xff),
+      0x400_004c => self.gpu.engine_a.mosaic.write(value as u16, 0xff00),
+      0x400_004d => self.gpu.engine_a.mosaic.write((value as u16) << 8, 0xff),
       0x400_01a1 => self.cartridge.spicnt.write((value as u16) << 8, self.exmem.nds_access_rights == AccessRights::Arm9, Some(0xff)),
       0x400_01a8..=0x400_01af => {
         let byte = address - 0x400_01a8;
@@ -582,6 +599,8 @@ impl Bus {
           self.gpu.engine_b.write_register(actual_address, (value as u16) << 8, Some(0xff));
         }
       }
+      DEBUG_CHAR_OUT => self.debug_output.write_char(true, value),
+      0x4ff_fa10..=0x4ff_fa1f => (),
       _ => println!("[WARN] 8-bit write to unsupported io address {:x}", address)
     }
   }
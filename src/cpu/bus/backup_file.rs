@@ -35,6 +35,12 @@ impl BackupFile {
 
       file.read_to_end(&mut buffer).unwrap();
 
+      // saves from emulators that only store what the chip holds can be smaller, the file itself is left alone until
+      // the game writes to it
+      if buffer.len() < capacity {
+        buffer.resize(capacity, 0xff);
+      }
+
       Self {
         file: Some(file),
         buffer,
@@ -44,9 +50,10 @@ impl BackupFile {
         is_desktop_cloud
       }
     } else if bytes.is_some() {
-      let bytes = bytes.unwrap();
+      let mut bytes = bytes.unwrap();
 
-      let buffer = if bytes.len() == capacity {
+      let buffer = if bytes.len() <= capacity {
+        bytes.resize(capacity, 0xff);
         bytes
       } else {
         vec![0xff; capacity]
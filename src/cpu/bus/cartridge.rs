@@ -3,6 +3,7 @@ use std::{collections::VecDeque, fs, ops::Range, path::PathBuf};
 
 use cartridge_control_register::CartridgeControlRegister;
 use key1_encryption::Key1Encryption;
+use key2_encryption::{Key2Encryption, KEY2_SEED1};
 use spicnt::SPICNT;
 
 use crate::{
@@ -18,14 +19,24 @@ use crate::{
   util
 };
 
-use super::{eeprom::Eeprom, flash::Flash};
+use super::{eeprom::Eeprom, flash::Flash, infrared::{Infrared, IrEndpoint}, nand::Nand};
 
 pub mod cartridge_control_register;
 pub mod spicnt;
 pub mod key1_encryption;
+pub mod key2_encryption;
 
 pub const CHIP_ID: u32 = 0x1fc2;
 
+const SECURE_AREA_START: usize = 0x4000;
+const SECURE_AREA_END: usize = 0x8000;
+// only the first 2K of the secure area are KEY1 encrypted
+const SECURE_AREA_ENCRYPTED_SIZE: usize = 0x800;
+// "encryObj"
+const SECURE_AREA_ID: [u32; 2] = [0x72636e65, 0x6a624f79];
+// the bios overwrites the secure area id with this value after decrypting it
+const SECURE_AREA_DESTROYED_ID: u32 = 0xe7ff_deff;
+
 
 #[derive(Serialize, Deserialize, Clone)]
 pub struct GameInfo {
@@ -41,8 +52,8 @@ pub struct Header {
   pub game_code: u32,
   _maker_code: String,
   _unit_code: u8,
-  _encryption_seed_select: u8,
-  _device_capacity: u8,
+  pub encryption_seed_select: u8,
+  device_capacity: u8,
   _region: u8,
   _rom_version: u8,
   _autostart: u8,
@@ -63,8 +74,8 @@ impl Header {
       game_code: 0,
       _maker_code: "".to_string(),
       _unit_code: 0,
-      _encryption_seed_select: 0,
-      _device_capacity: 0,
+      encryption_seed_select: 0,
+      device_capacity: 0,
       _region: 0,
       _rom_version: 0,
       _autostart: 0,
@@ -89,8 +100,8 @@ impl Header {
       game_code: u32::from_le_bytes(rom[0xc..0x10].try_into().unwrap()),
       _maker_code: std::str::from_utf8(&rom[0x10..0x12]).unwrap_or_default().to_string(),
       _unit_code: rom[0x12],
-      _encryption_seed_select: rom[0x13],
-      _device_capacity: rom[0x14],
+      encryption_seed_select: rom[0x13],
+      device_capacity: rom[0x14],
       _region: rom[0x1d],
       _rom_version: rom[0x1e],
       _autostart: rom[0x1f],
@@ -113,7 +124,9 @@ impl Header {
 pub enum BackupType {
   None,
   Flash(Flash),
-  Eeprom(Eeprom)
+  Eeprom(Eeprom),
+  Nand(Nand),
+  Infrared(Infrared)
 }
 
 pub struct Cartridge {
@@ -123,9 +136,18 @@ pub struct Cartridge {
   pub header: Header,
   pub command: [u8; 8],
   pub rom_bytes_left: usize,
+  // the CLK rate and gap2 length of the command in progress, ROMCTRL is only looked at when a command starts
+  clock_cycles: usize,
+  gap2_clocks: usize,
   pub out_fifo: VecDeque<u32>,
   pub current_word: u32,
   pub key1_encryption: Key1Encryption,
+  pub key2_seed0: u64,
+  pub key2_seed1: u64,
+  key2_encryption: Key2Encryption,
+  card_key2_encryption: Key2Encryption,
+  card_key2_seed0: u64,
+  card_key2_active: bool,
   pub spidata: u8,
   pub backup: BackupType,
   main_area_load: bool
@@ -140,8 +162,16 @@ impl Cartridge {
       rom: Vec::new(),
       command: [0; 8],
       rom_bytes_left: 0,
+      clock_cycles: 5,
+      gap2_clocks: 0,
       out_fifo: VecDeque::new(),
       key1_encryption: Key1Encryption::new(bios7),
+      key2_seed0: 0,
+      key2_seed1: 0,
+      key2_encryption: Key2Encryption::new(),
+      card_key2_encryption: Key2Encryption::new(),
+      card_key2_seed0: 0,
+      card_key2_active: false,
       spidata: 0,
       current_word: 0,
       backup: BackupType::None,
@@ -149,6 +179,87 @@ impl Cartridge {
     }
   }
 
+  pub fn load_rom(&mut self, rom: &Vec<u8>) {
+    self.header = Header::from(rom);
+    self.rom = rom.clone();
+
+    self.decrypt_secure_area();
+  }
+
+  fn is_secure_area_decrypted(&self) -> bool {
+    let id = [util::read_word(&self.rom, SECURE_AREA_START), util::read_word(&self.rom, SECURE_AREA_START + 4)];
+
+    id == SECURE_AREA_ID || id == [SECURE_AREA_DESTROYED_ID; 2]
+  }
+
+  /*
+    Dumps come with the secure area either still encrypted (straight off the card) or already
+    decrypted (as left behind by the bios). Direct boot copies the arm9 binary straight out of
+    the rom, so keep it decrypted internally and re-encrypt it when the bios asks for it.
+   */
+  fn decrypt_secure_area(&mut self) {
+    if self.rom.len() < SECURE_AREA_END || self.is_secure_area_decrypted() {
+      return;
+    }
+
+    let mut block: Vec<u32> = (SECURE_AREA_START..SECURE_AREA_START + SECURE_AREA_ENCRYPTED_SIZE)
+      .step_by(4)
+      .map(|address| util::read_word(&self.rom, address))
+      .collect();
+
+    self.key1_encryption.init_keycode(self.header.game_code, 2, 2);
+    self.key1_encryption.decrypt_64bit(&mut block[0..2]);
+
+    self.key1_encryption.init_keycode(self.header.game_code, 3, 2);
+
+    for i in (0..block.len()).step_by(2) {
+      self.key1_encryption.decrypt_64bit(&mut block[i..i+2]);
+    }
+
+    // init_keycode arms key1 for commands, which shouldn't happen until the game asks for it
+    self.key1_encryption.ready = false;
+
+    if block[0..2] != SECURE_AREA_ID {
+      // no valid secure area (homebrew, most likely), so leave the rom untouched
+      println!("warning: could not decrypt secure area");
+      return;
+    }
+
+    block[0] = SECURE_AREA_DESTROYED_ID;
+    block[1] = SECURE_AREA_DESTROYED_ID;
+
+    for (i, word) in block.iter().enumerate() {
+      let address = SECURE_AREA_START + i * 4;
+
+      self.rom[address..address + 4].copy_from_slice(&word.to_le_bytes());
+    }
+  }
+
+  fn encrypt_secure_area(&mut self) {
+    self.out_fifo[0] = SECURE_AREA_ID[0];
+    self.out_fifo[1] = SECURE_AREA_ID[1];
+
+    self.key1_encryption.init_keycode(self.header.game_code, 3, 2);
+
+    for i in (0..SECURE_AREA_ENCRYPTED_SIZE / 4).step_by(2) {
+      let mut data = [self.out_fifo[i], self.out_fifo[i + 1]];
+
+      self.key1_encryption.encrypt_64bit(&mut data);
+
+      self.out_fifo[i] = data[0];
+      self.out_fifo[i + 1] = data[1];
+    }
+
+    self.key1_encryption.init_keycode(self.header.game_code, 2, 2);
+
+    let mut data = [self.out_fifo[0], self.out_fifo[1]];
+
+    self.key1_encryption.encrypt_64bit(&mut data);
+
+    self.out_fifo[0] = data[0];
+    self.out_fifo[1] = data[1];
+  }
+
   pub fn detect_backup_type(&mut self) -> Option<GameInfo> {
     // thanks to MelonDS for the game db
     let game_db: Vec<GameInfo> = serde_json::from_str(&fs::read_to_string("./game_db.json").unwrap()).unwrap();
@@ -163,19 +274,43 @@ impl Cartridge {
   }
 
   pub fn set_backup(&mut self, save_filename: PathBuf, entry: GameInfo) {
-    let backup_file = BackupFile::new(Some(save_filename), None, entry.ram_capacity, false);
+    let capacity = self.backup_capacity(&entry.save_type, entry.ram_capacity);
+    let backup_file = BackupFile::new(Some(save_filename), None, capacity, false);
 
     println!("detected backup type {}", entry.save_type);
 
-    self.set_backup_file(backup_file, entry.ram_capacity, entry.save_type.clone())
+    self.set_backup_file(backup_file, capacity, entry.save_type.clone())
   }
 
   pub fn set_cloud_backup(&mut self, bytes: Vec<u8>, entry: GameInfo) {
-    let backup_file = BackupFile::new(None, Some(bytes), entry.ram_capacity, true);
+    let capacity = self.backup_capacity(&entry.save_type, entry.ram_capacity);
+    let backup_file = BackupFile::new(None, Some(bytes), capacity, true);
 
     println!("detected backup type {}", entry.save_type);
 
-    self.set_backup_file(backup_file, entry.ram_capacity, entry.save_type.clone());
+    self.set_backup_file(backup_file, capacity, entry.save_type.clone());
+  }
+
+  // header 96h holds the start of the NAND save area in units of 20000h bytes
+  fn nand_save_base(&self) -> usize {
+    (self.rom[0x96] as usize | (self.rom[0x97] as usize) << 8) << 17
+  }
+
+  // the game db only has a placeholder size for NAND saves, they really take up the rest of the chip after the save base
+  fn backup_capacity(&self, save_type: &str, ram_capacity: usize) -> usize {
+    if save_type != "nand" {
+      return ram_capacity;
+    }
+
+    let chip_size = 0x2_0000_usize.checked_shl(self.header.device_capacity as u32).unwrap_or(0);
+
+    match chip_size.checked_sub(self.nand_save_base()) {
+      Some(capacity) if capacity > 0 => capacity,
+      _ => {
+        println!("warning: NAND save base is past the end of the chip");
+        ram_capacity
+      }
+    }
   }
 
   fn set_backup_file(&mut self, backup_file: BackupFile, ram_capacity: usize, save_type: String) {
@@ -195,30 +330,39 @@ impl Cartridge {
         self.backup = BackupType::Eeprom(Eeprom::new(backup_file, address_width));
       }
       "flash" => {
-        self.backup = BackupType::Flash(Flash::new(backup_file));
+        // game codes starting with 'I' have an IR transceiver in front of the flash
+        if self.header.game_code & 0xff == b'I' as u32 {
+          self.backup = BackupType::Infrared(Infrared::new(Flash::new(backup_file)));
+        } else {
+          self.backup = BackupType::Flash(Flash::new(backup_file));
+        }
+      }
+      "nand" => {
+        self.backup = BackupType::Nand(Nand::new(backup_file, self.nand_save_base()));
       }
       _ => panic!("backup type not supported: {}", save_type)
     }
   }
 
+  pub fn connect_ir(&mut self, endpoint: Box<dyn IrEndpoint>) {
+    match &mut self.backup {
+      BackupType::Infrared(infrared) => infrared.connect(endpoint),
+      _ => println!("warning: cartridge doesn't have an IR transceiver")
+    }
+  }
+
   pub fn set_backup_external(&mut self, bytes: &[u8], save_type: String, ram_capacity: usize) {
-    let backup_file = BackupFile::new(None, Some(bytes.to_vec()), ram_capacity, false);
-    self.set_backup_file(backup_file, ram_capacity, save_type);
+    let capacity = self.backup_capacity(&save_type, ram_capacity);
+    let backup_file = BackupFile::new(None, Some(bytes.to_vec()), capacity, false);
+    self.set_backup_file(backup_file, capacity, save_type);
   }
 
   pub fn read_gamecard_bus(&mut self, scheduler: &mut Scheduler, has_access: bool, is_arm9: bool) -> u32 {
     if has_access {
-      if self.control.data_word_status {
+      if self.control.data_word_status && !self.control.data_direction {
         self.control.data_word_status = false;
 
-        self.rom_bytes_left -= 4;
-
-        if self.rom_bytes_left > 0 {
-          scheduler.schedule(EventType::WordTransfer(is_arm9), self.get_transfer_time() * 4);
-        } else {
-          // run immediately
-          scheduler.schedule(EventType::BlockFinished(is_arm9), 0);
-        }
+        self.advance_transfer(scheduler, is_arm9);
       }
 
       return self.current_word;
@@ -227,16 +371,108 @@ impl Cartridge {
     0
   }
 
+  // only used with the data direction set to write, which is how NAND saves get written
+  pub fn write_gamecard_bus(&mut self, value: u32, scheduler: &mut Scheduler, has_access: bool, is_arm9: bool) {
+    if has_access && self.control.data_word_status && self.control.data_direction {
+      self.control.data_word_status = false;
+
+      let mut value = value;
+
+      if self.control.key2_encrypt_data {
+        value = self.key2_encryption.process_word(value);
+      }
+
+      if self.card_key2_active && self.control.key2_encrypt_data {
+        value = self.card_key2_encryption.process_word(value);
+      }
+
+      if let BackupType::Nand(nand) = &mut self.backup {
+        nand.write_data(&self.command, value);
+      }
+
+      self.advance_transfer(scheduler, is_arm9);
+    }
+  }
+
+  fn advance_transfer(&mut self, scheduler: &mut Scheduler, is_arm9: bool) {
+    self.rom_bytes_left -= 4;
+
+    if self.rom_bytes_left > 0 {
+      let mut clocks = 4;
+
+      // gap2 is inserted after every 200h bytes of data
+      if self.rom_bytes_left & 0x1ff == 0 {
+        clocks += self.gap2_clocks;
+      }
+
+      scheduler.schedule(EventType::WordTransfer(is_arm9), self.clock_cycles * clocks);
+    } else {
+      // run immediately
+      scheduler.schedule(EventType::BlockFinished(is_arm9), 0);
+    }
+  }
+
   pub fn write_command(&mut self, command: u8, byte: usize, has_access: bool) {
     if has_access {
       self.command[byte] = command;
     }
   }
 
+  /*
+    40001B0h 4  Encryption Seed 0 Lower 32bit
+    40001B4h 4  Encryption Seed 1 Lower 32bit
+    40001B8h 2  Encryption Seed 0 Upper 7bit (bit7-15 unused)
+    40001BAh 2  Encryption Seed 1 Upper 7bit (bit7-15 unused)
+   */
+  pub fn write_key2_seed(&mut self, offset: u32, value: u16, has_access: bool) {
+    if has_access {
+      let (seed, shift) = match offset {
+        0x0 => (&mut self.key2_seed0, 0),
+        0x2 => (&mut self.key2_seed0, 16),
+        0x4 => (&mut self.key2_seed1, 0),
+        0x6 => (&mut self.key2_seed1, 16),
+        0x8 => (&mut self.key2_seed0, 32),
+        0xa => (&mut self.key2_seed1, 32),
+        _ => unreachable!()
+      };
+
+      let mask: u64 = if shift == 32 { 0x7f } else { 0xffff };
+
+      *seed = (*seed & !(mask << shift)) | ((value as u64 & mask) << shift);
+    }
+  }
+
+  // puts the card into the same state the bios leaves it in after loading the secure area. KEY2 is only applied
+  // while ROMCTRL enables it, so anything that never turns it on still reads plain data
+  pub fn skip_bios(&mut self) {
+    self.main_area_load = true;
+
+    self.key2_seed0 = Key2Encryption::get_seed0(0, self.header.encryption_seed_select);
+    self.key2_seed1 = KEY2_SEED1;
+
+    self.key2_encryption.apply_seeds(self.key2_seed0, self.key2_seed1);
+
+    self.activate_card_key2(0);
+  }
+
+  fn activate_card_key2(&mut self, mmmnnn: u32) {
+    self.card_key2_seed0 = Key2Encryption::get_seed0(mmmnnn, self.header.encryption_seed_select);
+    self.card_key2_active = true;
+
+    self.card_key2_encryption.apply_seeds(self.card_key2_seed0, KEY2_SEED1);
+  }
+
   pub fn write_control(&mut self, value: u32, mask: Option<u32>, scheduler: &mut Scheduler, is_arm9: bool, has_access: bool) {
     if has_access {
       self.control.write(value, mask, has_access);
 
+      if self.control.key2_apply_seed {
+        self.key2_encryption.apply_seeds(self.key2_seed0, self.key2_seed1);
+
+        // write only
+        self.control.key2_apply_seed = false;
+      }
+
       if (value >> 31) & 0b1 == 1 {
         // run a command
         self.execute_command(scheduler, is_arm9);
@@ -255,20 +491,68 @@ impl Cartridge {
       num => 0x100 << num
     };
 
+    self.out_fifo.clear();
+
+    // ROMCMD keeps the value that was written to it, encryption only happens on the way to the card
+    let command = self.command;
+    let previous_main_area_load = self.main_area_load;
+
+    if self.control.key2_encrypt_command {
+      for byte in self.command.iter_mut() {
+        *byte = self.key2_encryption.process_byte(*byte);
+      }
+    }
+
     // next check whether to run an encrypted command or unencrypted
-    if self.key1_encryption.ready {
+    if self.rom.is_empty() {
+      // without a card in the slot the data lines float high, which is how the firmware tells there's nothing inserted
+      for _ in 0..self.rom_bytes_left / 4 {
+        self.out_fifo.push_back(0xffff_ffff);
+      }
+    } else if self.key1_encryption.ready {
       self.execute_encrypted_command();
     } else {
+      // in main data mode the card expects KEY2 encrypted commands, which only happens with ROMCTRL bit 22 set
+      if self.main_area_load && self.card_key2_active && self.control.key2_encrypt_command {
+        for byte in self.command.iter_mut() {
+          *byte = self.card_key2_encryption.process_byte(*byte);
+        }
+      }
+
       self.execute_unencrypted_command();
     }
 
+    self.command = command;
+
+    // same for the data coming back and ROMCTRL bit 13, the LFSRs on both ends only step when it's in use
+    if self.card_key2_active && self.control.key2_encrypt_data {
+      for word in self.out_fifo.iter_mut() {
+        *word = self.card_key2_encryption.process_word(*word);
+      }
+    }
+
+    // after command 0xA the card restarts KEY2 from the initial seeds, the bios re-applies the same seeds on its end
+    if self.card_key2_active && self.main_area_load && !previous_main_area_load {
+      self.card_key2_encryption.apply_seeds(self.card_key2_seed0, KEY2_SEED1);
+    }
+
+    self.clock_cycles = self.get_transfer_time();
+
+    // the gaps last the same number of clocks whether CLK is held high or dummy pulses are output (ROMCTRL bit 28),
+    // and they're skipped entirely when writing to the card
+    self.gap2_clocks = if self.control.data_direction { 0 } else { self.control.key1_gap2_length as usize };
+
+    let command_clocks = self.get_command_clocks();
+
     if self.rom_bytes_left == 0 {
-      scheduler.schedule(EventType::BlockFinished(is_arm9), self.get_transfer_time() * 8);
+      scheduler.schedule(EventType::BlockFinished(is_arm9), self.clock_cycles * command_clocks);
     } else {
-      scheduler.schedule(EventType::WordTransfer(is_arm9), self.get_transfer_time() * 12);
+      // the first word arrives after the command, the gaps, and 4 more clocks for the word itself
+      scheduler.schedule(EventType::WordTransfer(is_arm9), self.clock_cycles * (command_clocks + 4));
     }
   }
 
+  // returns the amount of cycles per CLK pulse: 33.51MHz / 5 = 6.7MHz, or 33.51MHz / 8 = 4.2MHz with ROMCTRL bit 27 set
   fn get_transfer_time(&self) -> usize {
     if self.control.transfer_clock_rate {
       8
@@ -277,9 +561,40 @@ impl Cartridge {
     }
   }
 
+  /*
+    Clocks spent before the first data word: 8 clocks for the command itself, then the gap1 length,
+    and gap2 as well if there's any data to transfer. Together with 4 clocks per word and gap2 again
+    in front of every further 200h bytes, a block of n bytes takes
+
+      8 + gap1 + (n / 200h) * gap2 + n
+
+    clocks from start to finish, n rounded up to 200h for the gap2 count.
+   */
+  fn get_command_clocks(&self) -> usize {
+    let mut clocks = 8;
+
+    if !self.control.data_direction {
+      clocks += self.control.key1_gap1_length as usize;
+
+      if self.rom_bytes_left > 0 {
+        clocks += self.gap2_clocks;
+      }
+    }
+
+    clocks
+  }
+
   pub fn on_word_transferred(&mut self, dma: &mut DmaChannels) {
     self.control.data_word_status = true;
-    self.current_word = self.out_fifo.pop_front().unwrap();
+
+    // when writing, the word is ready to be filled in by the cpu instead
+    if !self.control.data_direction {
+      self.current_word = self.out_fifo.pop_front().unwrap();
+
+      if self.control.key2_encrypt_data {
+        self.current_word = self.key2_encryption.process_word(self.current_word);
+      }
+    }
 
     dma.notify_cartridge_event();
   }
@@ -302,7 +617,10 @@ impl Cartridge {
         BackupType::Flash(ref mut flash) => {
           flash.write(val, self.spicnt.hold_chipselect);
         }
-        BackupType::None => ()
+        BackupType::Infrared(ref mut infrared) => {
+          infrared.write(val, self.spicnt.hold_chipselect);
+        }
+        BackupType::Nand(_) | BackupType::None => ()
       }
     }
   }
@@ -316,7 +634,10 @@ impl Cartridge {
         BackupType::Flash(ref flash) => {
           return flash.read();
         }
-        BackupType::None => return 0
+        BackupType::Infrared(ref infrared) => {
+          return infrared.read();
+        }
+        BackupType::Nand(_) | BackupType::None => return 0
       }
     }
 
@@ -344,6 +665,11 @@ impl Cartridge {
 
     match command {
       0x4 => {
+        // 4llllmmmnnnkkkkkh, activates KEY2 on the card side
+        let mmmnnn = (u64::from_be_bytes(self.command) >> 20) as u32 & 0xff_ffff;
+
+        self.activate_card_key2(mmmnnn);
+
         // Returns 910h dummy bytes
         for _ in 0..self.rom_bytes_left / 4 {
           self.out_fifo.push_back(0xffff_ffff);
@@ -357,37 +683,11 @@ impl Cartridge {
       0x2 => {
         let address = ((self.command[2] as usize) & 0xf0) << 8;
 
-        // TODO: possibly move the code below into copy_rom?
         self.copy_rom(address..address+self.rom_bytes_left);
 
-        if address == 0x4000 {
-          // encryObj string
-          self.out_fifo[0] = 0x72636e65;
-          self.out_fifo[1] = 0x6a624f79;
-
-          self.key1_encryption.init_keycode(self.header.game_code, 3, 2);
-
-          for i in (0..512).step_by(2) {
-            let mut data = Vec::new();
-            data.push(self.out_fifo[i]);
-            data.push(self.out_fifo[i+1]);
-
-            self.key1_encryption.encrypt_64bit(&mut data);
-
-            self.out_fifo[i] = data[0];
-            self.out_fifo[i+1] = data[1];
-          }
-
-          self.key1_encryption.init_keycode(self.header.game_code, 2, 2);
-
-          let mut data = Vec::new();
-          data.push(self.out_fifo[0]);
-          data.push(self.out_fifo[1]);
-
-          self.key1_encryption.encrypt_64bit(&mut data);
-
-          self.out_fifo[0] = data[0];
-          self.out_fifo[1] = data[1];
+        // roms that failed to decrypt at load time are already encrypted, so pass them through as is
+        if address == SECURE_AREA_START && self.is_secure_area_decrypted() {
+          self.encrypt_secure_area();
         }
       }
       0xa => {
@@ -457,6 +757,12 @@ impl Cartridge {
   }
 
   fn execute_unencrypted_command(&mut self) {
+    if let BackupType::Nand(nand) = &mut self.backup {
+      if nand.execute_command(&self.command, self.rom_bytes_left, &mut self.out_fifo) {
+        return;
+      }
+    }
+
     let command = self.command[0];
 
     match command {
@@ -494,4 +800,83 @@ impl Cartridge {
       }
     }
   }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const DATA_ADDRESS: usize = 0x8000;
+
+  fn direct_boot_cartridge() -> Cartridge {
+    let mut rom = vec![0; 0x9000];
+
+    rom[DATA_ADDRESS..DATA_ADDRESS + 4].copy_from_slice(&[0x78, 0x56, 0x34, 0x12]);
+
+    let mut cartridge = Cartridge::new(&[0; 0x4000]);
+    cartridge.header = Header::from(&rom);
+    cartridge.rom = rom;
+
+    cartridge.skip_bios();
+
+    cartridge
+  }
+
+  // reads a single word of main data with command B7h
+  fn read_data_word(cartridge: &mut Cartridge, romctrl: u32) -> u32 {
+    let mut scheduler = Scheduler::new();
+    let mut dma = DmaChannels::new(true);
+
+    let command = [0xb7, 0, 0, (DATA_ADDRESS >> 8) as u8, DATA_ADDRESS as u8, 0, 0, 0];
+
+    for (i, byte) in command.iter().enumerate() {
+      cartridge.write_command(*byte, i, true);
+    }
+
+    // 4 byte block, start
+    cartridge.write_control(romctrl | 7 << 24 | 1 << 31, None, &mut scheduler, true, true);
+    cartridge.on_word_transferred(&mut dma);
+
+    cartridge.read_gamecard_bus(&mut scheduler, true, true)
+  }
+
+  #[test]
+  fn direct_boot_reads_plain_data_without_key2() {
+    let mut cartridge = direct_boot_cartridge();
+
+    assert_eq!(read_data_word(&mut cartridge, 0), 0x1234_5678);
+    assert_eq!(read_data_word(&mut cartridge, 0), 0x1234_5678);
+  }
+
+  #[test]
+  fn direct_boot_reads_data_with_key2() {
+    let mut cartridge = direct_boot_cartridge();
+
+    // KEY2 for both commands and data
+    let romctrl = 1 << 13 | 1 << 22;
+
+    assert_eq!(read_data_word(&mut cartridge, romctrl), 0x1234_5678);
+    assert_eq!(read_data_word(&mut cartridge, romctrl), 0x1234_5678);
+  }
+
+  #[test]
+  fn nand_saves_take_up_the_rest_of_the_chip() {
+    let mut rom = vec![0; 0x200];
+
+    // 16mb chip with the save area starting at 8mb
+    rom[0x14] = 7;
+    rom[0x96] = 0x40;
+
+    let mut cartridge = Cartridge::new(&[0; 0x4000]);
+    cartridge.header = Header::from(&rom);
+    cartridge.rom = rom;
+
+    cartridge.set_backup_external(&[], "nand".to_string(), 0x200);
+
+    let BackupType::Nand(nand) = &cartridge.backup else {
+      panic!("expected a NAND save");
+    };
+
+    assert_eq!(nand.backup_file.buffer.len(), 0x80_0000);
+  }
+}
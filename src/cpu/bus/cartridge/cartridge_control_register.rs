@@ -83,7 +83,7 @@ impl CartridgeControlRegister {
     self.key1_gap1_length = value & 0x1fff;
     self.key2_encrypt_data = (value >> 13) & 0b1 == 1;
     self.key2_apply_seed = (value >> 15) & 0b1 == 1;
-    self.key1_gap2_length = (value >> 16) & 0x1f;
+    self.key1_gap2_length = (value >> 16) & 0x3f;
     self.key2_encrypt_command = (value >> 22) & 0b1 == 1;
     self.data_block_size = (value >> 24) & 0x7;
     self.transfer_clock_rate = (value >> 27) & 0b1 == 1;
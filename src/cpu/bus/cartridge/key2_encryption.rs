@@ -0,0 +1,64 @@
+// KEY2 is a pair of 39-bit LFSRs that are xored with every byte sent over the gamecard bus.
+// see https://problemkaputt.de/gbatek.htm#dsencryptionbyrandomseedkey2
+
+const KEY2_MASK: u64 = 0x7f_ffff_ffff;
+
+// seed1 is the same for every game
+pub const KEY2_SEED1: u64 = 0x5c_879b_9b05;
+
+// indexed by header[0x13] & 0x7
+const KEY2_SEED_TABLE: [u64; 8] = [0xe8, 0x4d, 0x5a, 0xb1, 0x17, 0x8f, 0x99, 0xd5];
+
+#[derive(Default)]
+pub struct Key2Encryption {
+  x: u64,
+  y: u64
+}
+
+impl Key2Encryption {
+  pub fn new() -> Self {
+    Self {
+      x: 0,
+      y: 0
+    }
+  }
+
+  /*
+    Seed0 = (mmmnnn SHL 15) + 6000h + seedbyte
+    where mmmnnn comes from the KEY1 "activate KEY2" command (4llllmmmnnnkkkkkh)
+   */
+  pub fn get_seed0(mmmnnn: u32, seed_select: u8) -> u64 {
+    (((mmmnnn as u64) << 15) + 0x6000 + KEY2_SEED_TABLE[(seed_select & 0x7) as usize]) & KEY2_MASK
+  }
+
+  pub fn apply_seeds(&mut self, seed0: u64, seed1: u64) {
+    // the seeds are loaded into the LFSRs in reversed bit order
+    self.x = (seed0 & KEY2_MASK).reverse_bits() >> 25;
+    self.y = (seed1 & KEY2_MASK).reverse_bits() >> 25;
+  }
+
+  /*
+    x = (((x shr 5)xor(x shr 17)xor(x shr 18)xor(x shr 31)) and 0FFh)+(x shl 8)
+    y = (((y shr 5)xor(y shr 23)xor(y shr 18)xor(y shr 31)) and 0FFh)+(y shl 8)
+    x = x and 7FFFFFFFFFh
+    y = y and 7FFFFFFFFFh
+    data = (data xor x xor y) and 0FFh
+   */
+  pub fn process_byte(&mut self, byte: u8) -> u8 {
+    self.x = ((((self.x >> 5) ^ (self.x >> 17) ^ (self.x >> 18) ^ (self.x >> 31)) & 0xff) + (self.x << 8)) & KEY2_MASK;
+    self.y = ((((self.y >> 5) ^ (self.y >> 23) ^ (self.y >> 18) ^ (self.y >> 31)) & 0xff) + (self.y << 8)) & KEY2_MASK;
+
+    byte ^ (self.x ^ self.y) as u8
+  }
+
+  // encryption and decryption are the same operation, so this is used for both
+  pub fn process_word(&mut self, word: u32) -> u32 {
+    let mut bytes = word.to_le_bytes();
+
+    for byte in bytes.iter_mut() {
+      *byte = self.process_byte(*byte);
+    }
+
+    u32::from_le_bytes(bytes)
+  }
+}
\ No newline at end of file
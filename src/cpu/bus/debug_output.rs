@@ -0,0 +1,145 @@
+/*
+  no$gba's debug message registers, which homebrew uses for printf debugging. Messages are handed
+  to a callback set by the frontend, along with whether they came from the arm9.
+
+  0x4ff_fa00..=0x4ff_fa0f reads back no$gba's emulation id, which is how programs tell that they can print
+  0x4ff_fa10 takes a pointer to a string that's printed as is
+  0x4ff_fa14 takes a pointer to a string with %params% in it. the cpu that wrote it fills those in
+  0x4ff_fa18 takes a single character, and the line is printed once it hits a newline
+ */
+
+pub const DEBUG_STRING_RAW: u32 = 0x4ff_fa10;
+pub const DEBUG_STRING_PARAMS: u32 = 0x4ff_fa14;
+pub const DEBUG_CHAR_OUT: u32 = 0x4ff_fa18;
+
+const EMULATION_ID: &[u8; 16] = b"no$gba v2.7     ";
+
+// no$gba doesn't print anything past this either
+pub const MAX_STRING_LENGTH: usize = 0x100;
+
+pub type DebugCallback = Box<dyn FnMut(bool, &str)>;
+
+#[derive(Default)]
+pub struct DebugOutput {
+  callback: Option<DebugCallback>,
+  char_lines: [String; 2],
+  last_clocks: [usize; 2]
+}
+
+// what the %params% can refer to at the moment the message was written
+pub struct DebugContext {
+  pub registers: [u32; 16],
+  pub pc: u32,
+  pub scanline: u16,
+  pub frame: usize,
+  pub cycles: usize
+}
+
+impl DebugOutput {
+  pub fn new() -> Self {
+    Self {
+      callback: None,
+      char_lines: [String::new(), String::new()],
+      last_clocks: [0; 2]
+    }
+  }
+
+  // the callback belongs to the frontend, so it carries over when the console is reset
+  pub fn reset(&mut self) -> Self {
+    Self {
+      callback: self.callback.take(),
+      char_lines: [String::new(), String::new()],
+      last_clocks: [0; 2]
+    }
+  }
+
+  pub fn set_callback(&mut self, callback: DebugCallback) {
+    self.callback = Some(callback);
+  }
+
+  pub fn read_id(&self, address: u32) -> u8 {
+    EMULATION_ID[(address & 0xf) as usize]
+  }
+
+  pub fn print(&mut self, is_arm9: bool, message: &str) {
+    if let Some(callback) = &mut self.callback {
+      callback(is_arm9, message.trim_end_matches('\n'));
+    }
+  }
+
+  pub fn write_char(&mut self, is_arm9: bool, value: u8) {
+    let index = is_arm9 as usize;
+
+    if value == b'\n' {
+      let line = std::mem::take(&mut self.char_lines[index]);
+
+      self.print(is_arm9, &line);
+    } else if value != 0 {
+      self.char_lines[index].push(value as char);
+    }
+  }
+
+  pub fn expand_params(&mut self, is_arm9: bool, text: &str, context: &DebugContext) -> String {
+    let mut message = String::new();
+
+    let mut rest = text;
+
+    while let Some(start) = rest.find('%') {
+      message.push_str(&rest[..start]);
+
+      let after = &rest[start + 1..];
+
+      match after.find('%') {
+        Some(end) => {
+          match self.expand_param(is_arm9, &after[..end], context) {
+            Some(value) => message.push_str(&value),
+            // not a parameter, so leave the text alone
+            None => message.push_str(&rest[start..start + end + 2])
+          }
+
+          rest = &after[end + 1..];
+        }
+        None => {
+          message.push_str(&rest[start..]);
+          rest = "";
+        }
+      }
+    }
+
+    message.push_str(rest);
+
+    message
+  }
+
+  fn expand_param(&mut self, is_arm9: bool, param: &str, context: &DebugContext) -> Option<String> {
+    let index = is_arm9 as usize;
+
+    let value = match param {
+      "sp" => format!("{:08X}", context.registers[13]),
+      "lr" => format!("{:08X}", context.registers[14]),
+      "pc" => format!("{:08X}", context.pc),
+      "scanline" => context.scanline.to_string(),
+      "frame" => context.frame.to_string(),
+      "totalclks" => context.cycles.to_string(),
+      "lastclks" => {
+        let elapsed = context.cycles.saturating_sub(self.last_clocks[index]);
+
+        self.last_clocks[index] = context.cycles;
+
+        elapsed.to_string()
+      }
+      "zeroclks" => {
+        self.last_clocks[index] = context.cycles;
+
+        String::new()
+      }
+      _ => {
+        let register = param.strip_prefix('r')?.parse::<usize>().ok().filter(|register| *register < 16)?;
+
+        format!("{:08X}", context.registers[register])
+      }
+    };
+
+    Some(value)
+  }
+}
@@ -0,0 +1,252 @@
+pub mod fat_image;
+
+use std::{
+  fs::{File, OpenOptions},
+  io::{Read, Seek, SeekFrom, Write},
+  ops::Range,
+  path::Path
+};
+
+use fat_image::SECTOR_SIZE;
+
+/*
+  An sd card for homebrew that uses libfat. The rom's dldi stub gets swapped out for a tiny driver
+  that passes every call through a window of io registers, and the bus answers those with sectors
+  from an image file or a directory turned into a FAT image.
+
+  The driver writes the sector, sector count and buffer address to the window, then the command,
+  and reads back the result. Only the arm9 can reach the window.
+ */
+
+pub const DLDI_IO_BASE: u32 = 0x4ff_d000;
+pub const DLDI_SECTOR: u32 = DLDI_IO_BASE;
+pub const DLDI_COUNT: u32 = DLDI_IO_BASE + 0x4;
+pub const DLDI_BUFFER: u32 = DLDI_IO_BASE + 0x8;
+pub const DLDI_COMMAND: u32 = DLDI_IO_BASE + 0xc;
+pub const DLDI_RESULT: u32 = DLDI_IO_BASE + 0x10;
+
+const DLDI_MAGIC: [u8; 12] = [0xed, 0xa5, 0x8d, 0xbf, b' ', b'C', b'h', b'i', b's', b'h', b'm', 0];
+
+const FEATURE_CAN_READ: u32 = 0x1;
+const FEATURE_CAN_WRITE: u32 = 0x2;
+const FEATURE_SLOT_NDS: u32 = 0x20;
+
+const DRIVER_CODE_OFFSET: usize = 0x80;
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum DldiCommand {
+  Startup = 1,
+  IsInserted = 2,
+  ReadSectors = 3,
+  WriteSectors = 4,
+  ClearStatus = 5,
+  Shutdown = 6
+}
+
+impl DldiCommand {
+  pub fn from(value: u32) -> Option<Self> {
+    match value {
+      1 => Some(DldiCommand::Startup),
+      2 => Some(DldiCommand::IsInserted),
+      3 => Some(DldiCommand::ReadSectors),
+      4 => Some(DldiCommand::WriteSectors),
+      5 => Some(DldiCommand::ClearStatus),
+      6 => Some(DldiCommand::Shutdown),
+      _ => None
+    }
+  }
+}
+
+enum Storage {
+  File(File),
+  Memory(Vec<u8>)
+}
+
+pub struct SdCard {
+  storage: Storage,
+  pub sector: u32,
+  pub count: u32,
+  pub buffer: u32,
+  pub result: u32
+}
+
+impl SdCard {
+  // image files are read and written in place, directories get turned into an image in memory
+  pub fn open(path: &Path) -> Option<Self> {
+    let storage = if path.is_dir() {
+      Storage::Memory(fat_image::from_directory(path)?)
+    } else {
+      Storage::File(OpenOptions::new().read(true).write(true).open(path).ok()?)
+    };
+
+    Some(Self {
+      storage,
+      sector: 0,
+      count: 0,
+      buffer: 0,
+      result: 0
+    })
+  }
+
+  fn size(&self) -> Option<usize> {
+    match &self.storage {
+      Storage::File(file) => Some(file.metadata().ok()?.len() as usize),
+      Storage::Memory(image) => Some(image.len())
+    }
+  }
+
+  // the sector and count come straight from the guest, so anything running past the end of the image is refused
+  // before touching it
+  pub fn sector_range(&self, sector: u32, count: u32) -> Option<Range<usize>> {
+    let offset = (sector as usize).checked_mul(SECTOR_SIZE)?;
+    let end = offset.checked_add((count as usize).checked_mul(SECTOR_SIZE)?)?;
+
+    if end > self.size()? {
+      return None;
+    }
+
+    Some(offset..end)
+  }
+
+  pub fn read_sectors(&mut self, sector: u32, count: u32) -> Option<Vec<u8>> {
+    let range = self.sector_range(sector, count)?;
+
+    match &mut self.storage {
+      Storage::File(file) => {
+        let mut bytes = vec![0; range.len()];
+
+        file.seek(SeekFrom::Start(range.start as u64)).ok()?;
+        file.read_exact(&mut bytes).ok()?;
+
+        Some(bytes)
+      }
+      Storage::Memory(image) => Some(image[range].to_vec())
+    }
+  }
+
+  pub fn write_sectors(&mut self, sector: u32, bytes: &[u8]) -> bool {
+    let Some(range) = self.sector_range(sector, (bytes.len() / SECTOR_SIZE) as u32) else {
+      return false;
+    };
+
+    match &mut self.storage {
+      Storage::File(file) => {
+        file.seek(SeekFrom::Start(range.start as u64)).is_ok() && file.write_all(bytes).is_ok()
+      }
+      Storage::Memory(image) => {
+        image[range].copy_from_slice(bytes);
+        true
+      }
+    }
+  }
+}
+
+/*
+  The driver that replaces the stub. Every entry point loads its command number and jumps to the
+  same bit of code, which goes through the io window. It only uses pc relative addressing, so it
+  doesn't matter where the stub ended up.
+
+  startup:       mov r3, #1
+                 b common
+  ...            (one pair per function)
+  common:        ldr r12, =DLDI_IO_BASE
+                 str r0, [r12]          @ sector
+                 str r1, [r12, #4]      @ count
+                 str r2, [r12, #8]      @ buffer
+                 str r3, [r12, #0xc]    @ command
+                 ldr r0, [r12, #0x10]   @ result
+                 bx lr
+ */
+fn driver_code() -> Vec<u32> {
+  let mut code = Vec::new();
+
+  let entry_points: u32 = 6;
+  let common = entry_points * 2;
+
+  for i in 0..entry_points {
+    let branch_index = i * 2 + 1;
+
+    // branch offsets are relative to the branch plus 8, in words
+    let offset = common.wrapping_sub(branch_index + 2) & 0xff_ffff;
+
+    code.push(0xe3a0_3000 | (i + 1));
+    code.push(0xea00_0000 | offset);
+  }
+
+  let literal_index = common + 7;
+  let literal_offset = (literal_index - common - 2) * 4;
+
+  code.push(0xe59f_c000 | literal_offset);
+  code.push(0xe58c_0000);
+  code.push(0xe58c_1004);
+  code.push(0xe58c_2008);
+  code.push(0xe58c_300c);
+  code.push(0xe59c_0010);
+  code.push(0xe12f_ff1e);
+  code.push(DLDI_IO_BASE);
+
+  code
+}
+
+// finds the dldi stub in the arm9 binary and puts the emulator's driver in its place
+pub fn patch_rom(rom: &mut [u8], arm9_rom_offset: usize, arm9_size: usize) -> bool {
+  let end = (arm9_rom_offset + arm9_size).min(rom.len());
+
+  let Some(position) = rom.get(arm9_rom_offset..end).and_then(|arm9| arm9.windows(DLDI_MAGIC.len()).position(|window| window == DLDI_MAGIC)) else {
+    return false;
+  };
+
+  let stub = arm9_rom_offset + position;
+
+  let code = driver_code();
+  let driver_size = DRIVER_CODE_OFFSET + code.len() * 4;
+
+  let driver_size_log2 = driver_size.next_power_of_two().trailing_zeros() as u8;
+  let allocated_size_log2 = rom[stub + 0xf];
+
+  if driver_size_log2 > allocated_size_log2 || stub + driver_size > rom.len() {
+    println!("[WARN] the dldi stub is too small for the driver");
+    return false;
+  }
+
+  let read_32 = |rom: &[u8], offset: usize| u32::from_le_bytes(rom[offset..offset + 4].try_into().unwrap());
+  let write_32 = |rom: &mut [u8], offset: usize, value: u32| rom[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+
+  // the stub records the address it was linked at, which is where it'll be in memory
+  let address = read_32(rom, stub + 0x40);
+  let end_address = address + driver_size as u32;
+
+  rom[stub + 0xc] = 1;
+  rom[stub + 0xd] = driver_size_log2;
+  // no fixups needed
+  rom[stub + 0xe] = 0;
+
+  let mut name = [0u8; 0x30];
+  let friendly_name = b"NDS Plus virtual SD card";
+
+  name[..friendly_name.len()].copy_from_slice(friendly_name);
+
+  rom[stub + 0x10..stub + 0x40].copy_from_slice(&name);
+
+  write_32(rom, stub + 0x40, address);
+  write_32(rom, stub + 0x44, end_address);
+
+  // the glue, got and bss sections are all empty
+  for offset in (0x48..0x60).step_by(4) {
+    write_32(rom, stub + offset, end_address);
+  }
+
+  rom[stub + 0x60..stub + 0x64].copy_from_slice(b"NDSP");
+
+  write_32(rom, stub + 0x64, FEATURE_CAN_READ | FEATURE_CAN_WRITE | FEATURE_SLOT_NDS);
+
+  for i in 0..6 {
+    write_32(rom, stub + 0x68 + i * 4, address + (DRIVER_CODE_OFFSET + i * 8) as u32);
+  }
+
+  for (i, instruction) in code.iter().enumerate() {
+    write_32(rom, stub + DRIVER_CODE_OFFSET + i * 4, *instruction);
+  }
+
+  true
+}
@@ -0,0 +1,391 @@
+use std::{fs, path::Path};
+
+/*
+  Builds a FAT16 image in memory out of a directory on the host, so homebrew can read a folder
+  of files without anyone having to make a disk image first. Long names get lfn entries next to
+  a generated 8.3 name. Writes only go to the image, the directory itself is left alone.
+ */
+
+pub const SECTOR_SIZE: usize = 512;
+
+const RESERVED_SECTORS: usize = 1;
+const NUM_FATS: usize = 2;
+const ROOT_ENTRIES: usize = 512;
+const ENTRY_SIZE: usize = 32;
+
+// fat16 has to have at least this many clusters, or it's fat12
+const MIN_CLUSTERS: usize = 4085;
+const MAX_CLUSTERS: usize = 65524;
+
+const MAX_CLUSTER_SIZE: usize = 0x8000;
+
+// room for homebrew to write new files
+const FREE_SPACE: usize = 64 * 1024 * 1024;
+
+const ATTRIBUTE_DIRECTORY: u8 = 0x10;
+const ATTRIBUTE_ARCHIVE: u8 = 0x20;
+const ATTRIBUTE_LFN: u8 = 0x0f;
+
+const LFN_CHARS: usize = 13;
+
+enum Node {
+  File { name: String, data: Vec<u8> },
+  Directory { name: String, children: Vec<Node> }
+}
+
+impl Node {
+  fn name(&self) -> &str {
+    match self {
+      Node::File { name, .. } | Node::Directory { name, .. } => name
+    }
+  }
+}
+
+struct Builder {
+  image: Vec<u8>,
+  cluster_size: usize,
+  fat_offset: usize,
+  fat_sectors: usize,
+  data_offset: usize,
+  next_cluster: usize
+}
+
+pub fn from_directory(path: &Path) -> Option<Vec<u8>> {
+  let children = read_directory(path)?;
+
+  let mut cluster_size = 2048;
+
+  let (cluster_size, num_clusters) = loop {
+    let used = children.iter().map(|child| clusters_needed(child, cluster_size)).sum::<usize>();
+
+    let num_clusters = (used + FREE_SPACE / cluster_size).max(MIN_CLUSTERS);
+
+    if num_clusters <= MAX_CLUSTERS {
+      break (cluster_size, num_clusters);
+    }
+
+    if cluster_size == MAX_CLUSTER_SIZE {
+      println!("[WARN] {} is too big to fit on a FAT16 image", path.display());
+      return None;
+    }
+
+    cluster_size *= 2;
+  };
+
+  // two bytes per cluster, plus the two reserved entries at the start
+  let fat_sectors = ((num_clusters + 2) * 2).div_ceil(SECTOR_SIZE);
+  let root_sectors = ROOT_ENTRIES * ENTRY_SIZE / SECTOR_SIZE;
+
+  let fat_offset = RESERVED_SECTORS * SECTOR_SIZE;
+  let root_offset = fat_offset + NUM_FATS * fat_sectors * SECTOR_SIZE;
+  let data_offset = root_offset + root_sectors * SECTOR_SIZE;
+
+  let mut builder = Builder {
+    image: vec![0; data_offset + num_clusters * cluster_size],
+    cluster_size,
+    fat_offset,
+    fat_sectors,
+    data_offset,
+    next_cluster: 2
+  };
+
+  builder.write_boot_sector();
+
+  builder.set_fat_entry(0, 0xfff8);
+  builder.set_fat_entry(1, 0xffff);
+
+  let root = builder.write_directory_entries(&children, None);
+
+  if root.len() > ROOT_ENTRIES * ENTRY_SIZE {
+    println!("[WARN] too many files in the root of {}, some were left out", path.display());
+  }
+
+  let length = root.len().min(ROOT_ENTRIES * ENTRY_SIZE);
+
+  builder.image[root_offset..root_offset + length].copy_from_slice(&root[..length]);
+
+  builder.copy_fat();
+
+  Some(builder.image)
+}
+
+fn read_directory(path: &Path) -> Option<Vec<Node>> {
+  let mut children = Vec::new();
+
+  let mut entries: Vec<_> = fs::read_dir(path).ok()?.filter_map(|entry| entry.ok()).collect();
+
+  // keep the image the same between runs
+  entries.sort_by_key(|entry| entry.file_name());
+
+  for entry in entries {
+    let name = entry.file_name().to_string_lossy().to_string();
+    let path = entry.path();
+
+    if path.is_dir() {
+      children.push(Node::Directory { name, children: read_directory(&path)? });
+    } else if let Ok(data) = fs::read(&path) {
+      children.push(Node::File { name, data });
+    }
+  }
+
+  Some(children)
+}
+
+fn clusters_needed(node: &Node, cluster_size: usize) -> usize {
+  match node {
+    Node::File { data, .. } => data.len().div_ceil(cluster_size),
+    Node::Directory { children, .. } => {
+      let entries = 2 + children.iter().map(|child| 1 + lfn_entries(child.name())).sum::<usize>();
+
+      (entries * ENTRY_SIZE).div_ceil(cluster_size).max(1)
+        + children.iter().map(|child| clusters_needed(child, cluster_size)).sum::<usize>()
+    }
+  }
+}
+
+fn lfn_entries(name: &str) -> usize {
+  if short_name_exact(name).is_some() {
+    0
+  } else {
+    name.encode_utf16().count().div_ceil(LFN_CHARS)
+  }
+}
+
+// names that already fit in 8.3 don't need an lfn entry
+fn short_name_exact(name: &str) -> Option<[u8; 11]> {
+  let (base, extension) = match name.rsplit_once('.') {
+    Some((base, extension)) => (base, extension),
+    None => (name, "")
+  };
+
+  let valid = |part: &str, length: usize| {
+    !part.is_empty() && part.len() <= length && part.chars().all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || "_-~!#$%&'()@^`{}".contains(c))
+  };
+
+  if !valid(base, 8) || (!extension.is_empty() && !valid(extension, 3)) {
+    return None;
+  }
+
+  let mut short_name = [b' '; 11];
+
+  short_name[..base.len()].copy_from_slice(base.as_bytes());
+  short_name[8..8 + extension.len()].copy_from_slice(extension.as_bytes());
+
+  Some(short_name)
+}
+
+fn generated_short_name(name: &str, index: usize) -> [u8; 11] {
+  let (base, extension) = match name.rsplit_once('.') {
+    Some((base, extension)) if !base.is_empty() => (base, extension),
+    _ => (name, "")
+  };
+
+  let clean = |part: &str| -> Vec<u8> {
+    part.chars().filter(|c| c.is_ascii_alphanumeric()).map(|c| c.to_ascii_uppercase() as u8).collect()
+  };
+
+  let tail = format!("~{}", index);
+
+  let mut base = clean(base);
+  base.truncate(8 - tail.len());
+  base.extend_from_slice(tail.as_bytes());
+
+  let mut extension = clean(extension);
+  extension.truncate(3);
+
+  let mut short_name = [b' '; 11];
+
+  short_name[..base.len()].copy_from_slice(&base);
+  short_name[8..8 + extension.len()].copy_from_slice(&extension);
+
+  short_name
+}
+
+fn short_name_checksum(short_name: &[u8; 11]) -> u8 {
+  short_name.iter().fold(0u8, |sum, byte| ((sum & 0b1) << 7).wrapping_add(sum >> 1).wrapping_add(*byte))
+}
+
+impl Builder {
+  fn write_boot_sector(&mut self) {
+    let total_sectors = self.image.len() / SECTOR_SIZE;
+
+    let sector = &mut self.image[0..SECTOR_SIZE];
+
+    sector[0..3].copy_from_slice(&[0xeb, 0x3c, 0x90]);
+    sector[3..11].copy_from_slice(b"NDSPLUS ");
+    sector[0xb..0xd].copy_from_slice(&(SECTOR_SIZE as u16).to_le_bytes());
+    sector[0xd] = (self.cluster_size / SECTOR_SIZE) as u8;
+    sector[0xe..0x10].copy_from_slice(&(RESERVED_SECTORS as u16).to_le_bytes());
+    sector[0x10] = NUM_FATS as u8;
+    sector[0x11..0x13].copy_from_slice(&(ROOT_ENTRIES as u16).to_le_bytes());
+
+    if total_sectors < 0x10000 {
+      sector[0x13..0x15].copy_from_slice(&(total_sectors as u16).to_le_bytes());
+    } else {
+      sector[0x20..0x24].copy_from_slice(&(total_sectors as u32).to_le_bytes());
+    }
+
+    sector[0x15] = 0xf8;
+    sector[0x16..0x18].copy_from_slice(&(self.fat_sectors as u16).to_le_bytes());
+    sector[0x18..0x1a].copy_from_slice(&63u16.to_le_bytes());
+    sector[0x1a..0x1c].copy_from_slice(&255u16.to_le_bytes());
+    sector[0x24] = 0x80;
+    sector[0x26] = 0x29;
+    sector[0x27..0x2b].copy_from_slice(&0x1234_5678u32.to_le_bytes());
+    sector[0x2b..0x36].copy_from_slice(b"NDS PLUS   ");
+    sector[0x36..0x3e].copy_from_slice(b"FAT16   ");
+    sector[0x1fe..0x200].copy_from_slice(&[0x55, 0xaa]);
+  }
+
+  fn set_fat_entry(&mut self, cluster: usize, value: u16) {
+    let offset = self.fat_offset + cluster * 2;
+
+    self.image[offset..offset + 2].copy_from_slice(&value.to_le_bytes());
+  }
+
+  fn copy_fat(&mut self) {
+    let fat_size = self.fat_sectors * SECTOR_SIZE;
+
+    let fat = self.image[self.fat_offset..self.fat_offset + fat_size].to_vec();
+
+    for i in 1..NUM_FATS {
+      let offset = self.fat_offset + i * fat_size;
+
+      self.image[offset..offset + fat_size].copy_from_slice(&fat);
+    }
+  }
+
+  // stores the data in a fresh chain of clusters and returns the first one, or 0 for nothing
+  fn write_chain(&mut self, data: &[u8]) -> usize {
+    if data.is_empty() {
+      return 0;
+    }
+
+    let first_cluster = self.next_cluster;
+    let clusters = data.len().div_ceil(self.cluster_size);
+
+    for i in 0..clusters {
+      let cluster = first_cluster + i;
+
+      let next = if i + 1 == clusters { 0xffff } else { cluster as u16 + 1 };
+
+      self.set_fat_entry(cluster, next);
+
+      let chunk = &data[i * self.cluster_size..((i + 1) * self.cluster_size).min(data.len())];
+      let offset = self.data_offset + (cluster - 2) * self.cluster_size;
+
+      self.image[offset..offset + chunk.len()].copy_from_slice(chunk);
+    }
+
+    self.next_cluster += clusters;
+
+    first_cluster
+  }
+
+  // builds the entries of a directory, writing out everything in it along the way. the root has
+  // no clusters of its own, since it lives in a fixed area right before the data
+  fn write_directory_entries(&mut self, children: &[Node], own_clusters: Option<(usize, usize)>) -> Vec<u8> {
+    let mut entries = Vec::new();
+
+    if let Some((cluster, parent_cluster)) = own_clusters {
+      entries.extend(directory_entry(*b".          ", ATTRIBUTE_DIRECTORY, cluster, 0));
+      entries.extend(directory_entry(*b"..         ", ATTRIBUTE_DIRECTORY, parent_cluster, 0));
+    }
+
+    let parent = own_clusters.map(|(cluster, _)| cluster).unwrap_or(0);
+
+    for (index, child) in children.iter().enumerate() {
+      let (short_name, needs_lfn) = match short_name_exact(child.name()) {
+        Some(short_name) => (short_name, false),
+        None => (generated_short_name(child.name(), index + 1), true)
+      };
+
+      if needs_lfn {
+        entries.extend(lfn_entries_for(child.name(), &short_name));
+      }
+
+      match child {
+        Node::File { data, .. } => {
+          let cluster = self.write_chain(data);
+
+          entries.extend(directory_entry(short_name, ATTRIBUTE_ARCHIVE, cluster, data.len() as u32));
+        }
+        Node::Directory { children, .. } => {
+          // the directory needs to know its own cluster before its entries can be written
+          let entry_count = 2 + children.iter().map(|child| 1 + lfn_entries(child.name())).sum::<usize>();
+          let placeholder = vec![0; (entry_count * ENTRY_SIZE).div_ceil(self.cluster_size).max(1) * self.cluster_size];
+
+          let cluster = self.write_chain(&placeholder);
+
+          let directory = self.write_directory_entries(children, Some((cluster, parent)));
+
+          self.fill_chain(cluster, &directory);
+
+          entries.extend(directory_entry(short_name, ATTRIBUTE_DIRECTORY, cluster, 0));
+        }
+      }
+    }
+
+    entries
+  }
+
+  fn fill_chain(&mut self, first_cluster: usize, data: &[u8]) {
+    for (i, chunk) in data.chunks(self.cluster_size).enumerate() {
+      let offset = self.data_offset + (first_cluster + i - 2) * self.cluster_size;
+
+      self.image[offset..offset + chunk.len()].copy_from_slice(chunk);
+    }
+  }
+}
+
+fn directory_entry(short_name: [u8; 11], attributes: u8, cluster: usize, size: u32) -> [u8; ENTRY_SIZE] {
+  let mut entry = [0; ENTRY_SIZE];
+
+  entry[0..11].copy_from_slice(&short_name);
+  entry[11] = attributes;
+  entry[0x1a..0x1c].copy_from_slice(&(cluster as u16).to_le_bytes());
+  entry[0x1c..0x20].copy_from_slice(&size.to_le_bytes());
+
+  entry
+}
+
+// lfn entries go in front of the short entry, last piece of the name first
+fn lfn_entries_for(name: &str, short_name: &[u8; 11]) -> Vec<u8> {
+  let mut characters: Vec<u16> = name.encode_utf16().collect();
+
+  let count = characters.len().div_ceil(LFN_CHARS);
+
+  // the name ends with a null if there's room, and the rest is padded with 0xffff
+  if characters.len() < count * LFN_CHARS {
+    characters.push(0);
+  }
+
+  characters.resize(count * LFN_CHARS, 0xffff);
+
+  let checksum = short_name_checksum(short_name);
+
+  let mut entries = Vec::new();
+
+  for sequence in (1..=count).rev() {
+    let mut entry = [0xffu8; ENTRY_SIZE];
+
+    let chunk = &characters[(sequence - 1) * LFN_CHARS..sequence * LFN_CHARS];
+
+    entry[0] = sequence as u8 | if sequence == count { 0x40 } else { 0 };
+    entry[11] = ATTRIBUTE_LFN;
+    entry[12] = 0;
+    entry[13] = checksum;
+    entry[26] = 0;
+    entry[27] = 0;
+
+    let offsets = [1, 3, 5, 7, 9, 14, 16, 18, 20, 22, 24, 28, 30];
+
+    for (character, offset) in chunk.iter().zip(offsets) {
+      entry[offset..offset + 2].copy_from_slice(&character.to_le_bytes());
+    }
+
+    entries.extend(entry);
+  }
+
+  entries
+}
@@ -0,0 +1,279 @@
+use std::mem;
+
+use crate::{
+  cpu::{
+    registers::{
+      external_memory::AccessRights,
+      interrupt_enable_register::InterruptEnableRegister,
+      interrupt_request_register::InterruptRequestRegister
+    },
+    MemoryAccess
+  },
+  number::Number,
+  scheduler::EventType
+};
+
+use super::{Bus, HaltMode};
+
+/*
+  In GBA mode the ARM7 is the only cpu running, and it gets the GBA memory map: 256KB of
+  main memory becomes EWRAM, 32KB of shared WRAM becomes IWRAM, and engine A with
+  VRAM banks A and B stand in for the GBA's PPU.
+ */
+
+pub const GBA_BIOS_SIZE: usize = 0x4000;
+
+const GBA_EWRAM_MASK: u32 = 0x3_ffff;
+const GBA_IWRAM_MASK: u32 = 0x7fff;
+
+// the tile and bitmap data lives below this offset, the obj tiles above it
+const GBA_OBJ_VRAM_OFFSET: u32 = 0x1_0000;
+const GBA_BITMAP_VRAM_END: u32 = 0x1_4000;
+
+impl Bus {
+  pub fn enter_gba_mode(&mut self) {
+    self.gba_mode = true;
+
+    self.arm7.timers.set_gba_mode(true);
+    self.arm7.dma.set_gba_mode(true);
+    self.arm7.apu.gba_mode = true;
+
+    self.arm7.haltcnt = HaltMode::None;
+    self.arm7.interrupt_master_enable = false;
+    self.arm7.interrupt_enable = InterruptEnableRegister::from_bits_retain(0);
+    self.arm7.interrupt_request = InterruptRequestRegister::from_bits_retain(0);
+
+    // the gba cartridge belongs to the arm7 from here on out
+    self.exmem.gba_access_rights = AccessRights::Arm7;
+
+    // the ds sound channels can't be reached anymore
+    for channel_id in 0..self.arm7.apu.channels.len() {
+      self.scheduler.remove(EventType::StepAudio(channel_id));
+    }
+
+    self.cycle_luts.init();
+    self.cycle_luts.update_tables(&self.waitcnt);
+
+    self.gpu.enter_gba_mode();
+  }
+
+  // the lookup tables are in gba cycles, the scheduler runs at twice the gba clock
+  pub fn get_gba_cycles(&self, address: u32, access: MemoryAccess, is_32bit: bool) -> usize {
+    let page = ((address >> 24) & 0xf) as usize;
+
+    let cycles = match (access, is_32bit) {
+      (MemoryAccess::NonSequential, false) => self.cycle_luts.n_cycles_16[page],
+      (MemoryAccess::Sequential, false) => self.cycle_luts.s_cycles_16[page],
+      (MemoryAccess::NonSequential, true) => self.cycle_luts.n_cycles_32[page],
+      (MemoryAccess::Sequential, true) => self.cycle_luts.s_cycles_32[page]
+    };
+
+    2 * cycles as usize
+  }
+
+  pub fn gba_mem_read_32(&mut self, address: u32) -> u32 {
+    match address {
+      0x400_0000..=0x4ff_ffff => self.gba_io_read_16(address) as u32 | (self.gba_io_read_16(address + 2) as u32) << 16,
+      _ => self.gba_mem_read::<u32>(address)
+    }
+  }
+
+  pub fn gba_mem_read_16(&mut self, address: u32) -> u16 {
+    match address {
+      0x400_0000..=0x4ff_ffff => self.gba_io_read_16(address),
+      _ => self.gba_mem_read::<u16>(address)
+    }
+  }
+
+  pub fn gba_mem_read_8(&mut self, address: u32) -> u8 {
+    match address {
+      0x400_0000..=0x4ff_ffff => (self.gba_io_read_16(address & !(0b1)) >> ((address & 0b1) * 8)) as u8,
+      _ => self.gba_mem_read::<u8>(address)
+    }
+  }
+
+  fn gba_mem_read<T: Number>(&mut self, address: u32) -> T {
+    match address {
+      0..=0x3fff => {
+        if address as usize + mem::size_of::<T>() > self.gba_bios.len() {
+          return num::zero();
+        }
+
+        unsafe { *(&self.gba_bios[address as usize] as *const u8 as *const T) }
+      }
+      0x200_0000..=0x2ff_ffff => {
+        unsafe { *(&self.main_memory[(address & GBA_EWRAM_MASK) as usize] as *const u8 as *const T) }
+      }
+      0x300_0000..=0x3ff_ffff => {
+        unsafe { *(&self.shared_wram[(address & GBA_IWRAM_MASK) as usize] as *const u8 as *const T) }
+      }
+      0x500_0000..=0x5ff_ffff => self.gpu.read_palette_a(address),
+      // obj tiles are mirrored into engine a's bg vram, so every read can come from there
+      0x600_0000..=0x6ff_ffff => self.gpu.vram.read_engine_a_bg(Self::gba_vram_offset(address)),
+      0x700_0000..=0x7ff_ffff => {
+        unsafe { *(&self.gpu.engine_a.oam[(address & 0x3ff) as usize] as *const u8 as *const T) }
+      }
+      0x800_0000..=0xdff_ffff => match &mut self.gba_cartridge {
+        Some(gba_cartridge) => gba_cartridge.read_rom(address),
+        None => num::zero()
+      },
+      0xe00_0000..=0xfff_ffff => match &self.gba_cartridge {
+        Some(gba_cartridge) => gba_cartridge.read_sram(address),
+        None => num::cast::<u32, T>(0xffff_ffff).unwrap_or(num::zero())
+      },
+      _ => num::zero()
+    }
+  }
+
+  pub fn gba_mem_write_32(&mut self, address: u32, value: u32) {
+    match address {
+      0x400_00a0..=0x400_00a7 => self.arm7.apu.gba_sound.write_fifo(address, value, 4),
+      0x400_00b0..=0x400_00de => self.arm7_io_write_32(address, value),
+      0x400_0000..=0x4ff_ffff => {
+        self.gba_io_write_16(address, value as u16);
+        self.gba_io_write_16(address + 2, (value >> 16) as u16);
+      }
+      _ => self.gba_mem_write::<u32>(address, value)
+    }
+  }
+
+  pub fn gba_mem_write_16(&mut self, address: u32, value: u16) {
+    match address {
+      0x400_0000..=0x4ff_ffff => self.gba_io_write_16(address, value),
+      _ => self.gba_mem_write::<u16>(address, value)
+    }
+  }
+
+  pub fn gba_mem_write_8(&mut self, address: u32, value: u8) {
+    match address {
+      0x400_0000..=0x4ff_ffff => self.gba_io_write_8(address, value),
+      // byte writes to palette ram and bg vram write the byte to both halves of the halfword
+      0x500_0000..=0x5ff_ffff => self.gpu.write_palette_a(address & !(0b1), value as u16 * 0x0101),
+      0x600_0000..=0x6ff_ffff => {
+        let offset = Self::gba_vram_offset(address);
+
+        let bg_end = if self.gpu.engine_a.gba_bitmap_mode() {
+          GBA_BITMAP_VRAM_END
+        } else {
+          GBA_OBJ_VRAM_OFFSET
+        };
+
+        if offset < bg_end {
+          self.gba_mem_write::<u16>(address & !(0b1), value as u16 * 0x0101);
+        }
+      }
+      // while byte writes to obj vram and oam are ignored entirely
+      0x700_0000..=0x7ff_ffff => (),
+      _ => self.gba_mem_write::<u8>(address, value)
+    }
+  }
+
+  fn gba_mem_write<T: Number>(&mut self, address: u32, value: T) {
+    match address {
+      0x200_0000..=0x2ff_ffff => {
+        unsafe { *(&mut self.main_memory[(address & GBA_EWRAM_MASK) as usize] as *mut u8 as *mut T) = value }
+      }
+      0x300_0000..=0x3ff_ffff => {
+        unsafe { *(&mut self.shared_wram[(address & GBA_IWRAM_MASK) as usize] as *mut u8 as *mut T) = value }
+      }
+      0x500_0000..=0x5ff_ffff => self.gpu.write_palette_a(address, value),
+      0x600_0000..=0x6ff_ffff => {
+        let offset = Self::gba_vram_offset(address);
+
+        self.gpu.vram.write_engine_a_bg(offset, value);
+
+        if offset >= GBA_OBJ_VRAM_OFFSET {
+          self.gpu.vram.write_engine_a_obj(offset - GBA_OBJ_VRAM_OFFSET, value);
+        }
+      }
+      0x700_0000..=0x7ff_ffff => {
+        unsafe { *(&mut self.gpu.engine_a.oam[(address & 0x3ff) as usize] as *mut u8 as *mut T) = value }
+      }
+      0x800_0000..=0xdff_ffff => {
+        if let Some(gba_cartridge) = &mut self.gba_cartridge {
+          gba_cartridge.write_rom(address, value);
+        }
+      }
+      0xe00_0000..=0xfff_ffff => {
+        if let Some(gba_cartridge) = &mut self.gba_cartridge {
+          gba_cartridge.write_sram(address, value);
+        }
+      }
+      _ => ()
+    }
+  }
+
+  // vram is 96KB, with the last 32KB mirrored across the upper half of every 128KB block
+  fn gba_vram_offset(address: u32) -> u32 {
+    let offset = address & 0x1_ffff;
+
+    if offset >= 0x1_8000 {
+      offset - 0x8000
+    } else {
+      offset
+    }
+  }
+
+  fn gba_io_read_16(&mut self, address: u32) -> u16 {
+    match address {
+      0x400_0000 => self.gpu.engine_a.gba_dispcnt,
+      0x400_0002 => 0,
+      0x400_0004 => self.gpu.dispstat[0].read() & !(0x80),
+      0x400_0006 => self.gpu.vcount,
+      0x400_0008..=0x400_005f => self.gpu.engine_a.read_register(address),
+      0x400_0060..=0x400_00a7 => self.arm7.apu.gba_sound.read(address),
+      0x400_00b0..=0x400_00de | 0x400_0100..=0x400_010e | 0x400_0130 | 0x400_0132 => self.arm7_io_read_16(address),
+      0x400_0200 => self.arm7.interrupt_enable.bits() as u16,
+      0x400_0202 => self.arm7.interrupt_request.bits() as u16,
+      0x400_0204 => self.waitcnt.value,
+      0x400_0208 => self.arm7.interrupt_master_enable as u16,
+      0x400_0300 => self.arm7.postflg as u16,
+      _ => 0
+    }
+  }
+
+  fn gba_io_write_16(&mut self, address: u32, value: u16) {
+    match address {
+      0x400_0000 => self.gpu.engine_a.write_gba_dispcnt(value),
+      0x400_0002 => (), // green swap
+      // the gba's vcount setting is only 8 bits
+      0x400_0004 => self.gpu.dispstat[0].write(value & !(0x80)),
+      0x400_0006 => (),
+      0x400_0008..=0x400_005f => self.gpu.engine_a.write_register(address, value, None),
+      0x400_0060..=0x400_00a7 => self.arm7.apu.gba_sound.write(address, value),
+      0x400_00b0..=0x400_00de | 0x400_0100..=0x400_010e => self.arm7_io_write_16(address, value),
+      0x400_0132 => self.write_keycnt(value, false),
+      0x400_0120..=0x400_015a => (), // serial registers
+      0x400_0200 => self.arm7.interrupt_enable = InterruptEnableRegister::from_bits_retain(value as u32),
+      0x400_0202 => self.arm7.interrupt_request = InterruptRequestRegister::from_bits_retain(self.arm7.interrupt_request.bits() & !(value as u32)),
+      0x400_0204 => {
+        self.waitcnt.value = value;
+        self.cycle_luts.update_tables(&self.waitcnt);
+      }
+      0x400_0206 | 0x400_020a => (),
+      0x400_0208 => self.arm7.interrupt_master_enable = value & 0b1 != 0,
+      0x400_0300 => {
+        self.gba_io_write_8(address, value as u8);
+        self.gba_io_write_8(address + 1, (value >> 8) as u8);
+      }
+      _ => println!("[WARN] gba mode write to io register not implemented: {:X}", address)
+    }
+  }
+
+  fn gba_io_write_8(&mut self, address: u32, value: u8) {
+    match address {
+      0x400_0060..=0x400_00a7 => self.arm7.apu.gba_sound.write_8(address, value),
+      0x400_0202 => self.gba_io_write_16(address, value as u16),
+      0x400_0203 => self.gba_io_write_16(address - 1, (value as u16) << 8),
+      0x400_0300 => self.arm7.postflg |= value & 0b1 == 1,
+      // stop mode isn't emulated, so it behaves the same as halt
+      0x400_0301 => self.arm7.haltcnt = HaltMode::Halt,
+      _ => {
+        let shift = (address & 0b1) * 8;
+        let old_value = self.gba_io_read_16(address & !(0b1));
+
+        self.gba_io_write_16(address & !(0b1), old_value & !(0xff << shift) | (value as u16) << shift);
+      }
+    }
+  }
+}
@@ -0,0 +1,188 @@
+use std::{fs, path::PathBuf};
+
+use gba_eeprom::GbaEeprom;
+use gba_flash::GbaFlash;
+
+use crate::number::Number;
+
+use super::backup_file::BackupFile;
+
+pub mod gba_flash;
+pub mod gba_eeprom;
+
+const SRAM_SIZE: usize = 0x8000;
+const FLASH_64K_SIZE: usize = 0x1_0000;
+const FLASH_128K_SIZE: usize = 0x2_0000;
+const EEPROM_SIZE: usize = 0x2000;
+const EEPROM_SMALL_SIZE: usize = 0x200;
+
+pub enum GbaBackupType {
+  None,
+  Sram(BackupFile),
+  Flash(GbaFlash),
+  Eeprom(GbaEeprom)
+}
+
+pub struct GbaCartridge {
+  pub rom: Vec<u8>,
+  pub backup: GbaBackupType
+}
+
+impl GbaCartridge {
+  pub fn new(rom: Vec<u8>, save_path: Option<PathBuf>, save_bytes: Option<Vec<u8>>) -> Self {
+    let mut cartridge = Self {
+      rom,
+      backup: GbaBackupType::None
+    };
+
+    cartridge.detect_backup_type(save_path, save_bytes);
+
+    cartridge
+  }
+
+  // GBA games built with the official SDK embed the name of their save library in the rom
+  fn detect_backup_type(&mut self, save_path: Option<PathBuf>, save_bytes: Option<Vec<u8>>) {
+    let contains = |pattern: &[u8]| self.rom.windows(pattern.len()).any(|window| window == pattern);
+
+    let (save_type, capacity) = if contains(b"EEPROM_V") {
+      ("eeprom", EEPROM_SIZE)
+    } else if contains(b"SRAM_V") || contains(b"SRAM_F_V") {
+      ("sram", SRAM_SIZE)
+    } else if contains(b"FLASH1M_V") {
+      ("flash", FLASH_128K_SIZE)
+    } else if contains(b"FLASH_V") || contains(b"FLASH512_V") {
+      ("flash", FLASH_64K_SIZE)
+    } else {
+      println!("warning: no GBA save type detected");
+      return;
+    };
+
+    println!("detected GBA backup type {}", save_type);
+
+    let existing_size = match (&save_path, &save_bytes) {
+      (Some(path), _) => fs::metadata(path).map(|metadata| metadata.len() as usize).ok(),
+      (None, Some(bytes)) => Some(bytes.len()),
+      (None, None) => None
+    };
+
+    // without a save file, keep the save in memory only
+    let save_bytes = if save_path.is_none() {
+      Some(save_bytes.unwrap_or_default())
+    } else {
+      save_bytes
+    };
+
+    let backup_file = BackupFile::new(save_path, save_bytes, capacity, false);
+
+    self.backup = match save_type {
+      "eeprom" => {
+        // a save the size of the small eeprom settles it before the game even starts
+        let address_width = if existing_size == Some(EEPROM_SMALL_SIZE) { Some(6) } else { None };
+
+        GbaBackupType::Eeprom(GbaEeprom::new(backup_file, address_width))
+      }
+      "sram" => GbaBackupType::Sram(backup_file),
+      "flash" => GbaBackupType::Flash(GbaFlash::new(backup_file)),
+      _ => unreachable!()
+    };
+  }
+
+  // the first dma to the eeprom tells how wide its addresses are
+  pub fn notify_dma(&mut self, destination_address: u32, count: u32) {
+    // the rom is mapped at 8000000h-dffffffh in gba mode, and only up to 9ffffffh in slot 2 of a ds
+    if (0x800_0000..0xe00_0000).contains(&destination_address) && self.is_eeprom_address(destination_address) {
+      if let GbaBackupType::Eeprom(eeprom) = &mut self.backup {
+        eeprom.detect_address_width(count);
+      }
+    }
+  }
+
+  /*
+    EEPROM sits at the top of the rom address space: anywhere from 1000000h on for roms
+    up to 16MB, and only the last 100h bytes for 32MB roms.
+   */
+  fn is_eeprom_address(&self, address: u32) -> bool {
+    if !matches!(self.backup, GbaBackupType::Eeprom(_)) {
+      return false;
+    }
+
+    let offset = address & 0x1ff_ffff;
+
+    if self.rom.len() > 0x100_0000 {
+      offset >= 0x1ff_ff00
+    } else {
+      offset >= 0x100_0000
+    }
+  }
+
+  // the rom bus is 16 bits wide
+  fn read_rom_16(&mut self, address: u32) -> u16 {
+    if self.is_eeprom_address(address) {
+      if let GbaBackupType::Eeprom(eeprom) = &mut self.backup {
+        return eeprom.read();
+      }
+    }
+
+    let offset = (address & 0x1ff_fffe) as usize;
+
+    if offset + 1 < self.rom.len() {
+      self.rom[offset] as u16 | (self.rom[offset + 1] as u16) << 8
+    } else {
+      // open bus returns the lower bits of the address
+      (offset / 2) as u16
+    }
+  }
+
+  pub fn read_rom<T: Number>(&mut self, address: u32) -> T {
+    let value = match std::mem::size_of::<T>() {
+      1 => (self.read_rom_16(address) >> ((address & 0b1) * 8)) as u32 & 0xff,
+      2 => self.read_rom_16(address) as u32,
+      _ => self.read_rom_16(address) as u32 | (self.read_rom_16(address + 2) as u32) << 16
+    };
+
+    num::cast::<u32, T>(value).unwrap_or(num::zero())
+  }
+
+  pub fn write_rom<T: Number>(&mut self, address: u32, value: T) {
+    if self.is_eeprom_address(address) {
+      if let GbaBackupType::Eeprom(eeprom) = &mut self.backup {
+        eeprom.write(num::cast::<T, u32>(value).unwrap() as u16);
+      }
+    }
+  }
+
+  fn read_sram_8(&self, address: u32) -> u8 {
+    match &self.backup {
+      GbaBackupType::Sram(backup_file) => backup_file.read((address as usize) & (SRAM_SIZE - 1)),
+      GbaBackupType::Flash(flash) => flash.read(address),
+      GbaBackupType::Eeprom(_) | GbaBackupType::None => 0xff
+    }
+  }
+
+  // the sram bus is only 8 bits wide, so wider reads see the same byte repeated
+  pub fn read_sram<T: Number>(&self, address: u32) -> T {
+    let byte = self.read_sram_8(address) as u32;
+
+    let value = match std::mem::size_of::<T>() {
+      1 => byte,
+      2 => byte * 0x0101,
+      _ => byte * 0x0101_0101
+    };
+
+    num::cast::<u32, T>(value).unwrap_or(num::zero())
+  }
+
+  pub fn write_sram<T: Number>(&mut self, address: u32, value: T) {
+    let shift = (address as usize & (std::mem::size_of::<T>() - 1)) * 8;
+    let byte = num::cast::<T, u32>(value).unwrap() >> shift;
+
+    match &mut self.backup {
+      GbaBackupType::Sram(backup_file) => {
+        backup_file.write((address as usize) & (SRAM_SIZE - 1), byte as u8);
+        backup_file.has_written = true;
+      }
+      GbaBackupType::Flash(flash) => flash.write(address, byte as u8),
+      GbaBackupType::Eeprom(_) | GbaBackupType::None => ()
+    }
+  }
+}
\ No newline at end of file
@@ -0,0 +1,146 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::cpu::bus::backup_file::BackupFile;
+
+#[derive(Copy, Clone, PartialEq)]
+enum EepromState {
+  AwaitingCommand,
+  Reading
+}
+
+/*
+  GBA EEPROM is accessed one bit at a time through bit 0 of 16-bit accesses.
+    Read request:  11b, address (6 or 14 bits), 0b. Then 4 dummy bits and 64 data bits are read back
+    Write request: 10b, address (6 or 14 bits), 64 data bits, 0b
+  Data and addresses are sent MSB first, and each address selects an 8-byte block.
+
+  Nothing in the rom says which of the two sizes a game has, but games send requests with a dma
+  sized to the request, so the length of the first one gives the address width away.
+ */
+pub struct GbaEeprom {
+  pub backup_file: BackupFile,
+  address_width: Option<usize>,
+  state: EepromState,
+  bits: Vec<bool>,
+  read_address: usize,
+  read_position: usize
+}
+
+impl GbaEeprom {
+  pub fn new(backup_file: BackupFile, address_width: Option<usize>) -> Self {
+    Self {
+      backup_file,
+      address_width,
+      state: EepromState::AwaitingCommand,
+      bits: Vec::new(),
+      read_address: 0,
+      read_position: 0
+    }
+  }
+
+  // 512 byte eeproms use 6 bit addresses, 8K ones use 14 bit addresses
+  pub fn detect_address_width(&mut self, dma_count: u32) {
+    if self.address_width.is_some() {
+      return;
+    }
+
+    // read requests are 2 + address + 1 bits long, write requests have 64 data bits on top
+    self.address_width = match dma_count {
+      9 | 73 => Some(6),
+      17 | 81 => Some(14),
+      _ => return
+    };
+
+    println!("detected {} bit GBA eeprom addresses", self.address_width.unwrap());
+  }
+
+  // games that don't use dma to talk to the eeprom are most likely the bigger kind
+  fn address_width(&self) -> usize {
+    self.address_width.unwrap_or(14)
+  }
+
+  fn get_address(&self) -> usize {
+    let mut address = 0;
+
+    for bit in &self.bits[2..2 + self.address_width()] {
+      address = (address << 1) | *bit as usize;
+    }
+
+    // only the lower 10 bits are used by the 8K variant
+    (address & 0x3ff) * 8 % self.backup_file.buffer.len()
+  }
+
+  pub fn read(&mut self) -> u16 {
+    if self.state != EepromState::Reading {
+      // ready
+      return 1;
+    }
+
+    let position = self.read_position;
+
+    self.read_position += 1;
+
+    if self.read_position == 68 {
+      self.state = EepromState::AwaitingCommand;
+    }
+
+    // first 4 bits are ignored
+    if position < 4 {
+      return 0;
+    }
+
+    let bit_index = position - 4;
+    let byte = self.backup_file.read(self.read_address + bit_index / 8);
+
+    ((byte >> (7 - bit_index % 8)) & 0b1) as u16
+  }
+
+  pub fn write(&mut self, value: u16) {
+    self.bits.push(value & 0b1 == 1);
+
+    if self.bits.len() < 2 {
+      return;
+    }
+
+    let is_read = self.bits[0] && self.bits[1];
+    let is_write = self.bits[0] && !self.bits[1];
+
+    if !is_read && !is_write {
+      self.bits.clear();
+      return;
+    }
+
+    let address_width = self.address_width();
+
+    if is_read && self.bits.len() == 2 + address_width + 1 {
+      self.read_address = self.get_address();
+      self.read_position = 0;
+      self.state = EepromState::Reading;
+
+      self.bits.clear();
+    } else if is_write && self.bits.len() == 2 + address_width + 64 + 1 {
+      let address = self.get_address();
+
+      for i in 0..8 {
+        let mut byte = 0;
+
+        for j in 0..8 {
+          byte = (byte << 1) | self.bits[2 + address_width + i * 8 + j] as u8;
+        }
+
+        self.backup_file.write(address + i, byte);
+      }
+
+      self.backup_file.has_written = true;
+      if self.backup_file.is_desktop_cloud {
+        self.backup_file.last_write = SystemTime::now()
+          .duration_since(UNIX_EPOCH)
+          .expect("an error occurred")
+          .as_millis();
+      }
+
+      self.state = EepromState::AwaitingCommand;
+      self.bits.clear();
+    }
+  }
+}
\ No newline at end of file
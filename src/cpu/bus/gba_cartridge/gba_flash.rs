@@ -0,0 +1,153 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::cpu::bus::backup_file::BackupFile;
+
+const BANK_SIZE: usize = 0x1_0000;
+const SECTOR_SIZE: usize = 0x1000;
+
+#[derive(Copy, Clone, PartialEq)]
+enum CommandState {
+  Ready,
+  FirstByte,
+  SecondByte
+}
+
+#[derive(Copy, Clone, PartialEq)]
+enum PendingOperation {
+  None,
+  WriteByte,
+  SelectBank
+}
+
+/*
+  GBA flash saves are driven by writing AAh to 5555h, 55h to 2AAAh and then the command to 5555h:
+    90h  enter ID mode
+    F0h  exit ID mode
+    80h  prepare erase (followed by another AAh/55h sequence and 10h for chip erase or 30h for sector erase)
+    A0h  program the next byte written
+    B0h  select the 64K bank with the next write to 0000h (128K flash only)
+ */
+pub struct GbaFlash {
+  pub backup_file: BackupFile,
+  state: CommandState,
+  pending: PendingOperation,
+  id_mode: bool,
+  erase_armed: bool,
+  bank: usize
+}
+
+impl GbaFlash {
+  pub fn new(backup_file: BackupFile) -> Self {
+    Self {
+      backup_file,
+      state: CommandState::Ready,
+      pending: PendingOperation::None,
+      id_mode: false,
+      erase_armed: false,
+      bank: 0
+    }
+  }
+
+  fn get_id(&self) -> [u8; 2] {
+    if self.backup_file.buffer.len() > BANK_SIZE {
+      // Sanyo, 128K
+      [0x62, 0x13]
+    } else {
+      // Panasonic, 64K
+      [0x32, 0x1b]
+    }
+  }
+
+  pub fn read(&self, address: u32) -> u8 {
+    let address = (address & 0xffff) as usize;
+
+    if self.id_mode && address < 2 {
+      return self.get_id()[address];
+    }
+
+    self.backup_file.read(self.bank * BANK_SIZE + address)
+  }
+
+  pub fn write(&mut self, address: u32, value: u8) {
+    let address = (address & 0xffff) as usize;
+
+    match self.pending {
+      PendingOperation::WriteByte => {
+        self.pending = PendingOperation::None;
+        self.backup_file.write(self.bank * BANK_SIZE + address, value);
+        self.mark_written();
+
+        return;
+      }
+      PendingOperation::SelectBank if address == 0 => {
+        self.pending = PendingOperation::None;
+
+        if self.backup_file.buffer.len() > BANK_SIZE {
+          self.bank = (value & 0b1) as usize;
+        }
+
+        return;
+      }
+      _ => ()
+    }
+
+    match (self.state, address, value) {
+      (CommandState::Ready, 0x5555, 0xaa) => self.state = CommandState::FirstByte,
+      (CommandState::FirstByte, 0x2aaa, 0x55) => self.state = CommandState::SecondByte,
+      (CommandState::SecondByte, _, _) => {
+        self.state = CommandState::Ready;
+        self.execute_command(address, value);
+      }
+      _ => self.state = CommandState::Ready
+    }
+  }
+
+  fn execute_command(&mut self, address: usize, command: u8) {
+    if self.erase_armed {
+      self.erase_armed = false;
+
+      match (address, command) {
+        (0x5555, 0x10) => {
+          for i in 0..self.backup_file.buffer.len() {
+            self.backup_file.write(i, 0xff);
+          }
+          self.mark_written();
+        }
+        (_, 0x30) => {
+          let base = self.bank * BANK_SIZE + (address & !(SECTOR_SIZE - 1));
+
+          for i in base..base + SECTOR_SIZE {
+            self.backup_file.write(i, 0xff);
+          }
+          self.mark_written();
+        }
+        _ => println!("unknown GBA flash erase command received: {:x}", command)
+      }
+
+      return;
+    }
+
+    if address != 0x5555 {
+      return;
+    }
+
+    match command {
+      0x90 => self.id_mode = true,
+      0xf0 => self.id_mode = false,
+      0x80 => self.erase_armed = true,
+      0xa0 => self.pending = PendingOperation::WriteByte,
+      0xb0 => self.pending = PendingOperation::SelectBank,
+      _ => println!("unknown GBA flash command received: {:x}", command)
+    }
+  }
+
+  fn mark_written(&mut self) {
+    self.backup_file.has_written = true;
+    if self.backup_file.is_desktop_cloud {
+      self.backup_file.last_write = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("an error occurred")
+        .as_millis();
+    }
+  }
+}
\ No newline at end of file
@@ -0,0 +1,164 @@
+use std::{collections::VecDeque, sync::{Arc, Mutex}};
+
+use super::flash::Flash;
+
+// returned by the IR chip for its ID command
+const IR_ID: u8 = 0xaa;
+
+/*
+  Something on the other side of the IR transceiver. Packets are sent whole, once the
+  game deselects the chip after an IR send command.
+ */
+pub trait IrEndpoint {
+  fn send(&mut self, packet: Vec<u8>);
+  fn receive(&mut self) -> Option<Vec<u8>>;
+}
+
+// sends every packet straight back to the cartridge
+#[derive(Default)]
+pub struct IrLoopback {
+  packets: VecDeque<Vec<u8>>
+}
+
+impl IrLoopback {
+  pub fn new() -> Self {
+    Self {
+      packets: VecDeque::new()
+    }
+  }
+}
+
+impl IrEndpoint for IrLoopback {
+  fn send(&mut self, packet: Vec<u8>) {
+    self.packets.push_back(packet);
+  }
+
+  fn receive(&mut self) -> Option<Vec<u8>> {
+    self.packets.pop_front()
+  }
+}
+
+// one end of a connection between two emulated cartridges (or a cartridge and something else)
+pub struct IrPeer {
+  outgoing: Arc<Mutex<VecDeque<Vec<u8>>>>,
+  incoming: Arc<Mutex<VecDeque<Vec<u8>>>>
+}
+
+impl IrPeer {
+  pub fn pair() -> (IrPeer, IrPeer) {
+    let a = Arc::new(Mutex::new(VecDeque::new()));
+    let b = Arc::new(Mutex::new(VecDeque::new()));
+
+    (
+      IrPeer {
+        outgoing: a.clone(),
+        incoming: b.clone()
+      },
+      IrPeer {
+        outgoing: b,
+        incoming: a
+      }
+    )
+  }
+}
+
+impl IrEndpoint for IrPeer {
+  fn send(&mut self, packet: Vec<u8>) {
+    self.outgoing.lock().unwrap().push_back(packet);
+  }
+
+  fn receive(&mut self) -> Option<Vec<u8>> {
+    self.incoming.lock().unwrap().pop_front()
+  }
+}
+
+/*
+  Pokemon HG/SS, B/W and friends put an IR transceiver in front of the save flash.
+  The first byte after selecting the chip picks what the rest of the transfer talks to:
+    00h  pass the transfer through to the flash
+    01h  receive an IR packet (length byte first, then the data)
+    02h  send an IR packet
+    08h  read IR chip ID
+ */
+pub struct Infrared {
+  pub flash: Flash,
+  endpoint: Option<Box<dyn IrEndpoint>>,
+  command: u8,
+  position: usize,
+  current_byte: u8,
+  rx_packet: VecDeque<u8>,
+  tx_packet: Vec<u8>
+}
+
+impl Infrared {
+  pub fn new(flash: Flash) -> Self {
+    Self {
+      flash,
+      endpoint: None,
+      command: 0,
+      position: 0,
+      current_byte: 0,
+      rx_packet: VecDeque::new(),
+      tx_packet: Vec::new()
+    }
+  }
+
+  pub fn connect(&mut self, endpoint: Box<dyn IrEndpoint>) {
+    self.endpoint = Some(endpoint);
+  }
+
+  pub fn disconnect(&mut self) {
+    self.endpoint = None;
+  }
+
+  pub fn write(&mut self, value: u8, hold: bool) {
+    if self.position == 0 {
+      self.command = value;
+      self.current_byte = 0;
+    } else {
+      match self.command {
+        0x00 => {
+          self.flash.write(value, hold);
+          self.current_byte = self.flash.read();
+        }
+        0x01 => {
+          if self.position == 1 {
+            self.rx_packet = self.endpoint
+              .as_mut()
+              .and_then(|endpoint| endpoint.receive())
+              .unwrap_or_default()
+              .into();
+
+            self.current_byte = self.rx_packet.len() as u8;
+          } else {
+            self.current_byte = self.rx_packet.pop_front().unwrap_or(0);
+          }
+        }
+        0x02 => self.tx_packet.push(value),
+        0x08 => self.current_byte = IR_ID,
+        _ => {
+          println!("unknown IR command received: {:x}", self.command);
+          self.current_byte = 0;
+        }
+      }
+    }
+
+    self.position += 1;
+
+    if !hold {
+      if self.command == 0x02 && !self.tx_packet.is_empty() {
+        let packet = std::mem::take(&mut self.tx_packet);
+
+        if let Some(endpoint) = &mut self.endpoint {
+          endpoint.send(packet);
+        }
+      }
+
+      self.position = 0;
+    }
+  }
+
+  pub fn read(&self) -> u8 {
+    self.current_byte
+  }
+}
\ No newline at end of file
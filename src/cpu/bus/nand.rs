@@ -0,0 +1,213 @@
+use std::{collections::VecDeque, time::{SystemTime, UNIX_EPOCH}};
+
+use super::backup_file::BackupFile;
+
+// the save area is accessed through a window of this size
+const WINDOW_SIZE: usize = 0x2_0000;
+const WRITE_BUFFER_SIZE: usize = 0x800;
+
+const STATUS_WRITE_ENABLE: u8 = 1 << 4;
+const STATUS_READY: u8 = 1 << 5;
+
+// taken from a Jam with the Band dump, presumably the ID of the NAND chip
+const NAND_ID: u32 = 0xec00_009f;
+
+/*
+  Some retail cards keep their saves in the same NAND chip as the rom, which is accessed
+  with additional gamecard commands instead of over AUXSPI:
+    81h  write data (200h bytes at a time into the write buffer)
+    82h  commit the write buffer
+    84h  discard the write buffer
+    85h  write enable
+    8Bh  switch back to rom mode
+    94h  read NAND ID
+    B2h  set the save area window
+    B7h  read data (from the window when one is set)
+    D6h  read NAND status
+ */
+pub struct Nand {
+  pub backup_file: BackupFile,
+  save_base: usize,
+  window: usize,
+  // where the write buffer goes once it's committed, set by the first 81h after a write enable
+  write_address: Option<usize>,
+  write_buffer: Box<[u8]>,
+  write_position: usize,
+  status: u8
+}
+
+impl Nand {
+  pub fn new(backup_file: BackupFile, save_base: usize) -> Self {
+    Self {
+      backup_file,
+      save_base,
+      window: 0,
+      write_address: None,
+      write_buffer: vec![0; WRITE_BUFFER_SIZE].into_boxed_slice(),
+      write_position: 0,
+      status: STATUS_READY
+    }
+  }
+
+  fn save_range_contains(&self, address: usize) -> bool {
+    address >= self.save_base && address < self.save_base + self.backup_file.buffer.len()
+  }
+
+  fn write_enabled(&self) -> bool {
+    self.status & STATUS_WRITE_ENABLE != 0
+  }
+
+  // anything outside of the window or the save area reads back as unprogrammed flash
+  fn read_window(&self, address: usize) -> u8 {
+    if address >= self.window && address < self.window + WINDOW_SIZE && self.save_range_contains(address) {
+      self.backup_file.read(address - self.save_base)
+    } else {
+      0xff
+    }
+  }
+
+  // returns false if the command should be handled as a regular rom command instead
+  pub fn execute_command(&mut self, command: &[u8; 8], bytes_left: usize, out_fifo: &mut VecDeque<u32>) -> bool {
+    let address = u32::from_be_bytes(command[1..5].try_into().unwrap()) as usize;
+
+    match command[0] {
+      0x81 => {
+        // the command is sent several times with the same address, only the first one counts
+        if self.write_enabled() && self.write_address.is_none() && address >= self.window && address < self.window + WINDOW_SIZE {
+          self.write_address = Some(address);
+        }
+
+        // the data comes from the cpu for this one, see write_data
+        return true;
+      }
+      0x82 => {
+        // a commit always ends the pending write, but only lands in the save if writes were enabled
+        if let Some(write_address) = self.write_address.take() {
+          if self.write_enabled() && self.write_position != 0 && self.save_range_contains(write_address) {
+            let base = write_address - self.save_base;
+
+            for i in 0..self.write_position {
+              if base + i < self.backup_file.buffer.len() {
+                self.backup_file.write(base + i, self.write_buffer[i]);
+              }
+            }
+
+            self.backup_file.has_written = true;
+            if self.backup_file.is_desktop_cloud {
+              self.backup_file.last_write = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .expect("an error occurred")
+                .as_millis();
+            }
+          }
+        }
+
+        self.write_position = 0;
+        self.status &= !STATUS_WRITE_ENABLE;
+      }
+      0x84 => {
+        self.write_address = None;
+        self.write_position = 0;
+      }
+      0x85 => {
+        self.status |= STATUS_WRITE_ENABLE;
+        self.write_address = None;
+        self.write_position = 0;
+      }
+      0x8b => self.window = 0,
+      0x94 => {
+        for i in 0..bytes_left / 4 {
+          out_fifo.push_back(if i == 0 { NAND_ID } else { 0xffff_ffff });
+        }
+      }
+      0xb2 => {
+        // the window is always aligned to 20000h bytes
+        self.window = address & !(WINDOW_SIZE - 1);
+
+        if !self.save_range_contains(self.window) {
+          println!("warning: NAND save window {:x} is outside of the save area", self.window);
+        }
+      }
+      0xb7 if self.window != 0 => {
+        for i in (0..bytes_left).step_by(4) {
+          let word = u32::from_le_bytes(std::array::from_fn(|j| self.read_window(address + i + j)));
+
+          out_fifo.push_back(word);
+        }
+
+        return true;
+      }
+      0xb7 => return false,
+      0xd6 => {
+        for _ in 0..bytes_left / 4 {
+          out_fifo.push_back(self.status as u32 * 0x0101_0101);
+        }
+      }
+      _ => return false
+    }
+
+    // commands without any data still need to fill up whatever block size was requested
+    while out_fifo.len() < bytes_left / 4 {
+      out_fifo.push_back(0xffff_ffff);
+    }
+
+    true
+  }
+
+  pub fn write_data(&mut self, command: &[u8; 8], value: u32) {
+    if command[0] == 0x81 && self.write_address.is_some() && self.write_position < WRITE_BUFFER_SIZE {
+      self.write_buffer[self.write_position..self.write_position + 4].copy_from_slice(&value.to_le_bytes());
+      self.write_position += 4;
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const SAVE_BASE: usize = 0x80_0000;
+  // save area of a 16mb chip
+  const SAVE_SIZE: usize = 0x100_0000 - SAVE_BASE;
+
+  fn command(command: u8, address: usize) -> [u8; 8] {
+    let mut bytes = [0; 8];
+
+    bytes[0] = command;
+    bytes[1..5].copy_from_slice(&(address as u32).to_be_bytes());
+
+    bytes
+  }
+
+  #[test]
+  fn pages_past_the_first_200h_bytes_are_saved() {
+    let backup_file = BackupFile::new(None, Some(Vec::new()), SAVE_SIZE, false);
+    let mut nand = Nand::new(backup_file, SAVE_BASE);
+    let mut out_fifo = VecDeque::new();
+
+    let window = SAVE_BASE + 0x2_0000;
+    let address = window + 0x1_0400;
+
+    nand.execute_command(&command(0xb2, window), 0, &mut out_fifo);
+    nand.execute_command(&command(0x85, 0), 0, &mut out_fifo);
+
+    let write = command(0x81, address);
+    nand.execute_command(&write, 0, &mut out_fifo);
+
+    for i in 0..0x200 / 4 {
+      nand.write_data(&write, 0x1234_0000 | i);
+    }
+
+    nand.execute_command(&command(0x82, 0), 0, &mut out_fifo);
+
+    assert!(nand.backup_file.has_written);
+
+    nand.execute_command(&command(0xb7, address), 0x200, &mut out_fifo);
+
+    assert_eq!(out_fifo.len(), 0x200 / 4);
+
+    for (i, word) in out_fifo.iter().enumerate() {
+      assert_eq!(*word, 0x1234_0000 | i as u32);
+    }
+  }
+}
@@ -0,0 +1,136 @@
+/*
+  The power management chip sits on the spi bus. A transfer is an index byte
+  (bit 7 set for reads) followed by a data byte, which is either written to the register
+  or answered with the register's value.
+ */
+
+const SOUND_AMP_ENABLE: u8 = 1;
+const SOUND_AMP_MUTE: u8 = 1 << 1;
+const LOWER_BACKLIGHT: u8 = 1 << 2;
+const UPPER_BACKLIGHT: u8 = 1 << 3;
+const SYSTEM_POWER_OFF: u8 = 1 << 6;
+
+const BATTERY_LOW: u8 = 1;
+
+// the ds lite's four backlight levels, as a fraction of full brightness
+const BACKLIGHT_LEVELS: [f32; 4] = [0.4, 0.6, 0.8, 1.0];
+
+pub struct PowerManager {
+  control: u8,
+  battery_status: u8,
+  mic_amp_control: u8,
+  mic_amp_gain: u8,
+  backlight_levels: u8,
+  index: Option<u8>,
+  return_byte: u8,
+  power_off_requested: bool
+}
+
+impl Default for PowerManager {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl PowerManager {
+  pub fn new() -> Self {
+    Self {
+      // sound amp and both backlights are on at boot
+      control: SOUND_AMP_ENABLE | LOWER_BACKLIGHT | UPPER_BACKLIGHT,
+      battery_status: 0,
+      mic_amp_control: 0,
+      mic_amp_gain: 0,
+      backlight_levels: 0x3,
+      index: None,
+      return_byte: 0,
+      power_off_requested: false
+    }
+  }
+
+  pub fn write(&mut self, value: u8, chipselect_hold: bool) {
+    match self.index {
+      None => {
+        self.index = Some(value);
+        self.return_byte = 0;
+      }
+      Some(index) => {
+        if (index >> 7) & 0b1 == 1 {
+          self.return_byte = self.read_register(index & 0x7f);
+        } else {
+          self.write_register(index & 0x7f, value);
+          self.return_byte = 0;
+        }
+      }
+    }
+
+    if !chipselect_hold {
+      self.index = None;
+    }
+  }
+
+  pub fn read(&self) -> u8 {
+    self.return_byte
+  }
+
+  pub fn deselect(&mut self) {
+    self.index = None;
+  }
+
+  fn read_register(&self, index: u8) -> u8 {
+    match index {
+      0 => self.control,
+      1 => self.battery_status,
+      2 => self.mic_amp_control,
+      3 => self.mic_amp_gain,
+      4 => self.backlight_levels,
+      _ => 0
+    }
+  }
+
+  fn write_register(&mut self, index: u8, value: u8) {
+    match index {
+      0 => {
+        self.control = value & 0x7f;
+
+        if value & SYSTEM_POWER_OFF != 0 {
+          self.power_off_requested = true;
+        }
+      }
+      1 => (), // battery status is read only
+      2 => self.mic_amp_control = value & 0b1,
+      3 => self.mic_amp_gain = value & 0x3,
+      // bit 3 reports whether external power is connected and can't be written
+      4 => self.backlight_levels = value & 0x7,
+      _ => ()
+    }
+  }
+
+  pub fn set_battery_low(&mut self, low: bool) {
+    self.battery_status = if low { BATTERY_LOW } else { 0 };
+  }
+
+  pub fn speakers_enabled(&self) -> bool {
+    self.control & SOUND_AMP_ENABLE != 0 && self.control & SOUND_AMP_MUTE == 0
+  }
+
+  pub fn top_brightness(&self) -> f32 {
+    self.backlight_brightness(UPPER_BACKLIGHT)
+  }
+
+  pub fn bottom_brightness(&self) -> f32 {
+    self.backlight_brightness(LOWER_BACKLIGHT)
+  }
+
+  fn backlight_brightness(&self, backlight: u8) -> f32 {
+    if self.control & backlight == 0 {
+      return 0.0;
+    }
+
+    BACKLIGHT_LEVELS[(self.backlight_levels & 0x3) as usize]
+  }
+
+  // the request is cleared once the frontend has seen it, so a rom loaded afterwards doesn't get switched off too
+  pub fn take_power_off_request(&mut self) -> bool {
+    std::mem::take(&mut self.power_off_requested)
+  }
+}
@@ -0,0 +1,43 @@
+pub mod rumble_pak;
+pub mod memory_expansion_pak;
+pub mod guitar_grip;
+pub mod paddle;
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum GuitarGripButton {
+  Green,
+  Red,
+  Yellow,
+  Blue
+}
+
+// called whenever a rumble pak's motor switches on or off
+pub type RumbleCallback = Box<dyn FnMut(bool)>;
+
+/*
+  Accessories that plug into Slot-2 instead of a GBA cartridge. The rom bus is 16 bits
+  wide and the sram bus is 8 bits wide, the bus takes care of splitting up other widths.
+  A byte written to the rom area shows up on both halves of the bus, and mask says which
+  of them is actually being written.
+  Frontends talk to the devices through the input methods, which do nothing for devices
+  that don't have them.
+ */
+pub trait Slot2Device {
+  fn read_rom(&mut self, address: u32) -> u16;
+
+  fn write_rom(&mut self, _address: u32, _value: u16, _mask: u16) {}
+
+  fn read_sram(&mut self, _address: u32) -> u8 {
+    0xff
+  }
+
+  fn write_sram(&mut self, _address: u32, _value: u8) {}
+
+  fn set_button(&mut self, _button: GuitarGripButton, _pressed: bool) {}
+
+  fn move_paddle(&mut self, _delta: i32) {}
+
+  fn motor_on(&self) -> bool {
+    false
+  }
+}
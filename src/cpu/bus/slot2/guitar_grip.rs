@@ -0,0 +1,41 @@
+use super::{GuitarGripButton, Slot2Device};
+
+// Guitar Hero: On Tour's fret buttons, read from the sram area (0=pressed)
+#[derive(Default)]
+pub struct GuitarGrip {
+  buttons: u8
+}
+
+impl GuitarGrip {
+  pub fn new() -> Self {
+    Self {
+      buttons: 0
+    }
+  }
+}
+
+impl Slot2Device for GuitarGrip {
+  fn read_rom(&mut self, _address: u32) -> u16 {
+    // used by games to detect the grip
+    0xf9ff
+  }
+
+  fn read_sram(&mut self, _address: u32) -> u8 {
+    !self.buttons
+  }
+
+  fn set_button(&mut self, button: GuitarGripButton, pressed: bool) {
+    let bit = match button {
+      GuitarGripButton::Blue => 3,
+      GuitarGripButton::Yellow => 4,
+      GuitarGripButton::Red => 5,
+      GuitarGripButton::Green => 6
+    };
+
+    if pressed {
+      self.buttons |= 1 << bit;
+    } else {
+      self.buttons &= !(1 << bit);
+    }
+  }
+}
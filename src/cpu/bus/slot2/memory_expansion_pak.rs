@@ -0,0 +1,94 @@
+use super::Slot2Device;
+
+const RAM_SIZE: usize = 0x80_0000;
+
+const RAM_START: u32 = 0x100_0000;
+const RAM_END: u32 = 0x180_0000;
+
+const LOCK_REGISTER: u32 = 0x24_0000;
+
+/*
+  8MB of extra RAM at 9000000h-97FFFFFh, used by the Opera browser. The RAM stays
+  write protected until bit 0 of the lock register at 8240000h is set.
+ */
+pub struct MemoryExpansionPak {
+  ram: Box<[u8]>,
+  ram_enabled: bool
+}
+
+impl Default for MemoryExpansionPak {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl MemoryExpansionPak {
+  pub fn new() -> Self {
+    Self {
+      ram: vec![0; RAM_SIZE].into_boxed_slice(),
+      ram_enabled: false
+    }
+  }
+}
+
+impl Slot2Device for MemoryExpansionPak {
+  fn read_rom(&mut self, address: u32) -> u16 {
+    let address = address & 0x1ff_fffe;
+
+    match address {
+      // the header the Opera browser checks for
+      0xb0 => 0xffff,
+      0xb2 => 0x0000,
+      0xb4 => 0x2400,
+      0xb6 => 0x2424,
+      0xb8..=0xbc => 0xffff,
+      0xbe => 0x7fff,
+      0x1_fffc => 0xffff,
+      0x1_fffe => 0x7fff,
+      LOCK_REGISTER => self.ram_enabled as u16,
+      RAM_START..RAM_END => {
+        let offset = (address - RAM_START) as usize;
+
+        self.ram[offset] as u16 | (self.ram[offset + 1] as u16) << 8
+      }
+      _ => 0xffff
+    }
+  }
+
+  fn write_rom(&mut self, address: u32, value: u16, mask: u16) {
+    let address = address & 0x1ff_fffe;
+
+    match address {
+      LOCK_REGISTER if mask & 0xff != 0 => self.ram_enabled = value & 0b1 == 1,
+      RAM_START..RAM_END if self.ram_enabled => {
+        let offset = (address - RAM_START) as usize;
+
+        if mask & 0xff != 0 {
+          self.ram[offset] = value as u8;
+        }
+        if mask & 0xff00 != 0 {
+          self.ram[offset + 1] = (value >> 8) as u8;
+        }
+      }
+      _ => ()
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn byte_writes_leave_the_other_byte_alone() {
+    let mut pak = MemoryExpansionPak::new();
+
+    pak.write_rom(LOCK_REGISTER, 1, 0xffff);
+    pak.write_rom(RAM_START, 0x1234, 0xffff);
+
+    // the byte shows up on both halves of the bus
+    pak.write_rom(RAM_START + 1, 0x5656, 0xff00);
+
+    assert_eq!(pak.read_rom(RAM_START), 0x5634);
+  }
+}
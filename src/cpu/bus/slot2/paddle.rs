@@ -0,0 +1,36 @@
+use super::Slot2Device;
+
+/*
+  The Taito paddle that came with Arkanoid DS. The knob position is a 12 bit counter that
+  wraps around, read from the sram area: lower 8 bits at A000000h, upper 4 bits at A000001h.
+ */
+#[derive(Default)]
+pub struct Paddle {
+  position: u16
+}
+
+impl Paddle {
+  pub fn new() -> Self {
+    Self {
+      position: 0
+    }
+  }
+}
+
+impl Slot2Device for Paddle {
+  fn read_rom(&mut self, _address: u32) -> u16 {
+    // used by games to detect the paddle
+    0xefff
+  }
+
+  fn read_sram(&mut self, address: u32) -> u8 {
+    match address & 0x1 {
+      0 => self.position as u8,
+      _ => (self.position >> 8) as u8
+    }
+  }
+
+  fn move_paddle(&mut self, delta: i32) {
+    self.position = (self.position as i32 + delta).rem_euclid(0x1000) as u16;
+  }
+}
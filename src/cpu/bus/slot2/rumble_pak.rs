@@ -0,0 +1,35 @@
+use super::Slot2Device;
+
+/*
+  The motor is switched by writing to the rom area. Games toggle it on and off
+  every few frames to control the strength, and the bus hands every change to the
+  frontend's rumble callback.
+ */
+#[derive(Default)]
+pub struct RumblePak {
+  motor_on: bool
+}
+
+impl RumblePak {
+  pub fn new() -> Self {
+    Self {
+      motor_on: false
+    }
+  }
+}
+
+impl Slot2Device for RumblePak {
+  fn read_rom(&mut self, address: u32) -> u16 {
+    // for detection, AD1 is pulled low while the rest of the bus is open
+    ((address >> 1) & 0xfffd) as u16
+  }
+
+  // the motor is wired to AD1, which sees the byte on either half of the bus
+  fn write_rom(&mut self, _address: u32, value: u16, _mask: u16) {
+    self.motor_on = value & 0x2 != 0;
+  }
+
+  fn motor_on(&self) -> bool {
+    self.motor_on
+  }
+}
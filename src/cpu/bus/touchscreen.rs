@@ -1,31 +1,175 @@
-use crate::gpu::{SCREEN_HEIGHT, SCREEN_WIDTH};
+use crate::{
+  gpu::{SCREEN_HEIGHT, SCREEN_WIDTH},
+  mic::{copy_from_ring, MicSource},
+  util
+};
 
 pub const SAMPLE_SIZE: usize = 735;
 const CYCLES_PER_FRAME: usize = 560190;
 
+pub const DEFAULT_PRESSURE: f32 = 0.5;
+pub const DEFAULT_TEMPERATURE: f32 = 25.0;
+
+// resistance of the x plate and the range a touch can measure, in ohms
+const X_PLATE_RESISTANCE: f32 = 400.0;
+const MIN_TOUCH_RESISTANCE: f32 = 100.0;
+const MAX_TOUCH_RESISTANCE: f32 = 1500.0;
+
+const Z2_PRESSED: f32 = 0xc00 as f32;
+
+// the temperature diode reads about 600mV at 25 degrees celsius, dropping 2.1mV per degree
+const TEMP0_VOLTAGE: f32 = 0.6;
+const TEMP0_SLOPE: f32 = 0.0021;
+const REFERENCE_VOLTAGE: f32 = 3.3;
+const KELVIN_SCALE: f32 = 8568.0;
+
+// user settings offset in the firmware header, in units of 8 bytes
+const USER_SETTINGS_POINTER: usize = 0x20;
+const USER_SETTINGS_SIZE: usize = 0x100;
+const CALIBRATION_OFFSET: usize = 0x58;
+const UPDATE_COUNTER_OFFSET: usize = 0x70;
+// the crc covers everything in front of the update counter
+const CRC_OFFSET: usize = 0x72;
+
+/*
+  The firmware user settings store two calibration points, each an adc reading and the pixel
+  it was taken at. Games convert adc values back into pixels with these, so touches have to be
+  turned into adc values the same way in reverse.
+ */
+#[derive(Copy, Clone)]
+pub struct TouchCalibration {
+  pub adc_x1: u16,
+  pub adc_y1: u16,
+  pub scr_x1: u8,
+  pub scr_y1: u8,
+  pub adc_x2: u16,
+  pub adc_y2: u16,
+  pub scr_x2: u8,
+  pub scr_y2: u8
+}
+
+impl TouchCalibration {
+  pub fn from_firmware(firmware: &[u8]) -> Option<Self> {
+    if firmware.len() < USER_SETTINGS_POINTER + 2 {
+      return None;
+    }
+
+    let read_16 = |address: usize| firmware[address] as u16 | (firmware[address + 1] as u16) << 8;
+
+    let first = read_16(USER_SETTINGS_POINTER) as usize * 8;
+    let second = first + USER_SETTINGS_SIZE;
+
+    if first + USER_SETTINGS_SIZE > firmware.len() {
+      return None;
+    }
+
+    let is_valid = |offset: usize| util::crc16(0xffff, &firmware[offset..offset + UPDATE_COUNTER_OFFSET]) == read_16(offset + CRC_OFFSET);
+
+    /*
+      there are two copies of the user settings and the firmware overwrites the older one when saving. the
+      newer copy is the one whose update counter is one ahead of the other, and that's the one that's read.
+      a copy that got cut off halfway through saving fails its crc, so then the other one is used instead.
+     */
+    let (newer, older) = if second + USER_SETTINGS_SIZE <= firmware.len() {
+      let count0 = read_16(first + UPDATE_COUNTER_OFFSET) & 0x7f;
+      let count1 = read_16(second + UPDATE_COUNTER_OFFSET) & 0x7f;
+
+      if (count0 + 1) & 0x7f == count1 {
+        (second, Some(first))
+      } else {
+        (first, Some(second))
+      }
+    } else {
+      (first, None)
+    };
+
+    let offset = if is_valid(newer) {
+      newer
+    } else if let Some(older) = older.filter(|older| is_valid(*older)) {
+      older
+    } else {
+      println!("[WARN] firmware user settings failed their crc check, using the default touch calibration");
+      return None;
+    };
+
+    let base = offset + CALIBRATION_OFFSET;
+
+    let calibration = Self {
+      adc_x1: read_16(base) & 0xfff,
+      adc_y1: read_16(base + 2) & 0xfff,
+      scr_x1: firmware[base + 4],
+      scr_y1: firmware[base + 5],
+      adc_x2: read_16(base + 6) & 0xfff,
+      adc_y2: read_16(base + 8) & 0xfff,
+      scr_x2: firmware[base + 10],
+      scr_y2: firmware[base + 11]
+    };
+
+    if calibration.adc_x1 == calibration.adc_x2 ||
+      calibration.adc_y1 == calibration.adc_y2 ||
+      calibration.scr_x1 == calibration.scr_x2 ||
+      calibration.scr_y1 == calibration.scr_y2
+    {
+      return None;
+    }
+
+    Some(calibration)
+  }
+
+  pub fn to_adc_x(&self, x: f32) -> u16 {
+    Self::to_adc(x, self.adc_x1, self.adc_x2, self.scr_x1, self.scr_x2)
+  }
+
+  pub fn to_adc_y(&self, y: f32) -> u16 {
+    Self::to_adc(y, self.adc_y1, self.adc_y2, self.scr_y1, self.scr_y2)
+  }
+
+  fn to_adc(position: f32, adc1: u16, adc2: u16, scr1: u8, scr2: u8) -> u16 {
+    let scale = (adc2 as f32 - adc1 as f32) / (scr2 as f32 - scr1 as f32);
+
+    let adc = adc1 as f32 + (position - scr1 as f32) * scale;
+
+    adc.round().clamp(0.0, 0xfff as f32) as u16
+  }
+}
+
+#[derive(Copy, Clone, PartialEq)]
+enum ConversionMode {
+  Bit12 = 0,
+  Bit8 = 1
+}
+
 pub struct Touchscreen {
   pub x: u16,
   pub y: u16,
+  pub pressure: f32,
+  pub temperature: f32,
+  pub calibration: Option<TouchCalibration>,
 
   data: u16,
   return_byte: u8,
   mic_buffer: [i16; SAMPLE_SIZE],
-  read_pos: usize
+  read_pos: usize,
+  power_down_mode: u8
 }
 
 impl Touchscreen {
-  pub fn new() -> Self {
+  pub fn new(firmware: &[u8]) -> Self {
     Self {
       x: 0,
       y: 0,
+      pressure: DEFAULT_PRESSURE,
+      temperature: DEFAULT_TEMPERATURE,
+      calibration: TouchCalibration::from_firmware(firmware),
       data: 0,
       return_byte: 0,
       mic_buffer: [0; SAMPLE_SIZE],
-      read_pos: 0
+      read_pos: 0,
+      power_down_mode: 0
     }
   }
 
-  pub fn write(&mut self, value: u8, frame_cycles: usize) {
+  pub fn write(&mut self, value: u8, frame_cycles: usize, pen_down: bool) {
     self.return_byte = (self.data >> 8) as u8;
 
     self.data <<= 8;
@@ -34,9 +178,23 @@ impl Touchscreen {
     if (value >> 7) & 0b1 == 1 {
       // start bit must be set to write the data
       let channel = (value >> 4) & 0x7;
-      self.data = match channel {
-        1 => self.y << 3,
-        5 => self.x << 3,
+
+      let mode = if (value >> 3) & 0b1 == 1 {
+        ConversionMode::Bit8
+      } else {
+        ConversionMode::Bit12
+      };
+
+      self.power_down_mode = value & 0x3;
+
+      let result = match channel {
+        0 => self.temp0(),
+        1 => self.y,
+        // battery voltage isn't connected on the ds
+        2 => 0,
+        3 => self.z1(pen_down),
+        4 => self.z2(pen_down),
+        5 => self.x,
         6 => {
           let index = (frame_cycles * SAMPLE_SIZE) / CYCLES_PER_FRAME;
 
@@ -54,38 +212,84 @@ impl Touchscreen {
           //   sample << 1
           // };
 
-          (((sample ^-32768) >> 4) as u16) << 3
+          ((sample ^-32768) >> 4) as u16
         },
-        _ => 0xfff
+        7 => self.temp1(),
+        _ => unreachable!()
+      };
+
+      // the result comes out after a null bit, msb first
+      self.data = match mode {
+        ConversionMode::Bit12 => (result & 0xfff) << 3,
+        ConversionMode::Bit8 => ((result >> 4) & 0xff) << 7
       };
     }
   }
 
-  pub fn update_mic_buffer(&mut self, samples: &[i16]) {
-    if (self.read_pos + SAMPLE_SIZE) >= samples.len() {
-      let len  = samples.len() - self.read_pos;
+  // harder presses lower the touch resistance, which brings z1 up closer to z2
+  fn touch_resistance(&self) -> f32 {
+    let pressure = self.pressure.clamp(0.0, 1.0);
 
-      let mut buffer_index = 0;
+    MAX_TOUCH_RESISTANCE - (MAX_TOUCH_RESISTANCE - MIN_TOUCH_RESISTANCE) * pressure
+  }
 
-      for i in self.read_pos..len {
-        self.mic_buffer[buffer_index] = samples[i];
+  fn z1(&self, pen_down: bool) -> u16 {
+    if !pen_down {
+      return 0;
+    }
 
-        buffer_index += 1;
-      }
+    let x = self.x.max(1) as f32;
 
-      let diff = SAMPLE_SIZE - len;
+    // resistance = x plate * (x / 4096) * (z2 / z1 - 1)
+    let ratio = 1.0 + self.touch_resistance() * 4096.0 / (X_PLATE_RESISTANCE * x);
 
-      for i in 0..diff {
-        self.mic_buffer[buffer_index] = samples[i];
-        buffer_index += 1;
-      }
-
-      self.read_pos = diff;
-    } else {
-      self.mic_buffer.copy_from_slice(&samples[self.read_pos..self.read_pos + SAMPLE_SIZE]);
+    (Z2_PRESSED / ratio) as u16
+  }
 
-      self.read_pos += SAMPLE_SIZE;
+  fn z2(&self, pen_down: bool) -> u16 {
+    if !pen_down {
+      return 0xfff;
     }
+
+    Z2_PRESSED as u16
+  }
+
+  fn temp0(&self) -> u16 {
+    let voltage = TEMP0_VOLTAGE - TEMP0_SLOPE * (self.temperature - DEFAULT_TEMPERATURE);
+
+    Self::to_adc(voltage)
+  }
+
+  // kelvin = (temp1 - temp0) * 8568 / 4096
+  fn temp1(&self) -> u16 {
+    let kelvin = self.temperature + 273.15;
+
+    self.temp0() + (kelvin * 4096.0 / KELVIN_SCALE) as u16
+  }
+
+  fn to_adc(voltage: f32) -> u16 {
+    ((voltage / REFERENCE_VOLTAGE * 4096.0) as u16).min(0xfff)
+  }
+
+  // power down modes 1 and 3 switch off the pen interrupt
+  pub fn penirq_enabled(&self) -> bool {
+    self.power_down_mode & 0b1 == 0
+  }
+
+  pub fn set_pressure(&mut self, pressure: f32) {
+    self.pressure = pressure.clamp(0.0, 1.0);
+  }
+
+  pub fn set_temperature(&mut self, temperature: f32) {
+    self.temperature = temperature;
+  }
+
+  pub fn update_mic_buffer(&mut self, samples: &[i16]) {
+    copy_from_ring(samples, &mut self.read_pos, &mut self.mic_buffer);
+  }
+
+  pub fn update_mic(&mut self, source: &mut dyn MicSource) {
+    source.fill(&mut self.mic_buffer);
   }
 
   pub fn deselect(&mut self) {
@@ -97,8 +301,21 @@ impl Touchscreen {
   }
 
   pub fn touch_screen(&mut self, x: u16, y: u16) {
-    self.x = x << 4;
-    self.y = y << 4;
+    self.touch_screen_subpixel(x as f32, y as f32);
+  }
+
+  // takes fractional pixel coordinates, for frontends with higher resolution input than the screen
+  pub fn touch_screen_subpixel(&mut self, x: f32, y: f32) {
+    match &self.calibration {
+      Some(calibration) => {
+        self.x = calibration.to_adc_x(x);
+        self.y = calibration.to_adc_y(y);
+      }
+      None => {
+        self.x = (x * 16.0).clamp(0.0, 0xfff as f32) as u16;
+        self.y = (y * 16.0).clamp(0.0, 0xfff as f32) as u16;
+      }
+    }
   }
 
   pub fn touch_screen_controller(&mut self, x: i16, y: i16) {
@@ -108,8 +325,7 @@ impl Touchscreen {
     let pointer_x = x / 1000;
     let pointer_y = y / 1000;
 
-    self.x = ((middle_x + pointer_x) << 4) as u16;
-    self.y = ((middle_y + pointer_y) << 4) as u16;
+    self.touch_screen_subpixel((middle_x + pointer_x) as f32, (middle_y + pointer_y) as f32);
   }
 
 
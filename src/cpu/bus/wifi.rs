@@ -0,0 +1,861 @@
+use std::collections::VecDeque;
+
+use crate::scheduler::{EventType, Scheduler};
+
+/*
+  Register model for the wifi hardware. There's nobody else on the air, so transmits finish
+  right away and nothing is ever received unless a frame is handed in from outside. That's
+  enough for games to get through wireless init and find no networks instead of spinning forever.
+ */
+
+pub const WIFI_RAM_SIZE: usize = 0x2000;
+const NUM_REGISTERS: usize = 0x800;
+
+// the microsecond counter is synced up once a millisecond
+pub const WIFI_TICK_CYCLES: usize = 33_514;
+const ARM7_CLOCK_RATE: u64 = 33_513_982;
+
+const W_ID: u32 = 0x000;
+const W_MODE_RST: u32 = 0x004;
+const W_IF: u32 = 0x010;
+const W_IE: u32 = 0x012;
+const W_MACADDR_0: u32 = 0x018;
+const W_BSSID_0: u32 = 0x020;
+const W_RXCNT: u32 = 0x030;
+const W_POWERSTATE: u32 = 0x03c;
+const W_POWERFORCE: u32 = 0x040;
+const W_RANDOM: u32 = 0x044;
+const W_RXBUF_BEGIN: u32 = 0x050;
+const W_RXBUF_END: u32 = 0x052;
+const W_RXBUF_WRCSR: u32 = 0x054;
+const W_RXBUF_WR_ADDR: u32 = 0x056;
+const W_RXBUF_RD_ADDR: u32 = 0x058;
+const W_RXBUF_READCSR: u32 = 0x05a;
+const W_RXBUF_COUNT: u32 = 0x05c;
+const W_RXBUF_RD_DATA: u32 = 0x060;
+const W_RXBUF_GAP: u32 = 0x062;
+const W_RXBUF_GAPDISP: u32 = 0x064;
+const W_TXBUF_WR_ADDR: u32 = 0x068;
+const W_TXBUF_COUNT: u32 = 0x06c;
+const W_TXBUF_WR_DATA: u32 = 0x070;
+const W_TXBUF_GAP: u32 = 0x074;
+const W_TXBUF_GAPDISP: u32 = 0x076;
+const W_TXBUF_BEACON: u32 = 0x080;
+const W_BEACONINT: u32 = 0x08c;
+const W_TXBUF_CMD: u32 = 0x090;
+const W_TXBUF_REPLY1: u32 = 0x094;
+const W_TXBUF_REPLY2: u32 = 0x098;
+const W_TXBUF_LOC1: u32 = 0x0a0;
+const W_TXBUF_LOC2: u32 = 0x0a4;
+const W_TXBUF_LOC3: u32 = 0x0a8;
+const W_TXREQ_RESET: u32 = 0x0ac;
+const W_TXREQ_SET: u32 = 0x0ae;
+const W_TXREQ_READ: u32 = 0x0b0;
+const W_TXBUF_RESET: u32 = 0x0b4;
+const W_TXBUSY: u32 = 0x0b6;
+const W_TXSTAT: u32 = 0x0b8;
+const W_CMD_TOTALTIME: u32 = 0x0c0;
+const W_US_COUNTCNT: u32 = 0x0e8;
+const W_US_COMPARECNT: u32 = 0x0ea;
+const W_US_COMPARE0: u32 = 0x0f0;
+const W_US_COMPARE3: u32 = 0x0f6;
+const W_US_COUNT0: u32 = 0x0f8;
+const W_US_COUNT3: u32 = 0x0fe;
+const W_PRE_BEACON: u32 = 0x110;
+const W_BB_CNT: u32 = 0x158;
+const W_BB_WRITE: u32 = 0x15a;
+const W_BB_READ: u32 = 0x15c;
+const W_BB_BUSY: u32 = 0x15e;
+const W_RF_DATA2: u32 = 0x17c;
+const W_RF_DATA1: u32 = 0x17e;
+const W_RF_BUSY: u32 = 0x180;
+const W_RF_STATUS: u32 = 0x214;
+const W_IF_SET: u32 = 0x21c;
+
+// ds lite, the original ds reads 0x1440
+const CHIP_ID: u16 = 0xc340;
+const BASEBAND_CHIP_ID: u8 = 0x6d;
+
+const RF_STATUS_IDLE: u16 = 1;
+const RF_STATUS_OFF: u16 = 9;
+
+const POWER_DOWN: u16 = 0x200;
+
+// size of the hardware header in front of every frame in wifi ram
+const TX_HEADER_SIZE: usize = 12;
+const RX_HEADER_SIZE: usize = 12;
+
+// the beacon timestamp goes right after the 802.11 header
+const BEACON_TIMESTAMP_OFFSET: usize = TX_HEADER_SIZE + 24;
+
+// frame control of a multiplay command, the frame clients answer with their reply frame
+const FRAME_CONTROL_MULTIPLAY_CMD: u16 = 0x0228;
+// the host confirms the command with this once the replies are in
+const FRAME_CONTROL_MULTIPLAY_ACK: u16 = 0x0218;
+const MULTIPLAY_ACK_ADDRESS: [u8; 6] = [0x03, 0x09, 0xbf, 0x00, 0x00, 0x03];
+
+// anything shorter than a full 802.11 header is a control frame and has no addresses to filter on
+const MAC_HEADER_SIZE: usize = 24;
+const FRAME_ADDRESS1: usize = 4;
+const FRAME_ADDRESS3: usize = 16;
+
+const RX_RATE_2MBIT: u8 = 0x14;
+// the consoles are right next to each other, so the signal is always strong
+const RX_RSSI: u8 = 0x40;
+
+// a command goes out and the replies come back over two slices of the link, so that's the shortest the command can take
+const MIN_CMD_TIME_US: u64 = 2 * 1000;
+
+/*
+  W_TXREQ and W_TXBUF_RESET share their bit layout: bit 0 is loc1, bit 1 the multiplay command,
+  bit 2 loc2 and bit 3 loc3. W_TXBUF_RESET also has the two reply slots in bits 6 and 7.
+ */
+const TX_SLOTS: [(u16, u32); 4] = [(0, W_TXBUF_LOC1), (1, W_TXBUF_CMD), (2, W_TXBUF_LOC2), (3, W_TXBUF_LOC3)];
+const TX_CMD_SLOT: u16 = 1;
+
+#[derive(Copy, Clone)]
+pub enum WifiInterrupt {
+  RxComplete = 0,
+  TxComplete = 1,
+  RxEventIncrement = 2,
+  TxError = 3,
+  RxStart = 6,
+  TxStart = 7,
+  TxBufCountEnd = 8,
+  RxBufCountEnd = 9,
+  RfWakeup = 11,
+  MultiplayCmdDone = 12,
+  PostBeacon = 13,
+  BeaconTimeslot = 14,
+  PreBeacon = 15
+}
+
+pub struct Wifi {
+  registers: Box<[u16]>,
+  ram: Box<[u8]>,
+  baseband_registers: [u8; 0x100],
+  rf_registers: [u32; 0x20],
+  us_count: u64,
+  us_count_cycles: usize,
+  random: u16,
+  pub irq_request: bool,
+  // when the multiplay command that's out finishes, in microseconds
+  cmd_done_us: Option<u64>,
+  // frames that went out over the air, for anything that wants to listen in
+  pub outgoing: VecDeque<Vec<u8>>
+}
+
+impl Wifi {
+  pub fn new(scheduler: &mut Scheduler) -> Self {
+    scheduler.schedule(EventType::WifiTick, WIFI_TICK_CYCLES);
+
+    let mut wifi = Self {
+      registers: vec![0; NUM_REGISTERS].into_boxed_slice(),
+      ram: vec![0; WIFI_RAM_SIZE].into_boxed_slice(),
+      baseband_registers: [0; 0x100],
+      rf_registers: [0; 0x20],
+      us_count: 0,
+      us_count_cycles: 0,
+      random: 1,
+      irq_request: false,
+      cmd_done_us: None,
+      outgoing: VecDeque::new()
+    };
+
+    wifi.baseband_registers[0] = BASEBAND_CHIP_ID;
+    wifi.set_register(W_POWERSTATE, POWER_DOWN);
+    wifi.set_register(W_RF_STATUS, RF_STATUS_OFF);
+
+    wifi
+  }
+
+  fn register(&self, offset: u32) -> u16 {
+    self.registers[(offset >> 1) as usize]
+  }
+
+  fn set_register(&mut self, offset: u32, value: u16) {
+    self.registers[(offset >> 1) as usize] = value;
+  }
+
+  pub fn read_ram(&self, offset: u32) -> u16 {
+    let offset = (offset as usize) & (WIFI_RAM_SIZE - 2);
+
+    self.ram[offset] as u16 | (self.ram[offset + 1] as u16) << 8
+  }
+
+  pub fn write_ram(&mut self, offset: u32, value: u16) {
+    let offset = (offset as usize) & (WIFI_RAM_SIZE - 2);
+
+    self.ram[offset] = value as u8;
+    self.ram[offset + 1] = (value >> 8) as u8;
+  }
+
+  pub fn read(&mut self, offset: u32, scheduler: &Scheduler) -> u16 {
+    let offset = offset & 0xffe;
+
+    match offset {
+      W_ID => CHIP_ID,
+      W_RANDOM => {
+        // 11 bit lfsr that steps on every read
+        self.random = (self.random & 0b1) ^ (((self.random & 0x3ff) << 1) | (self.random >> 10));
+
+        self.random
+      }
+      W_RXBUF_RD_DATA => {
+        let address = self.register(W_RXBUF_RD_ADDR);
+        let value = self.read_ram(address as u32);
+
+        self.set_register(W_RXBUF_RD_ADDR, self.advance_rx_read_address(address));
+
+        self.decrement_count(W_RXBUF_COUNT, WifiInterrupt::RxBufCountEnd);
+
+        value
+      }
+      W_US_COUNT0..=W_US_COUNT3 => {
+        let us_count = self.current_us_count(scheduler);
+
+        (us_count >> ((offset - W_US_COUNT0) * 8)) as u16
+      }
+      W_BB_BUSY | W_RF_BUSY => 0,
+      _ => self.register(offset)
+    }
+  }
+
+  pub fn write(&mut self, offset: u32, value: u16, scheduler: &Scheduler) {
+    let offset = offset & 0xffe;
+
+    match offset {
+      W_ID | W_RANDOM | W_RXBUF_RD_DATA | W_TXREQ_READ | W_TXBUSY | W_TXSTAT | W_BB_READ | W_BB_BUSY | W_RF_BUSY | W_RF_STATUS => (),
+      W_MODE_RST => {
+        let previous = self.register(W_MODE_RST);
+
+        self.set_register(W_MODE_RST, value);
+
+        if previous & 0b1 == 0 && value & 0b1 == 1 {
+          self.set_register(W_RF_STATUS, RF_STATUS_IDLE);
+        } else if previous & 0b1 == 1 && value & 0b1 == 0 {
+          self.set_register(W_RF_STATUS, RF_STATUS_OFF);
+          self.set_register(W_POWERSTATE, POWER_DOWN);
+        }
+      }
+      // writing a 1 acknowledges the interrupt
+      W_IF => self.set_register(W_IF, self.register(W_IF) & !value),
+      W_IE => {
+        self.set_register(W_IE, value);
+
+        if self.register(W_IF) & value != 0 {
+          self.irq_request = true;
+        }
+      }
+      W_IF_SET => {
+        for bit in 0..16 {
+          if (value >> bit) & 0b1 == 1 {
+            self.trigger_interrupt(bit);
+          }
+        }
+      }
+      W_POWERSTATE => {
+        let state = self.register(W_POWERSTATE) & 0x300 | value & 0b10;
+
+        self.set_register(W_POWERSTATE, state);
+
+        // a wake up request while the hardware is enabled
+        if value & 0b10 != 0 && self.register(W_MODE_RST) & 0b1 == 1 {
+          self.set_register(W_POWERSTATE, 0);
+          self.set_register(W_RF_STATUS, RF_STATUS_IDLE);
+          self.trigger_interrupt(WifiInterrupt::RfWakeup as u16);
+        }
+      }
+      W_POWERFORCE => {
+        self.set_register(W_POWERFORCE, value & 0x8001);
+
+        if value & 0x8000 != 0 {
+          let state = if value & 0b1 == 1 { POWER_DOWN } else { 0 };
+
+          self.set_register(W_POWERSTATE, state);
+        }
+      }
+      W_RXBUF_WRCSR | W_RXBUF_WR_ADDR | W_RXBUF_RD_ADDR | W_RXBUF_READCSR | W_RXBUF_GAP | W_TXBUF_WR_ADDR | W_TXBUF_GAP => {
+        self.set_register(offset, value & 0x1ffe);
+      }
+      W_RXBUF_BEGIN | W_RXBUF_END => self.set_register(offset, value & 0x1ffe),
+      W_RXCNT => {
+        // bit 0 moves the write cursor to the address the game set up
+        if value & 0b1 == 1 {
+          self.set_register(W_RXBUF_WRCSR, self.register(W_RXBUF_WR_ADDR));
+        }
+
+        self.set_register(W_RXCNT, value & 0xff0e);
+      }
+      W_RXBUF_COUNT | W_TXBUF_COUNT => self.set_register(offset, value & 0xfff),
+      W_TXBUF_WR_DATA => {
+        let address = self.register(W_TXBUF_WR_ADDR);
+
+        self.write_ram(address as u32, value);
+
+        let mut next_address = address.wrapping_add(2) & 0x1ffe;
+
+        if next_address == self.register(W_TXBUF_GAP) {
+          next_address = next_address.wrapping_add(self.register(W_TXBUF_GAPDISP) << 1) & 0x1ffe;
+        }
+
+        self.set_register(W_TXBUF_WR_ADDR, next_address);
+
+        self.decrement_count(W_TXBUF_COUNT, WifiInterrupt::TxBufCountEnd);
+      }
+      W_TXREQ_RESET => self.set_register(W_TXREQ_READ, self.register(W_TXREQ_READ) & !value),
+      W_TXREQ_SET => {
+        self.set_register(W_TXREQ_READ, self.register(W_TXREQ_READ) | value & 0xf);
+        self.process_transmits();
+      }
+      W_TXBUF_RESET => {
+        for (bit, location) in TX_SLOTS.into_iter().chain([(6, W_TXBUF_REPLY2), (7, W_TXBUF_REPLY1)]) {
+          if (value >> bit) & 0b1 == 1 {
+            self.set_register(location, self.register(location) & 0x7fff);
+          }
+        }
+      }
+      W_TXBUF_LOC1 | W_TXBUF_LOC2 | W_TXBUF_LOC3 | W_TXBUF_CMD => {
+        self.set_register(offset, value);
+        self.process_transmits();
+      }
+      W_US_COUNTCNT => {
+        self.sync_us_count(scheduler);
+        self.set_register(W_US_COUNTCNT, value & 0b1);
+      }
+      W_US_COMPARECNT => self.set_register(W_US_COMPARECNT, value & 0b1),
+      W_US_COMPARE0 => self.set_register(W_US_COMPARE0, value & 0xfc00),
+      W_US_COUNT0..=W_US_COUNT3 => {
+        self.sync_us_count(scheduler);
+
+        let shift = (offset - W_US_COUNT0) * 8;
+
+        self.us_count = self.us_count & !(0xffff << shift) | (value as u64) << shift;
+      }
+      W_BB_CNT => {
+        let index = (value & 0xff) as usize;
+
+        match value >> 12 {
+          5 => self.baseband_registers[index] = self.register(W_BB_WRITE) as u8,
+          6 => self.set_register(W_BB_READ, self.baseband_registers[index] as u16),
+          _ => ()
+        }
+
+        self.set_register(W_BB_CNT, value);
+      }
+      W_RF_DATA1 => {
+        self.set_register(W_RF_DATA1, value);
+        self.transfer_rf();
+      }
+      _ => self.set_register(offset, value)
+    }
+  }
+
+  // writing to the upper half starts the transfer: bit 23 selects a read, bits 18-22 the register
+  fn transfer_rf(&mut self) {
+    let data = (self.register(W_RF_DATA1) as u32) << 16 | self.register(W_RF_DATA2) as u32;
+
+    let index = ((data >> 18) & 0x1f) as usize;
+
+    if (data >> 23) & 0b1 == 1 {
+      let value = self.rf_registers[index];
+
+      self.set_register(W_RF_DATA2, value as u16);
+      self.set_register(W_RF_DATA1, self.register(W_RF_DATA1) & !0x3 | ((value >> 16) & 0x3) as u16);
+    } else {
+      self.rf_registers[index] = data & 0x3_ffff;
+    }
+  }
+
+  fn advance_rx_read_address(&self, address: u16) -> u16 {
+    let mut next_address = address.wrapping_add(2) & 0x1ffe;
+
+    if next_address == self.register(W_RXBUF_GAP) {
+      next_address = next_address.wrapping_add(self.register(W_RXBUF_GAPDISP) << 1) & 0x1ffe;
+    }
+
+    if next_address == self.register(W_RXBUF_END) {
+      next_address = self.register(W_RXBUF_BEGIN);
+    }
+
+    next_address
+  }
+
+  fn decrement_count(&mut self, register: u32, interrupt: WifiInterrupt) {
+    let count = self.register(register);
+
+    if count > 0 {
+      self.set_register(register, count - 1);
+
+      if count == 1 {
+        self.trigger_interrupt(interrupt as u16);
+      }
+    }
+  }
+
+  pub fn trigger_interrupt(&mut self, bit: u16) {
+    self.set_register(W_IF, self.register(W_IF) | 1 << bit);
+
+    if (self.register(W_IE) >> bit) & 0b1 == 1 {
+      self.irq_request = true;
+    }
+  }
+
+  // queued frames go out and complete on the spot. the command stays busy until the replies had time to come back
+  fn process_transmits(&mut self) {
+    let requests = self.register(W_TXREQ_READ);
+
+    for (bit, location) in TX_SLOTS {
+      let loc = self.register(location);
+
+      if (requests >> bit) & 0b1 == 0 || loc & 0x8000 == 0 {
+        continue;
+      }
+
+      if bit == TX_CMD_SLOT {
+        if self.cmd_done_us.is_some() {
+          continue;
+        }
+
+        let total_time = self.register(W_CMD_TOTALTIME) as u64;
+
+        self.cmd_done_us = Some(self.us_count + total_time.max(MIN_CMD_TIME_US));
+      }
+
+      self.trigger_interrupt(WifiInterrupt::TxStart as u16);
+
+      self.transmit(loc);
+
+      if bit != TX_CMD_SLOT {
+        self.set_register(location, loc & 0x7fff);
+      }
+
+      self.set_register(W_TXSTAT, 0x0001 | bit << 8);
+
+      self.trigger_interrupt(WifiInterrupt::TxComplete as u16);
+    }
+  }
+
+  // sends the frame a W_TXBUF_* register points at
+  fn transmit(&mut self, loc: u16) {
+    let address = ((loc & 0xfff) << 1) as u32;
+
+    // the frame length in the tx header includes the 4 byte fcs the hardware adds
+    let length = (self.read_ram(address + 10) & 0x3fff) as usize;
+
+    let frame: Vec<u8> = (0..length.saturating_sub(4))
+      .map(|i| self.ram[(address as usize + TX_HEADER_SIZE + i) & (WIFI_RAM_SIZE - 1)])
+      .collect();
+
+    self.outgoing.push_back(frame);
+
+    // mark the frame as sent in its header
+    self.write_ram(address, 0x0001);
+  }
+
+  // the hardware stamps the microsecond counter into the beacon on its way out
+  fn send_beacon(&mut self) {
+    let loc = self.register(W_TXBUF_BEACON);
+
+    if loc & 0x8000 == 0 {
+      return;
+    }
+
+    let address = ((loc & 0xfff) << 1) as usize;
+
+    for i in 0..4 {
+      self.write_ram((address + BEACON_TIMESTAMP_OFFSET + i * 2) as u32, (self.us_count >> (i * 16)) as u16);
+    }
+
+    self.transmit(loc);
+
+    self.trigger_interrupt(WifiInterrupt::PostBeacon as u16);
+  }
+
+  // clients answer a multiplay command with the reply frame the game queued up for the next one
+  fn send_reply(&mut self) {
+    let loc = self.register(W_TXBUF_REPLY2);
+
+    self.set_register(W_TXBUF_REPLY1, loc);
+    self.set_register(W_TXBUF_REPLY2, 0);
+
+    if loc & 0x8000 != 0 {
+      self.transmit(loc);
+    }
+  }
+
+  /*
+    The ack the hardware sends on its own after the reply window. Its body is 0033h followed by the mask of
+    clients that didn't answer, which is always empty since the link doesn't lose frames.
+   */
+  fn send_multiplay_ack(&mut self) {
+    let mut frame = Vec::with_capacity(MAC_HEADER_SIZE + 4);
+
+    frame.extend_from_slice(&FRAME_CONTROL_MULTIPLAY_ACK.to_le_bytes());
+    frame.extend_from_slice(&[0, 0]);
+    frame.extend_from_slice(&MULTIPLAY_ACK_ADDRESS);
+    frame.extend_from_slice(&self.mac_address());
+    frame.extend_from_slice(&self.bssid());
+    frame.extend_from_slice(&[0, 0]);
+    frame.extend_from_slice(&[0x33, 0x00, 0x00, 0x00]);
+
+    self.outgoing.push_back(frame);
+  }
+
+  // writes a received frame into the rx ring buffer behind its header
+  pub fn receive_frame(&mut self, frame: &[u8]) {
+    if self.register(W_MODE_RST) & 0b1 == 0
+      || self.register(W_RXCNT) & 0x8000 == 0
+      || self.register(W_RXBUF_BEGIN) == self.register(W_RXBUF_END)
+    {
+      return;
+    }
+
+    let Some(bssid_match) = self.filter_frame(frame) else {
+      return;
+    };
+
+    self.trigger_interrupt(WifiInterrupt::RxStart as u16);
+
+    let mut address = self.register(W_RXBUF_WRCSR);
+
+    let flags = Self::rx_frame_type(frame) | 0x10 | if bssid_match { 0x8000 } else { 0 };
+
+    let mut header = [0u8; RX_HEADER_SIZE];
+
+    header[0] = flags as u8;
+    header[1] = (flags >> 8) as u8;
+    header[6] = RX_RATE_2MBIT;
+    header[8] = frame.len() as u8;
+    header[9] = (frame.len() >> 8) as u8;
+    header[10] = RX_RSSI;
+    header[11] = RX_RSSI;
+
+    let data: Vec<u8> = header.iter().chain(frame.iter()).copied().collect();
+
+    for halfword in data.chunks(2) {
+      let value = halfword[0] as u16 | (*halfword.get(1).unwrap_or(&0) as u16) << 8;
+
+      self.write_ram(address as u32, value);
+
+      address = address.wrapping_add(2) & 0x1ffe;
+
+      if address == self.register(W_RXBUF_END) {
+        address = self.register(W_RXBUF_BEGIN);
+      }
+    }
+
+    // the write cursor moves on to the next word boundary
+    if address & 0b10 != 0 {
+      address = address.wrapping_add(2) & 0x1ffe;
+
+      if address == self.register(W_RXBUF_END) {
+        address = self.register(W_RXBUF_BEGIN);
+      }
+    }
+
+    self.set_register(W_RXBUF_WRCSR, address);
+    self.set_register(W_RXBUF_WR_ADDR, address);
+
+    self.trigger_interrupt(WifiInterrupt::RxComplete as u16);
+
+    if frame.len() >= 2 && (frame[0] as u16 | (frame[1] as u16) << 8) == FRAME_CONTROL_MULTIPLAY_CMD {
+      self.send_reply();
+    }
+  }
+
+  /*
+    frames have to be addressed to this console or a group, and anything but management frames has to
+    come from the bss this console is in. returns whether the bssid matched, or None to drop the frame.
+   */
+  fn filter_frame(&self, frame: &[u8]) -> Option<bool> {
+    if frame.len() < MAC_HEADER_SIZE {
+      return Some(false);
+    }
+
+    let destination = &frame[FRAME_ADDRESS1..FRAME_ADDRESS1 + 6];
+
+    if destination[0] & 0b1 == 0 && destination != self.mac_address() {
+      return None;
+    }
+
+    let bssid = &frame[FRAME_ADDRESS3..FRAME_ADDRESS3 + 6];
+
+    let bssid_match = bssid == self.bssid() || bssid.iter().all(|byte| *byte == 0xff);
+
+    // management frames go through from anywhere, that's how beacons from other hosts get seen
+    if !bssid_match && (frame[0] >> 2) & 0b11 != 0 {
+      return None;
+    }
+
+    Some(bssid_match)
+  }
+
+  // the frame type bits of the rx header flags
+  fn rx_frame_type(frame: &[u8]) -> u16 {
+    let frame_control = frame[0] as u16 | (*frame.get(1).unwrap_or(&0) as u16) << 8;
+
+    let frame_type = (frame_control >> 2) & 0b11;
+    let subtype = (frame_control >> 4) & 0xf;
+
+    match (frame_type, subtype) {
+      // beacon
+      (0, 8) => 0x1,
+      (0, _) => 0x0,
+      (2, _) if frame_control == FRAME_CONTROL_MULTIPLAY_CMD => 0xc,
+      // multiplay replies go to the host, acks come from it
+      (2, 1) if frame_control & (1 << 8) != 0 => 0xe,
+      (2, 1) => 0xd,
+      _ => 0x8
+    }
+  }
+
+  pub fn mac_address(&self) -> [u8; 6] {
+    self.address_register(W_MACADDR_0)
+  }
+
+  fn bssid(&self) -> [u8; 6] {
+    self.address_register(W_BSSID_0)
+  }
+
+  fn address_register(&self, base: u32) -> [u8; 6] {
+    let mut address = [0; 6];
+
+    for i in 0..3 {
+      let value = self.register(base + i as u32 * 2);
+
+      address[i * 2] = value as u8;
+      address[i * 2 + 1] = (value >> 8) as u8;
+    }
+
+    address
+  }
+
+  fn current_us_count(&self, scheduler: &Scheduler) -> u64 {
+    if self.register(W_US_COUNTCNT) & 0b1 == 0 {
+      return self.us_count;
+    }
+
+    let elapsed = scheduler.cycles.saturating_sub(self.us_count_cycles) as u64;
+
+    self.us_count + elapsed * 1_000_000 / ARM7_CLOCK_RATE
+  }
+
+  fn sync_us_count(&mut self, scheduler: &Scheduler) {
+    self.us_count = self.current_us_count(scheduler);
+    self.us_count_cycles = scheduler.cycles;
+  }
+
+  // runs once a millisecond. fires the pre-beacon and beacon interrupts when the counter passes the compare value,
+  // sends the beacon and finishes the multiplay command once its time is up
+  pub fn tick(&mut self, scheduler: &Scheduler) {
+    let previous = self.us_count;
+
+    self.sync_us_count(scheduler);
+
+    if let Some(cmd_done_us) = self.cmd_done_us {
+      if self.us_count >= cmd_done_us {
+        self.cmd_done_us = None;
+
+        self.set_register(W_TXBUF_CMD, self.register(W_TXBUF_CMD) & 0x7fff);
+        self.set_register(W_TXREQ_READ, self.register(W_TXREQ_READ) & !(1 << TX_CMD_SLOT));
+
+        self.send_multiplay_ack();
+
+        self.trigger_interrupt(WifiInterrupt::MultiplayCmdDone as u16);
+      }
+    }
+
+    if self.register(W_US_COMPARECNT) & 0b1 == 0 {
+      return;
+    }
+
+    let mut compare = 0;
+
+    for offset in (W_US_COMPARE0..=W_US_COMPARE3).step_by(2) {
+      compare |= (self.register(offset) as u64) << ((offset - W_US_COMPARE0) * 8);
+    }
+
+    let pre_beacon = compare.saturating_sub(self.register(W_PRE_BEACON) as u64);
+
+    if previous < pre_beacon && self.us_count >= pre_beacon {
+      self.trigger_interrupt(WifiInterrupt::PreBeacon as u16);
+    }
+
+    if previous < compare && self.us_count >= compare {
+      self.trigger_interrupt(WifiInterrupt::BeaconTimeslot as u16);
+
+      self.send_beacon();
+
+      // the next beacon is due one beacon interval later, counted in units of 1024 microseconds
+      let interval = (self.register(W_BEACONINT) & 0x3ff) as u64;
+
+      if interval != 0 {
+        let compare = compare + interval * 1024;
+
+        for offset in (W_US_COMPARE0..=W_US_COMPARE3).step_by(2) {
+          self.set_register(offset, (compare >> ((offset - W_US_COMPARE0) * 8)) as u16);
+        }
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const HOST_MAC: [u8; 6] = [0x00, 0x09, 0xbf, 0x11, 0x22, 0x33];
+  const CLIENT_MAC: [u8; 6] = [0x00, 0x09, 0xbf, 0x44, 0x55, 0x66];
+  const MULTIPLAY_MAC: [u8; 6] = [0x03, 0x09, 0xbf, 0x00, 0x00, 0x00];
+
+  const RX_BUFFER_END: u16 = 0x1000;
+  const TX_ADDRESS: u16 = 0x1800;
+
+  // a console with its wifi powered up, listening and in the host's bss
+  fn console(mac_address: [u8; 6], scheduler: &mut Scheduler) -> Wifi {
+    let mut wifi = Wifi::new(scheduler);
+
+    for i in 0..3 {
+      wifi.write(W_MACADDR_0 + i * 2, mac_address[i as usize * 2] as u16 | (mac_address[i as usize * 2 + 1] as u16) << 8, scheduler);
+      wifi.write(W_BSSID_0 + i * 2, HOST_MAC[i as usize * 2] as u16 | (HOST_MAC[i as usize * 2 + 1] as u16) << 8, scheduler);
+    }
+
+    wifi.write(W_MODE_RST, 0b1, scheduler);
+    wifi.write(W_RXBUF_BEGIN, 0, scheduler);
+    wifi.write(W_RXBUF_END, RX_BUFFER_END, scheduler);
+    wifi.write(W_RXCNT, 0x8000, scheduler);
+
+    wifi
+  }
+
+  fn data_frame(frame_control: u16, destination: [u8; 6], source: [u8; 6], bssid: [u8; 6]) -> Vec<u8> {
+    let mut frame = vec![frame_control as u8, (frame_control >> 8) as u8, 0, 0];
+
+    frame.extend_from_slice(&destination);
+    frame.extend_from_slice(&source);
+    frame.extend_from_slice(&bssid);
+    frame.extend_from_slice(&[0, 0]);
+    frame.extend_from_slice(b"hello!");
+
+    frame
+  }
+
+  // puts a frame in wifi ram behind its tx header and returns the value for a W_TXBUF_* register
+  fn queue_frame(wifi: &mut Wifi, address: u16, frame: &[u8]) -> u16 {
+    wifi.write_ram(address as u32 + 10, frame.len() as u16 + 4);
+
+    for (i, halfword) in frame.chunks(2).enumerate() {
+      let value = halfword[0] as u16 | (*halfword.get(1).unwrap_or(&0) as u16) << 8;
+
+      wifi.write_ram((address as usize + TX_HEADER_SIZE + i * 2) as u32, value);
+    }
+
+    0x8000 | address >> 1
+  }
+
+  fn received_frame(wifi: &Wifi) -> Vec<u8> {
+    let length = wifi.read_ram(8) as usize;
+
+    (0..length).map(|i| wifi.ram[RX_HEADER_SIZE + i]).collect()
+  }
+
+  fn link(sender: &mut Wifi, receiver: &mut Wifi) {
+    while let Some(frame) = sender.outgoing.pop_front() {
+      receiver.receive_frame(&frame);
+    }
+  }
+
+  #[test]
+  fn linked_consoles_exchange_a_frame() {
+    let mut scheduler = Scheduler::new();
+
+    let mut host = console(HOST_MAC, &mut scheduler);
+    let mut client = console(CLIENT_MAC, &mut scheduler);
+
+    let frame = data_frame(0x0208, CLIENT_MAC, HOST_MAC, HOST_MAC);
+
+    let loc = queue_frame(&mut host, TX_ADDRESS, &frame);
+
+    host.write(W_TXBUF_LOC1, loc, &scheduler);
+    host.write(W_TXREQ_SET, 0b1, &scheduler);
+
+    assert_eq!(host.register(W_TXBUF_LOC1) & 0x8000, 0);
+
+    link(&mut host, &mut client);
+
+    assert_eq!(received_frame(&client), frame);
+    assert_eq!(client.read_ram(0), 0x8018);
+    assert_ne!(client.register(W_RXBUF_WRCSR), 0);
+    assert_ne!(client.register(W_IF) & (1 << WifiInterrupt::RxComplete as u16), 0);
+  }
+
+  #[test]
+  fn clients_answer_a_multiplay_command() {
+    let mut scheduler = Scheduler::new();
+
+    let mut host = console(HOST_MAC, &mut scheduler);
+    let mut client = console(CLIENT_MAC, &mut scheduler);
+
+    let reply = data_frame(0x0118, HOST_MAC, CLIENT_MAC, HOST_MAC);
+    let reply_loc = queue_frame(&mut client, TX_ADDRESS, &reply);
+
+    client.write(W_TXBUF_REPLY2, reply_loc, &scheduler);
+
+    let cmd = data_frame(FRAME_CONTROL_MULTIPLAY_CMD, MULTIPLAY_MAC, HOST_MAC, HOST_MAC);
+    let cmd_loc = queue_frame(&mut host, TX_ADDRESS, &cmd);
+
+    host.write(W_TXBUF_CMD, cmd_loc, &scheduler);
+    host.write(W_TXREQ_SET, 0b10, &scheduler);
+
+    link(&mut host, &mut client);
+
+    assert_eq!(received_frame(&client), cmd);
+    assert_eq!(client.read_ram(0) & 0xf, 0xc);
+    assert_eq!(client.register(W_TXBUF_REPLY1), reply_loc);
+
+    link(&mut client, &mut host);
+
+    assert_eq!(received_frame(&host), reply);
+    assert_eq!(host.read_ram(0) & 0xf, 0xe);
+
+    // once the reply window is over the host acks the command and it's done
+    host.write(W_US_COUNTCNT, 0b1, &scheduler);
+
+    scheduler.cycles += 3 * WIFI_TICK_CYCLES;
+
+    host.tick(&scheduler);
+
+    assert_eq!(host.register(W_TXBUF_CMD) & 0x8000, 0);
+    assert_ne!(host.register(W_IF) & (1 << WifiInterrupt::MultiplayCmdDone as u16), 0);
+
+    let ack = host.outgoing.pop_front().unwrap();
+
+    assert_eq!(&ack[0..2], &FRAME_CONTROL_MULTIPLAY_ACK.to_le_bytes());
+    assert_eq!(&ack[FRAME_ADDRESS1..FRAME_ADDRESS1 + 6], &MULTIPLAY_ACK_ADDRESS);
+    assert_eq!(&ack[MAC_HEADER_SIZE..], &[0x33, 0x00, 0x00, 0x00]);
+  }
+
+  #[test]
+  fn frames_from_another_bss_are_dropped() {
+    let mut scheduler = Scheduler::new();
+
+    let mut client = console(CLIENT_MAC, &mut scheduler);
+
+    let other_bssid = [0x00, 0x09, 0xbf, 0x77, 0x88, 0x99];
+
+    client.receive_frame(&data_frame(0x0208, CLIENT_MAC, other_bssid, other_bssid));
+
+    assert_eq!(client.register(W_RXBUF_WRCSR), 0);
+
+    // a beacon from another host still comes through
+    let mut beacon = data_frame(0x0080, [0xff; 6], other_bssid, other_bssid);
+
+    beacon.extend_from_slice(&[0; 12]);
+
+    client.receive_frame(&beacon);
+
+    assert_eq!(received_frame(&client), beacon);
+    assert_eq!(client.read_ram(0), 0x0011);
+  }
+}
@@ -29,7 +29,8 @@ pub struct DmaChannel {
   pub pending: bool,
   pub running: bool,
   fifo_mode: bool,
-  is_arm9: bool
+  is_arm9: bool,
+  pub gba_mode: bool
 }
 
 impl DmaChannel {
@@ -45,7 +46,16 @@ impl DmaChannel {
       running: false,
       fifo_mode: false,
       id,
-      is_arm9
+      is_arm9,
+      gba_mode: false
+    }
+  }
+
+  pub fn start_timing(&self, dma_control: DmaControlRegister) -> DmaTiming {
+    if self.gba_mode {
+      dma_control.gba_start_timing(self.id)
+    } else {
+      dma_control.dma_start_timing(self.is_arm9)
     }
   }
 
@@ -118,7 +128,10 @@ impl DmaChannel {
 
       self.running = true;
 
-      let timing = dma_control.dma_start_timing(self.is_arm9);
+      let timing = self.start_timing(dma_control);
+
+      // sound fifo transfers always send 4 words to the same address
+      self.fifo_mode = timing == DmaTiming::Fifo;
 
       if timing == DmaTiming::Immediately || timing == DmaTiming::GeometryCommandFifo {
         if timing == DmaTiming::Immediately {
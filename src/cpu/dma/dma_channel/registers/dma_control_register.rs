@@ -59,4 +59,19 @@ impl DmaControlRegister {
     }
 
   }
-}
\ No newline at end of file
+
+  // the ds arm7 timings take the place of the gba ones, so they need to be decoded differently in gba mode
+  pub fn gba_start_timing(&self, channel_id: usize) -> DmaTiming {
+    match (self.bits() >> 28) & 0x3 {
+      0 => DmaTiming::Immediately,
+      1 => DmaTiming::Vblank,
+      2 => DmaTiming::Hblank,
+      3 => if channel_id == 1 || channel_id == 2 {
+        DmaTiming::Fifo
+      } else {
+        DmaTiming::StartOfDisplay // video capture, which isn't supported
+      },
+      _ => unreachable!()
+    }
+  }
+}
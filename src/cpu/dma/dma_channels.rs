@@ -7,8 +7,7 @@ use super::dma_channel::{registers::dma_control_register::{DmaControlRegister, D
 // const FIFO_TIMING: u16 = 3;
 
 pub struct DmaChannels {
-  pub channels: [DmaChannel; 4],
-  is_arm9: bool
+  pub channels: [DmaChannel; 4]
 }
 
 pub enum AddressType {
@@ -24,14 +23,19 @@ impl DmaChannels{
         DmaChannel::new(1, is_arm9),
         DmaChannel::new(2, is_arm9),
         DmaChannel::new(3, is_arm9)
-      ],
-      is_arm9
+      ]
+    }
+  }
+
+  pub fn set_gba_mode(&mut self, gba_mode: bool) {
+    for channel in &mut self.channels {
+      channel.gba_mode = gba_mode;
     }
   }
 
   pub fn notify_gpu_event(&mut self, timing: DmaTiming) {
     for channel in &mut self.channels {
-      if channel.dma_control.contains(DmaControlRegister::DMA_ENABLE) && channel.dma_control.dma_start_timing(self.is_arm9) == timing {
+      if channel.dma_control.contains(DmaControlRegister::DMA_ENABLE) && channel.start_timing(channel.dma_control) == timing {
         channel.pending = true;
       }
     }
@@ -39,7 +43,7 @@ impl DmaChannels{
 
   pub fn notify_cartridge_event(&mut self) {
     for channel in &mut self.channels {
-      if channel.dma_control.contains(DmaControlRegister::DMA_ENABLE) && channel.dma_control.dma_start_timing(self.is_arm9) == DmaTiming::DSCartridgeSlot {
+      if channel.dma_control.contains(DmaControlRegister::DMA_ENABLE) && channel.start_timing(channel.dma_control) == DmaTiming::DSCartridgeSlot {
         channel.pending = true;
       }
     }
@@ -88,7 +92,7 @@ impl DmaChannels{
     for channel in &mut self.channels {
       if channel.dma_control.contains(DmaControlRegister::DMA_ENABLE)
         && channel.running
-        && channel.dma_control.dma_start_timing(self.is_arm9) == DmaTiming::Fifo
+        && channel.start_timing(channel.dma_control) == DmaTiming::Fifo
         && channel.destination_address == address {
           channel.pending = true;
         }
@@ -97,7 +101,7 @@ impl DmaChannels{
 
   pub fn notify_geometry_fifo_event(&mut self) {
     for channel in &mut self.channels {
-      if channel.dma_control.contains(DmaControlRegister::DMA_ENABLE) && channel.dma_control.dma_start_timing(self.is_arm9) == DmaTiming::GeometryCommandFifo {
+      if channel.dma_control.contains(DmaControlRegister::DMA_ENABLE) && channel.start_timing(channel.dma_control) == DmaTiming::GeometryCommandFifo {
         channel.pending = true;
       }
     }
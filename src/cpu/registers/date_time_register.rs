@@ -1,14 +1,14 @@
-use chrono::{Datelike, Local, Timelike};
-
-
+use chrono::{Datelike, Duration, Local, NaiveDate, NaiveDateTime, Timelike};
 
 pub struct DateTimeRegister {
   pub status_register1: StatusRegister1,
   pub status_register2: StatusRegister2,
   pub alarm1: AlarmRegister,
   pub alarm2: AlarmRegister,
-  pub frequency_duty_setting: bool,
-  pub clock_adjust: u8
+  pub frequency_duty_setting: u8,
+  pub clock_adjust: u8,
+  // the clock starts at host time and runs off emulated time from then on
+  pub clock: NaiveDateTime
 }
 
 impl DateTimeRegister {
@@ -16,10 +16,11 @@ impl DateTimeRegister {
     Self {
       status_register1: StatusRegister1::new(),
       status_register2: StatusRegister2::new(),
-      frequency_duty_setting: false,
+      frequency_duty_setting: 0,
       alarm1: AlarmRegister::new(),
       alarm2: AlarmRegister::new(),
-      clock_adjust: 0
+      clock_adjust: 0,
+      clock: Local::now().naive_local()
     }
   }
 
@@ -30,7 +31,11 @@ impl DateTimeRegister {
     tens << 4 | ones
   }
 
-  pub fn read_status1(&self) -> u8 {
+  fn from_bcd(value: u8) -> u8 {
+    (value >> 4) * 10 + (value & 0xf)
+  }
+
+  pub fn read_status1(&mut self) -> u8 {
     self.status_register1.read()
   }
 
@@ -46,7 +51,7 @@ impl DateTimeRegister {
   }
 
   pub fn read(&self, byte: u8) -> u8 {
-    let time = Local::now();
+    let time = self.clock;
 
     let mut am_or_pm = false;
 
@@ -78,12 +83,37 @@ impl DateTimeRegister {
     self.read(byte + 4)
   }
 
-  pub fn write(&mut self, _value: u8, _byte: u8) {
-    println!("Warning: ignoring setting the date");
+  /*
+    The date and time are taken as a whole once every byte of the command is in, so writing 2/29 doesn't depend
+    on the year that was set before it. Anything that doesn't make a valid date is dropped, and the day of the
+    week always follows the date.
+   */
+  pub fn write(&mut self, bytes: &[u8; 7]) {
+    let date = NaiveDate::from_ymd_opt(
+      2000 + Self::from_bcd(bytes[0]) as i32,
+      Self::from_bcd(bytes[1] & 0x1f) as u32,
+      Self::from_bcd(bytes[2] & 0x3f) as u32
+    );
+
+    if let Some(clock) = date.and_then(|date| self.with_time(date, &bytes[4..7])) {
+      self.clock = clock;
+    }
   }
 
-  pub fn write_time(&mut self, _value: u8, _byte: u8) {
-    println!("Warning: ignoring setting the time");
+  pub fn write_time(&mut self, bytes: &[u8; 3]) {
+    if let Some(clock) = self.with_time(self.clock.date(), bytes) {
+      self.clock = clock;
+    }
+  }
+
+  fn with_time(&self, date: NaiveDate, bytes: &[u8]) -> Option<NaiveDateTime> {
+    let mut hour = Self::from_bcd(bytes[0] & 0x3f) as u32;
+
+    if !self.status_register1.twenty_four_hour_mode {
+      hour = hour % 12 + if (bytes[0] >> 6) & 0b1 == 1 { 12 } else { 0 };
+    }
+
+    date.and_hms_opt(hour, Self::from_bcd(bytes[1] & 0x7f) as u32, Self::from_bcd(bytes[2] & 0x7f) as u32)
   }
 
   pub fn write_status1(&mut self, value: u8) {
@@ -101,24 +131,44 @@ impl DateTimeRegister {
   pub fn write_alarm2(&mut self, value: u8, byte: u8) {
     self.alarm2.write(value, byte);
   }
+
+  pub fn tick_second(&mut self) {
+    self.clock += Duration::seconds(1);
+  }
+
+  pub fn alarm_matches(&self, alarm: &AlarmRegister) -> bool {
+    alarm.matches(self.read(3), self.read(4), self.read(5))
+  }
 }
 
 pub struct StatusRegister1 {
   general_purpose_bits: u8,
-  twenty_four_hour_mode: bool
+  twenty_four_hour_mode: bool,
+  pub int1_flag: bool,
+  pub int2_flag: bool
 }
 
 impl StatusRegister1 {
   pub fn new() -> Self {
     Self {
       general_purpose_bits: 0,
-      twenty_four_hour_mode: false
+      twenty_four_hour_mode: false,
+      int1_flag: false,
+      int2_flag: false
     }
   }
 
-  pub fn read(&self) -> u8 {
-    (self.twenty_four_hour_mode as u8) << 1 |
-      (self.general_purpose_bits) << 2
+  // the interrupt flags clear once they've been read
+  pub fn read(&mut self) -> u8 {
+    let value = (self.twenty_four_hour_mode as u8) << 1 |
+      (self.general_purpose_bits) << 2 |
+      (self.int1_flag as u8) << 4 |
+      (self.int2_flag as u8) << 5;
+
+    self.int1_flag = false;
+    self.int2_flag = false;
+
+    value
   }
 
   pub fn write(&mut self, value: u8) {
@@ -130,7 +180,7 @@ impl StatusRegister1 {
 pub struct StatusRegister2 {
   pub int1_mode: u8,
   general_purpose_bits: u8,
-  int2_enable: bool,
+  pub int2_enable: bool,
   test_mode: bool
 }
 
@@ -191,6 +241,17 @@ impl AlarmRegister {
     }
   }
 
+  // only the fields with their compare bit set take part, and an alarm with none set never goes off
+  pub fn matches(&self, day: u8, hour: u8, min: u8) -> bool {
+    if !self.cmp_day && !self.cmp_hour && !self.cmp_min {
+      return false;
+    }
+
+    (!self.cmp_day || self.day == day) &&
+      (!self.cmp_hour || self.hour == hour & 0x3f && self.am_pm == ((hour >> 6) & 0b1 == 1)) &&
+      (!self.cmp_min || self.min == min)
+  }
+
   pub fn write(&mut self, value: u8, byte: u8) {
     match byte {
       0 => {
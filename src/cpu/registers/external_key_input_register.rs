@@ -8,7 +8,7 @@ bitflags! {
     const UNKNOWN2 = 1 << 4;
     const UNKNOWN3 = 1 << 5;
     const PEN_DOWN = 1 << 6;
-    const HINGE_OPEN = 1 << 7;
+    const HINGE_CLOSED = 1 << 7;
   }
 }
 
@@ -48,6 +48,8 @@ bitflags! {
     const GAME_CARD_TRANSFER_COMPLETE = 0b1 << 19;
     const GAME_CARD_IREQ_MC = 0b1 << 20;
     const GEOMETRY_COMMAND = 0b1 << 21;
+    const SCREENS_UNFOLDING = 0b1 << 22;
+    const WIFI = 0b1 << 24;
 
   }
 }
@@ -71,4 +73,6 @@ pub const FLAG_IPC_SEND_FIFO_EMPTY: u32 = 0b1 << 17;
 pub const FLAG_IPC_RECV_FIFO_NOT_EMPTY: u32 = 0b1 << 18;
 pub const FLAG_GAME_CARD_TRANSFER_COMPLETE: u32 = 0b1 << 19;
 pub const FLAG_GAME_CARD_IREQ_MC: u32 = 0b1 << 20;
-pub const FLAG_GEOMETRY_COMMAND: u32 = 0b1 << 21;
\ No newline at end of file
+pub const FLAG_GEOMETRY_COMMAND: u32 = 0b1 << 21;
+pub const FLAG_SCREENS_UNFOLDING: u32 = 0b1 << 22;
+pub const FLAG_WIFI: u32 = 0b1 << 24;
\ No newline at end of file
@@ -21,6 +21,8 @@ bitflags! {
     const GAME_CARD_TRANSFER_COMPLETE = 0b1 << 19;
     const GAME_CARD_IREQ_MC = 0b1 << 20;
     const GEOMETRY_COMMAND = 0b1 << 21;
+    const SCREENS_UNFOLDING = 0b1 << 22;
+    const WIFI = 0b1 << 24;
   }
 }
 
@@ -0,0 +1,46 @@
+use super::interrupt_request_register::InterruptRequestRegister;
+
+#[derive(Default)]
+pub struct KeyInterruptControlRegister {
+  pub key_mask: u16,
+  pub irq_enable: bool,
+  pub and_condition: bool
+}
+
+impl KeyInterruptControlRegister {
+  pub fn new() -> Self {
+    Self {
+      key_mask: 0,
+      irq_enable: false,
+      and_condition: false
+    }
+  }
+
+  pub fn read(&self) -> u16 {
+    self.key_mask | (self.irq_enable as u16) << 14 | (self.and_condition as u16) << 15
+  }
+
+  pub fn write(&mut self, value: u16) {
+    self.key_mask = value & 0x3ff;
+    self.irq_enable = (value >> 14) & 0b1 == 1;
+    self.and_condition = (value >> 15) & 0b1 == 1;
+  }
+
+  // keys are active low, so a pressed key reads back as 0
+  fn matches(&self, key_input: u16) -> bool {
+    let pressed = !key_input & self.key_mask;
+
+    if self.and_condition {
+      pressed == self.key_mask
+    } else {
+      pressed != 0
+    }
+  }
+
+  // the interrupt fires when the condition goes from unmet to met
+  pub fn check_interrupt(&self, previous_key_input: u16, key_input: u16, interrupt_request: &mut InterruptRequestRegister) {
+    if self.irq_enable && !self.matches(previous_key_input) && self.matches(key_input) {
+      interrupt_request.insert(InterruptRequestRegister::KEYPAD);
+    }
+  }
+}
@@ -11,6 +11,6 @@ pub mod spi_control_register;
 pub mod wram_control_register;
 pub mod wram_status_register;
 pub mod external_key_input_register;
-pub mod mosaic_register;
 pub mod real_time_clock_register;
-pub mod date_time_register;
\ No newline at end of file
+pub mod date_time_register;
+pub mod key_interrupt_control_register;
\ No newline at end of file
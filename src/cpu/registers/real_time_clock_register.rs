@@ -1,4 +1,15 @@
-use super::date_time_register::DateTimeRegister;
+use chrono::{NaiveDateTime, Timelike};
+
+use crate::scheduler::{EventType, Scheduler};
+
+use super::{date_time_register::DateTimeRegister, interrupt_request_register::InterruptRequestRegister};
+
+// the rtc ticks at 16Hz, the fastest interrupt frequency it can select
+const TICKS_PER_SECOND: usize = 16;
+pub const CYCLES_PER_TICK: usize = 33_513_982 / TICKS_PER_SECOND;
+
+// the alarm 1 register only holds an alarm in this int1 mode, the rest of the time it's the frequency duty setting
+const INT1_ALARM1: u8 = 0b0100;
 
 #[derive(Copy, Clone, PartialEq, Debug)]
 enum CommandMode {
@@ -56,12 +67,17 @@ pub struct RealTimeClockRegister {
   param: Param,
   current_data_byte: u8,
   date_time: DateTimeRegister,
+  // date and time writes are held here until the last byte comes in
+  date_time_bytes: [u8; 7],
   data_bytes_remaining: u8,
-  current_data_bits: usize
+  current_data_bits: usize,
+  tick_count: usize
 }
 
 impl RealTimeClockRegister {
-  pub fn new() -> Self {
+  pub fn new(scheduler: &mut Scheduler) -> Self {
+    scheduler.schedule(EventType::RtcTick, CYCLES_PER_TICK);
+
     Self {
       data: false,
       sck: false,
@@ -77,7 +93,9 @@ impl RealTimeClockRegister {
       current_data_byte: 0,
       data_bytes_remaining: 0,
       date_time: DateTimeRegister::new(),
-      current_data_bits: 0
+      date_time_bytes: [0; 7],
+      current_data_bits: 0,
+      tick_count: 0
     }
   }
 
@@ -210,9 +228,9 @@ impl RealTimeClockRegister {
     self.current_data_byte = match self.param {
       Param::StatusRegister1 => self.date_time.read_status1(),
       Param::StatusRegister2 => self.date_time.read_status2(),
-      Param::AlarmTime1FrequencyDuty if self.date_time.status_register2.int1_mode & 0b1 == 0 => {
+      Param::AlarmTime1FrequencyDuty if self.date_time.status_register2.int1_mode != INT1_ALARM1 => {
         self.data_bytes_remaining = 0;
-        self.date_time.frequency_duty_setting as u8
+        self.date_time.frequency_duty_setting
       }
       Param::AlarmTime1FrequencyDuty => self.date_time.read_alarm1(2 - self.data_bytes_remaining),
       Param::AlarmTime2 => self.date_time.read_alarm2(2 - self.data_bytes_remaining),
@@ -229,14 +247,27 @@ impl RealTimeClockRegister {
     match self.param {
       Param::StatusRegister1 => self.date_time.write_status1(self.current_data_byte),
       Param::StatusRegister2 => self.date_time.write_status2(self.current_data_byte),
-      Param::AlarmTime1FrequencyDuty if self.date_time.status_register2.int1_mode & 0b1 == 0 => {
+      Param::AlarmTime1FrequencyDuty if self.date_time.status_register2.int1_mode != INT1_ALARM1 => {
         self.data_bytes_remaining = 0;
-        self.date_time.frequency_duty_setting = self.current_data_byte != 0;
+        self.date_time.frequency_duty_setting = self.current_data_byte;
       }
       Param::AlarmTime1FrequencyDuty => self.date_time.write_alarm1(self.current_data_byte, 2 - self.data_bytes_remaining),
       Param::AlarmTime2 => self.date_time.write_alarm2(self.current_data_byte, 2 - self.data_bytes_remaining),
-      Param::DateTime => self.date_time.write(self.current_data_byte, 6 - self.data_bytes_remaining),
-      Param::Time => self.date_time.write_time(self.current_data_byte, 2 - self.data_bytes_remaining),
+      Param::DateTime => {
+        self.date_time_bytes[6 - self.data_bytes_remaining as usize] = self.current_data_byte;
+
+        // a transfer that's cut short leaves the clock alone
+        if self.data_bytes_remaining == 0 {
+          self.date_time.write(&self.date_time_bytes);
+        }
+      }
+      Param::Time => {
+        self.date_time_bytes[2 - self.data_bytes_remaining as usize] = self.current_data_byte;
+
+        if self.data_bytes_remaining == 0 {
+          self.date_time.write_time(self.date_time_bytes[..3].try_into().unwrap());
+        }
+      }
       Param::ClockAdjust => self.date_time.clock_adjust = self.current_data_byte,
       Param::None => unreachable!()
     }
@@ -254,4 +285,70 @@ impl RealTimeClockRegister {
       (self.sck_direction as u8) << 5 |
       (self.cs_direction as u8) << 6
   }
+
+  pub fn tick(&mut self, interrupt_request: &mut InterruptRequestRegister) {
+    self.tick_count = (self.tick_count + 1) % TICKS_PER_SECOND;
+
+    let int1_mode = self.date_time.status_register2.int1_mode;
+
+    // selected frequency steady interrupt
+    if int1_mode & 0b1011 == 0b0001 && self.on_selected_frequency() {
+      self.trigger_int1(interrupt_request);
+    }
+
+    if self.tick_count == 0 {
+      self.date_time.tick_second();
+
+      if self.date_time.clock.second() == 0 {
+        self.on_minute(interrupt_request);
+      }
+    }
+  }
+
+  fn on_minute(&mut self, interrupt_request: &mut InterruptRequestRegister) {
+    let int1_mode = self.date_time.status_register2.int1_mode;
+
+    let int1 = match int1_mode {
+      // per-minute edge and per-minute steady interrupts
+      0b0010 | 0b0110 | 0b0011 | 0b0111 => true,
+      INT1_ALARM1 => self.date_time.alarm_matches(&self.date_time.alarm1),
+      _ => false
+    };
+
+    if int1 {
+      self.trigger_int1(interrupt_request);
+    }
+
+    if self.date_time.status_register2.int2_enable && self.date_time.alarm_matches(&self.date_time.alarm2) {
+      self.date_time.status_register1.int2_flag = true;
+      interrupt_request.insert(InterruptRequestRegister::SIO_RTC);
+    }
+  }
+
+  // bits 0-4 of the duty setting select 1, 2, 4, 8 and 16Hz, the fastest one sets the pace
+  fn on_selected_frequency(&self) -> bool {
+    let setting = self.date_time.frequency_duty_setting & 0x1f;
+
+    if setting == 0 {
+      return false;
+    }
+
+    let frequency = 1 << (7 - setting.leading_zeros());
+
+    self.tick_count.is_multiple_of(TICKS_PER_SECOND / frequency)
+  }
+
+  fn trigger_int1(&mut self, interrupt_request: &mut InterruptRequestRegister) {
+    self.date_time.status_register1.int1_flag = true;
+    interrupt_request.insert(InterruptRequestRegister::SIO_RTC);
+  }
+
+  // the clock only moves with emulated time. a reset carries it over, and it's what a save state would need to keep
+  pub fn clock(&self) -> NaiveDateTime {
+    self.date_time.clock
+  }
+
+  pub fn set_clock(&mut self, clock: NaiveDateTime) {
+    self.date_time.clock = clock;
+  }
 }
\ No newline at end of file
@@ -17,6 +17,12 @@ impl Timers {
     }
   }
 
+  pub fn set_gba_mode(&mut self, gba_mode: bool) {
+    for timer in &mut self.t {
+      timer.gba_mode = gba_mode;
+    }
+  }
+
   pub fn handle_overflow(&mut self, timer_id: usize, dma: &mut DmaChannels, interrupt_request: &mut InterruptRequestRegister, scheduler: &mut Scheduler, cycles_left: usize) {
     if timer_id != 3 {
       let next_timer_id = timer_id + 1;
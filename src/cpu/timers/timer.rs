@@ -11,7 +11,8 @@ pub struct Timer {
   pub prescalar_frequency: u32,
   pub running: bool,
   is_arm9: bool,
-  start_cycles: usize
+  start_cycles: usize,
+  pub gba_mode: bool
 }
 
 impl Timer {
@@ -24,7 +25,8 @@ impl Timer {
       running: false,
       id,
       is_arm9,
-      start_cycles: 0
+      start_cycles: 0,
+      gba_mode: false
     }
   }
 
@@ -67,6 +69,11 @@ impl Timer {
     }
   }
 
+  // gba timers tick at half the rate of the ds ones
+  fn get_prescalar(&self, timer_ctl: TimerControl) -> u32 {
+    CYCLE_LUT[timer_ctl.prescalar_selection() as usize] << self.gba_mode as u32
+  }
+
   pub fn reload_timer_value(&mut self, value: u16) {
     self.reload_value = value;
   }
@@ -78,7 +85,7 @@ impl Timer {
       let prescalar = if self.prescalar_frequency != 0 {
         self.prescalar_frequency
       } else {
-        CYCLE_LUT[self.timer_ctl.prescalar_selection() as usize]
+        self.get_prescalar(self.timer_ctl)
       };
 
       let time_passed = (current_cycles - self.start_cycles) / prescalar as usize;
@@ -92,7 +99,7 @@ impl Timer {
   pub fn write_timer_control(&mut self, value: u16, scheduler: &mut Scheduler) {
     let new_ctl = TimerControl::from_bits_retain(value);
 
-    self.prescalar_frequency = CYCLE_LUT[new_ctl.prescalar_selection() as usize];
+    self.prescalar_frequency = self.get_prescalar(new_ctl);
 
     let event_type = if self.is_arm9 {
       EventType::Timer9(self.id)
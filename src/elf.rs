@@ -0,0 +1,296 @@
+use crate::util;
+
+/*
+  Loads the elf files devkitARM and BlocksDS build before ndstool packs them into a rom. The
+  loadable segments are flattened into one binary per cpu and wrapped in a minimal rom, so
+  they boot through the same direct boot path as everything else. The symbols are kept
+  around so traces and debugging tools can show function names.
+ */
+
+const ELF_MAGIC: [u8; 4] = [0x7f, b'E', b'L', b'F'];
+const ELF_CLASS_32: u8 = 1;
+const ELF_DATA_LITTLE_ENDIAN: u8 = 1;
+const ELF_MACHINE_ARM: u16 = 40;
+
+const PT_LOAD: u32 = 1;
+const SHT_SYMTAB: u32 = 2;
+
+const STT_OBJECT: u8 = 1;
+const STT_FUNC: u8 = 2;
+
+const SYMBOL_SIZE: usize = 0x10;
+
+const SECURE_AREA_START: usize = 0x4000;
+const ARM9_ROM_OFFSET: usize = 0x8000;
+
+// what ndstool puts in the secure area of homebrew, so the cartridge doesn't try to decrypt it
+const SECURE_AREA_DESTROYED_ID: u32 = 0xe7ff_deff;
+
+/*
+  The same limits ndstool checks: the arm9 binary is loaded into main ram, and the arm7 one into
+  either main ram or the arm7's own wram. Each binary is a single block, so it has to fit in one
+  of these, given as start and end address.
+ */
+const ARM9_LOAD_REGIONS: [(u32, u32); 1] = [(0x200_0000, 0x23b_fe00)];
+const ARM7_LOAD_REGIONS: [(u32, u32); 2] = [(0x37f_8000, 0x380_7e00), (0x200_0000, 0x23b_fe00)];
+
+// an arm7 that does nothing but spin, for when no arm7 elf is given
+const IDLE_ARM7_ADDRESS: u32 = 0x37f_8000;
+const IDLE_ARM7_LOOP: u32 = 0xeaff_fffe;
+
+#[derive(Clone)]
+pub struct Symbol {
+  pub name: String,
+  pub address: u32,
+  pub size: u32
+}
+
+#[derive(Clone, Default)]
+pub struct SymbolTable {
+  symbols: Vec<Symbol>
+}
+
+impl SymbolTable {
+  pub fn is_empty(&self) -> bool {
+    self.symbols.is_empty()
+  }
+
+  // the symbol an address falls into, along with how far into it the address is
+  pub fn lookup(&self, address: u32) -> Option<(&Symbol, u32)> {
+    let index = self.symbols.partition_point(|symbol| symbol.address <= address);
+
+    if index == 0 {
+      return None;
+    }
+
+    let symbol = &self.symbols[index - 1];
+    let offset = address - symbol.address;
+
+    if symbol.size != 0 && offset >= symbol.size {
+      return None;
+    }
+
+    Some((symbol, offset))
+  }
+
+  pub fn address_of(&self, name: &str) -> Option<u32> {
+    self.symbols.iter().find(|symbol| symbol.name == name).map(|symbol| symbol.address)
+  }
+
+  pub fn describe(&self, address: u32) -> Option<String> {
+    self.lookup(address).map(|(symbol, offset)| {
+      if offset == 0 {
+        symbol.name.clone()
+      } else {
+        format!("{}+0x{:x}", symbol.name, offset)
+      }
+    })
+  }
+}
+
+pub struct ElfFile {
+  pub entry: u32,
+  pub load_address: u32,
+  pub binary: Vec<u8>,
+  pub symbols: SymbolTable
+}
+
+impl ElfFile {
+  pub fn parse(bytes: &[u8], is_arm9: bool) -> Option<Self> {
+    if bytes.len() < 0x34 || bytes[0..4] != ELF_MAGIC {
+      println!("[WARN] not an elf file");
+      return None;
+    }
+
+    if bytes[4] != ELF_CLASS_32 || bytes[5] != ELF_DATA_LITTLE_ENDIAN || read_16(bytes, 0x12)? != ELF_MACHINE_ARM {
+      println!("[WARN] elf file isn't for a 32 bit little endian arm cpu");
+      return None;
+    }
+
+    let entry = read_32(bytes, 0x18)?;
+
+    let (load_address, binary) = Self::flatten_segments(bytes, entry, is_arm9)?;
+
+    Some(Self {
+      entry,
+      load_address,
+      binary,
+      symbols: Self::read_symbols(bytes).unwrap_or_default()
+    })
+  }
+
+  /*
+    segments are placed by their load address, which is where crt0 expects to find itcm and dtcm before copying them over.
+    anything that doesn't load into the region the entry point is in can't be part of the binary, like the dsi-only
+    sections blocksds puts at 2400000h, so those get left out.
+   */
+  fn flatten_segments(bytes: &[u8], entry: u32, is_arm9: bool) -> Option<(u32, Vec<u8>)> {
+    let (name, regions) = if is_arm9 { ("arm9", &ARM9_LOAD_REGIONS[..]) } else { ("arm7", &ARM7_LOAD_REGIONS[..]) };
+
+    let Some(&(region_start, region_end)) = regions.iter().find(|(start, end)| entry >= *start && entry < *end) else {
+      println!("[WARN] {} elf entry point {:x} is outside of where ndstool can load it", name, entry);
+      return None;
+    };
+
+    let program_header_offset = read_32(bytes, 0x1c)? as usize;
+    let program_header_size = read_16(bytes, 0x2a)? as usize;
+    let program_header_count = read_16(bytes, 0x2c)? as usize;
+
+    let mut segments = Vec::new();
+
+    for i in 0..program_header_count {
+      let header = program_header_offset + i * program_header_size;
+
+      let segment_type = read_32(bytes, header)?;
+      let offset = read_32(bytes, header + 0x4)? as usize;
+      let physical_address = read_32(bytes, header + 0xc)?;
+      let file_size = read_32(bytes, header + 0x10)? as usize;
+
+      if segment_type != PT_LOAD || file_size == 0 {
+        continue;
+      }
+
+      let segment_end = physical_address as u64 + file_size as u64;
+
+      if physical_address < region_start || segment_end > region_end as u64 {
+        println!(
+          "[WARN] leaving out {} elf segment at {:x}-{:x}, it's outside of the {:x}-{:x} load region",
+          name,
+          physical_address,
+          segment_end,
+          region_start,
+          region_end
+        );
+        continue;
+      }
+
+      segments.push((physical_address, bytes.get(offset..offset + file_size)?));
+    }
+
+    let start = segments.iter().map(|(address, _)| *address).min()?;
+    let end = segments.iter().map(|(address, data)| *address + data.len() as u32).max()?;
+
+    let mut binary = vec![0; (end - start) as usize];
+
+    for (address, data) in segments {
+      let offset = (address - start) as usize;
+
+      binary[offset..offset + data.len()].copy_from_slice(data);
+    }
+
+    Some((start, binary))
+  }
+
+  fn read_symbols(bytes: &[u8]) -> Option<SymbolTable> {
+    let section_header_offset = read_32(bytes, 0x20)? as usize;
+    let section_header_size = read_16(bytes, 0x2e)? as usize;
+    let section_header_count = read_16(bytes, 0x30)? as usize;
+
+    let section = |index: usize| section_header_offset + index * section_header_size;
+
+    let symbol_table = (0..section_header_count).map(section).find(|header| read_32(bytes, header + 0x4) == Some(SHT_SYMTAB))?;
+
+    let offset = read_32(bytes, symbol_table + 0x10)? as usize;
+    let size = read_32(bytes, symbol_table + 0x14)? as usize;
+    let string_table = section(read_32(bytes, symbol_table + 0x18)? as usize);
+
+    let strings_offset = read_32(bytes, string_table + 0x10)? as usize;
+
+    let mut symbols = Vec::new();
+
+    for entry in (offset..offset + size).step_by(SYMBOL_SIZE) {
+      let name_offset = read_32(bytes, entry)? as usize;
+      let address = read_32(bytes, entry + 0x4)?;
+      let symbol_size = read_32(bytes, entry + 0x8)?;
+      let symbol_type = bytes.get(entry + 0xc)? & 0xf;
+
+      if symbol_type != STT_FUNC && symbol_type != STT_OBJECT {
+        continue;
+      }
+
+      let name_bytes = bytes.get(strings_offset + name_offset..)?;
+      let name_length = name_bytes.iter().position(|byte| *byte == 0)?;
+      let name = String::from_utf8_lossy(&name_bytes[..name_length]).to_string();
+
+      if name.is_empty() {
+        continue;
+      }
+
+      symbols.push(Symbol {
+        name,
+        // thumb functions have the low bit set
+        address: if symbol_type == STT_FUNC { address & !0b1 } else { address },
+        size: symbol_size
+      });
+    }
+
+    symbols.sort_by_key(|symbol| symbol.address);
+
+    Some(SymbolTable { symbols })
+  }
+}
+
+// wraps the binaries in a rom with just enough of a header for direct boot
+pub fn build_rom(arm9: &ElfFile, arm7: Option<&ElfFile>) -> Vec<u8> {
+  let idle_arm7 = IDLE_ARM7_LOOP.to_le_bytes();
+
+  let (arm7_binary, arm7_address, arm7_entry) = match arm7 {
+    Some(arm7) => (&arm7.binary[..], arm7.load_address, arm7.entry),
+    None => {
+      println!("[WARN] no arm7 elf given, the arm7 will sit in a loop");
+      (&idle_arm7[..], IDLE_ARM7_ADDRESS, IDLE_ARM7_ADDRESS)
+    }
+  };
+
+  let arm7_rom_offset = align(ARM9_ROM_OFFSET + arm9.binary.len());
+  let rom_size = align(arm7_rom_offset + arm7_binary.len());
+
+  let mut rom = vec![0; rom_size];
+
+  rom[0x0..0xc].copy_from_slice(b"HOMEBREW\0\0\0\0");
+  rom[0xc..0x10].copy_from_slice(b"####");
+  rom[0x10..0x12].copy_from_slice(b"00");
+
+  let mut write_32 = |offset: usize, value: u32| rom[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+
+  write_32(0x20, ARM9_ROM_OFFSET as u32);
+  write_32(0x24, arm9.entry);
+  write_32(0x28, arm9.load_address);
+  write_32(0x2c, arm9.binary.len() as u32);
+
+  write_32(0x30, arm7_rom_offset as u32);
+  write_32(0x34, arm7_entry);
+  write_32(0x38, arm7_address);
+  write_32(0x3c, arm7_binary.len() as u32);
+
+  // the same gamecard timings ndstool uses
+  write_32(0x60, 0x0058_6000);
+  write_32(0x64, 0x0018_08f8);
+
+  write_32(0x80, rom_size as u32);
+  write_32(0x84, SECURE_AREA_START as u32);
+
+  write_32(SECURE_AREA_START, SECURE_AREA_DESTROYED_ID);
+  write_32(SECURE_AREA_START + 4, SECURE_AREA_DESTROYED_ID);
+
+  rom[ARM9_ROM_OFFSET..ARM9_ROM_OFFSET + arm9.binary.len()].copy_from_slice(&arm9.binary);
+  rom[arm7_rom_offset..arm7_rom_offset + arm7_binary.len()].copy_from_slice(arm7_binary);
+
+  let header_crc = util::crc16(0xffff, &rom[0..0x15e]);
+
+  rom[0x15e..0x160].copy_from_slice(&header_crc.to_le_bytes());
+
+  rom
+}
+
+fn align(size: usize) -> usize {
+  (size + 0x1ff) & !0x1ff
+}
+
+fn read_16(bytes: &[u8], offset: usize) -> Option<u16> {
+  Some(u16::from_le_bytes(bytes.get(offset..offset + 2)?.try_into().ok()?))
+}
+
+fn read_32(bytes: &[u8], offset: usize) -> Option<u32> {
+  Some(u32::from_le_bytes(bytes.get(offset..offset + 4)?.try_into().ok()?))
+}
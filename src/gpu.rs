@@ -17,6 +17,8 @@ use registers::{
   power_control_register2::PowerControlRegister2,
   vram_control_register::VramControlRegister
 };
+use display_fifo::DisplayFifo;
+use layer_debug::{DebugLayer, GpuEngine, LayerDebug};
 use vram::{Bank, VRam};
 
 use crate::{
@@ -24,10 +26,7 @@ use crate::{
     dma::{
       dma_channel::registers::dma_control_register::DmaTiming,
       dma_channels::DmaChannels
-    }, registers::{
-      interrupt_request_register::InterruptRequestRegister,
-      mosaic_register::MosaicRegister
-    }
+    }, registers::interrupt_request_register::InterruptRequestRegister
   },
   scheduler::{
     EventType,
@@ -40,6 +39,9 @@ pub mod engine_2d;
 pub mod engine_3d;
 pub mod vram;
 pub mod color;
+pub mod display_fifo;
+pub mod layer_debug;
+pub mod viewer;
 
 const NUM_LINES: u16 = 263;
 
@@ -51,6 +53,14 @@ pub const HDRAW_CYCLES: usize = 524;
 
 pub const FPS_INTERVAL: u128 = 1000 / 60;
 
+pub const GBA_SCREEN_HEIGHT: u16 = 160;
+pub const GBA_SCREEN_WIDTH: u16 = 240;
+
+// in GBA mode a line is 1232 GBA cycles long, which is twice that in scheduler cycles
+const GBA_NUM_LINES: u16 = 228;
+const GBA_HBLANK_CYCLES: usize = 1920;
+const GBA_HDRAW_CYCLES: usize = 544;
+
 const BANK_A: u32 = Bank::BankA as u32;
 const BANK_B: u32 = Bank::BankB as u32;
 const BANK_C: u32 = Bank::BankC as u32;
@@ -97,11 +107,13 @@ pub struct GPU {
   pub vramcnt: [VramControlRegister; 9],
   pub dispstat: [DisplayStatusRegister; 2],
   pub frame_finished: bool,
+  pub frame_count: usize,
   pub vram: VRam,
   pub vcount: u16,
   pub dispcapcnt: DisplayCaptureControlRegister,
-  pub mosaic: MosaicRegister,
+  pub display_fifo: DisplayFifo,
   pub is_capturing: bool,
+  pub gba_mode: bool,
   previous_time: u128
 }
 
@@ -122,11 +134,13 @@ impl GPU {
       vramcnt: vramcnt.try_into().unwrap(),
       dispstat: [DisplayStatusRegister::new(), DisplayStatusRegister::new()],
       dispcapcnt: DisplayCaptureControlRegister::new(),
+      display_fifo: DisplayFifo::new(),
       vcount: 0,
       frame_finished: false,
+      frame_count: 0,
       vram: VRam::new(),
-      mosaic: MosaicRegister::new(),
       is_capturing: false,
+      gba_mode: false,
       previous_time: 0
     };
 
@@ -168,11 +182,16 @@ impl GPU {
       dispstat.flags.insert(DispStatFlags::HBLANK);
     }
 
-    for dma in dma_channels {
-      dma.notify_gpu_event(DmaTiming::Hblank);
+    if !self.gba_mode {
+      for dma in dma_channels {
+        dma.notify_gpu_event(DmaTiming::Hblank);
+      }
+    } else if self.vcount < GBA_SCREEN_HEIGHT {
+      // gba hblank dmas don't run during vblank
+      dma_channels[0].notify_gpu_event(DmaTiming::Hblank);
     }
 
-    if self.vcount < SCREEN_HEIGHT {
+    if self.vcount < self.screen_height() {
       self.render_line();
     }
 
@@ -196,7 +215,9 @@ impl GPU {
     dma_channels: &mut [&mut DmaChannels],
     cycles_left: usize)
   {
-    scheduler.schedule(EventType::HBlank, HBLANK_CYCLES - cycles_left);
+    let hblank_cycles = if self.gba_mode { GBA_HBLANK_CYCLES } else { HBLANK_CYCLES };
+
+    scheduler.schedule(EventType::HBlank, hblank_cycles - cycles_left);
     for dispstat in &mut self.dispstat {
       dispstat.flags.remove(DispStatFlags::HBLANK);
     }
@@ -204,32 +225,51 @@ impl GPU {
     self.engine_a.clear_obj_lines();
     self.engine_b.clear_obj_lines();
 
+    self.engine_a.mosaic.on_end_line(self.vcount);
+    self.engine_b.mosaic.on_end_line(self.vcount);
+
     self.vcount += 1;
 
-    if self.vcount == NUM_LINES {
+    let num_lines = if self.gba_mode { GBA_NUM_LINES } else { NUM_LINES };
+
+    if self.vcount == num_lines {
       self.vcount = 0;
 
       self.engine_a.on_end_vblank();
       self.engine_b.on_end_vblank();
     }
 
+    // the display fifo gets topped up over the course of every visible line
+    if !self.gba_mode && self.vcount < SCREEN_HEIGHT {
+      self.display_fifo.start_line();
+
+      for dma in dma_channels.iter_mut() {
+        dma.notify_gpu_event(DmaTiming::MainMemoryDisplay);
+      }
+    }
+
     if self.vcount == 0 {
       self.is_capturing = self.dispcapcnt.capture_enable;
 
       for dispstat in &mut self.dispstat {
         dispstat.flags.remove(DispStatFlags::VBLANK);
       }
-    } else if self.vcount == SCREEN_HEIGHT {
+    } else if self.vcount == self.screen_height() {
       if self.is_capturing {
         self.dispcapcnt.capture_enable = false;
       }
       self.trigger_vblank();
 
+      if self.gba_mode {
+        self.compose_gba_frame();
+      }
+
       for dma in dma_channels {
         dma.notify_gpu_event(DmaTiming::Vblank);
       }
 
       self.frame_finished = true;
+      self.frame_count += 1;
 
       self.check_interrupts(DispStatFlags::VBLANK_IRQ_ENABLE, InterruptRequestRegister::VBLANK, interrupt_requests);
     } else if self.vcount == NUM_LINES - 48 {
@@ -264,10 +304,6 @@ impl GPU {
     let start_address = self.vcount as usize * SCREEN_WIDTH as usize;
     let block = self.engine_a.dispcnt.vram_block;
 
-    if self.dispcapcnt.source_b == ScreenSourceB::MainMemoryDisplayFifo {
-      todo!("main memory display fifo not implemented");
-    }
-
     fn get_3d_pixel(address: usize, _: &Engine2d<false>, engine3d: &Engine3d) -> u16 {
       if let Some(color) = engine3d.frame_buffer[address].color {
         return (color.r & 0x1f) as u16 | (color.g as u16 & 0x1f) << 5 | (color.b as u16 & 0x1f) << 10
@@ -315,7 +351,13 @@ impl GPU {
 
     let mut source_b: [u8; 2 * SCREEN_WIDTH as usize] = [0; 2 * SCREEN_WIDTH as usize];
 
-    source_b[..2 * width].copy_from_slice(&self.vram.banks[block as usize][read_offset..read_offset + 2 * width]);
+    if self.dispcapcnt.source_b == ScreenSourceB::MainMemoryDisplayFifo {
+      for (i, pixel) in self.display_fifo.line[..width].iter().enumerate() {
+        source_b[2 * i..2 * i + 2].copy_from_slice(&pixel.to_le_bytes());
+      }
+    } else {
+      source_b[..2 * width].copy_from_slice(&self.vram.banks[block as usize][read_offset..read_offset + 2 * width]);
+    }
 
     let write_offset = 2 * start_address as usize + self.dispcapcnt.vram_write_offset as usize;
     let write_block = self.dispcapcnt.vram_write_block as usize;
@@ -507,7 +549,99 @@ impl GPU {
   }
 
   pub fn schedule_hdraw(&mut self, scheduler: &mut Scheduler, cycles_left: usize) {
-    scheduler.schedule(EventType::HDraw, HDRAW_CYCLES - cycles_left);
+    let hdraw_cycles = if self.gba_mode { GBA_HDRAW_CYCLES } else { HDRAW_CYCLES };
+
+    scheduler.schedule(EventType::HDraw, hdraw_cycles - cycles_left);
+  }
+
+  fn screen_height(&self) -> u16 {
+    if self.gba_mode {
+      GBA_SCREEN_HEIGHT
+    } else {
+      SCREEN_HEIGHT
+    }
+  }
+
+  /*
+    GBA mode only uses engine A, displayed on the top screen. VRAM banks A and B are
+    mapped as the GBA's bg and obj vram respectively.
+   */
+  pub fn enter_gba_mode(&mut self) {
+    self.gba_mode = true;
+    self.engine_a.gba_mode = true;
+    self.vcount = 0;
+
+    self.write_vramcnt(BANK_A, 0x81);
+    self.write_vramcnt(BANK_B, 0x82);
+
+    self.powcnt1 = PowerControlRegister1::LCD_ENABLE | PowerControlRegister1::ENGINE_A_ENABLE | PowerControlRegister1::TOP_A;
+
+    self.engine_a.write_gba_dispcnt(0x80);
+
+    for pixel in self.engine_b.pixels.chunks_mut(4) {
+      pixel.copy_from_slice(&[0, 0, 0, 0xff]);
+    }
+  }
+
+  // centers the 240x160 gba image on the top screen, with a black border around it
+  fn compose_gba_frame(&mut self) {
+    let x_offset = ((SCREEN_WIDTH - GBA_SCREEN_WIDTH) / 2) as usize;
+    let y_offset = ((SCREEN_HEIGHT - GBA_SCREEN_HEIGHT) / 2) as usize;
+
+    let pixels = &mut self.engine_a.pixels;
+
+    // the image only ever moves down and to the right, so copy it starting from the end
+    for y in (0..GBA_SCREEN_HEIGHT as usize).rev() {
+      for x in (0..GBA_SCREEN_WIDTH as usize).rev() {
+        let source = 4 * (x + y * SCREEN_WIDTH as usize);
+        let destination = 4 * (x + x_offset + (y + y_offset) * SCREEN_WIDTH as usize);
+
+        pixels.copy_within(source..source + 4, destination);
+      }
+    }
+
+    for y in 0..SCREEN_HEIGHT as usize {
+      for x in 0..SCREEN_WIDTH as usize {
+        let is_border = x < x_offset
+          || x >= x_offset + GBA_SCREEN_WIDTH as usize
+          || y < y_offset
+          || y >= y_offset + GBA_SCREEN_HEIGHT as usize;
+
+        if is_border {
+          let i = 4 * (x + y * SCREEN_WIDTH as usize);
+
+          pixels[i..i + 4].copy_from_slice(&[0, 0, 0, 0xff]);
+        }
+      }
+    }
+  }
+
+  pub fn layer_debug(&self, engine: GpuEngine) -> &LayerDebug {
+    match engine {
+      GpuEngine::A => &self.engine_a.layer_debug,
+      GpuEngine::B => &self.engine_b.layer_debug
+    }
+  }
+
+  fn layer_debug_mut(&mut self, engine: GpuEngine) -> &mut LayerDebug {
+    match engine {
+      GpuEngine::A => &mut self.engine_a.layer_debug,
+      GpuEngine::B => &mut self.engine_b.layer_debug
+    }
+  }
+
+  pub fn set_layer_disabled(&mut self, engine: GpuEngine, layer: DebugLayer, disabled: bool) {
+    self.layer_debug_mut(engine).set_disabled(layer, disabled);
+  }
+
+  // only shows the given layer (plus effects), or everything again with None
+  pub fn set_solo_layer(&mut self, engine: GpuEngine, layer: Option<DebugLayer>) {
+    self.layer_debug_mut(engine).set_solo(layer);
+  }
+
+  pub fn reset_layer_debug(&mut self) {
+    self.engine_a.layer_debug = LayerDebug::new();
+    self.engine_b.layer_debug = LayerDebug::new();
   }
 
   fn trigger_vblank(&mut self) {
@@ -518,7 +652,7 @@ impl GPU {
 
   fn render_line(&mut self) {
     if self.powcnt1.contains(PowerControlRegister1::ENGINE_A_ENABLE) {
-      self.engine_a.render_line(self.vcount, &mut self.vram, &self.engine3d.frame_buffer);
+      self.engine_a.render_line(self.vcount, &mut self.vram, &self.engine3d.frame_buffer, &self.display_fifo);
 
       // capture image if needed
       if self.is_capturing && self.vcount < self.dispcapcnt.get_capture_height() {
@@ -527,7 +661,7 @@ impl GPU {
       }
     }
     if self.powcnt1.contains(PowerControlRegister1::ENGINE_B_ENABLE) {
-      self.engine_b.render_line(self.vcount, &mut self.vram, &self.engine3d.frame_buffer);
+      self.engine_b.render_line(self.vcount, &mut self.vram, &self.engine3d.frame_buffer, &self.display_fifo);
     }
   }
 }
\ No newline at end of file
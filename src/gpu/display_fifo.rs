@@ -0,0 +1,61 @@
+use std::collections::VecDeque;
+
+use super::SCREEN_WIDTH;
+
+const FIFO_CAPACITY: usize = 16;
+
+/*
+  The main memory display fifo, fed through DISP_MMEM_FIFO (usually by a dma with the main memory
+  display timing). It's read by display mode 3 and by capture source B.
+
+  The display drains the fifo as fast as it's filled until it has a full line. Pixels written after
+  that wait in the 16 entry fifo for the next line, and anything past that is dropped.
+ */
+pub struct DisplayFifo {
+  fifo: VecDeque<u16>,
+  pub line: [u16; SCREEN_WIDTH as usize],
+  line_length: usize
+}
+
+impl Default for DisplayFifo {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl DisplayFifo {
+  pub fn new() -> Self {
+    Self {
+      fifo: VecDeque::with_capacity(FIFO_CAPACITY),
+      line: [0; SCREEN_WIDTH as usize],
+      line_length: 0
+    }
+  }
+
+  pub fn write(&mut self, value: u32) {
+    self.push(value as u16);
+    self.push((value >> 16) as u16);
+  }
+
+  fn push(&mut self, pixel: u16) {
+    if self.line_length < self.line.len() {
+      self.line[self.line_length] = pixel;
+      self.line_length += 1;
+    } else if self.fifo.len() < FIFO_CAPACITY {
+      self.fifo.push_back(pixel);
+    }
+  }
+
+  // whether the dma should keep transferring for the current line
+  pub fn wants_data(&self) -> bool {
+    self.line_length < self.line.len()
+  }
+
+  pub fn start_line(&mut self) {
+    self.line_length = 0;
+
+    while let Some(pixel) = self.fifo.pop_front() {
+      self.push(pixel);
+    }
+  }
+}
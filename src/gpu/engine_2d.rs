@@ -1,3 +1,5 @@
+use std::cmp;
+
 use crate::number::Number;
 
 use super::{
@@ -6,17 +8,23 @@ use super::{
     bg_control_register::BgControlRegister,
     brightness_register::BrightnessRegister,
     color_effects_register::ColorEffectsRegister,
-    display_control_register::DisplayControlRegister,
+    display_control_register::{
+      BgMode,
+      DisplayControlRegister,
+      DisplayControlRegisterFlags
+    },
     master_brightness_register::MasterBrightnessRegister,
+    mosaic_register::MosaicRegister,
     window_horizontal_register::WindowHorizontalRegister,
     window_in_register::WindowInRegister,
     window_out_register::WindowOutRegister,
     window_vertical_register::WindowVerticalRegister
-  }, BgProps, SCREEN_HEIGHT, SCREEN_WIDTH
+  }, layer_debug::LayerDebug, BgProps, SCREEN_HEIGHT, SCREEN_WIDTH
 };
 
 pub mod rendering2d;
 pub mod pixel_processing;
+pub mod viewer;
 
 const COLOR_TRANSPARENT: u16 = 0x8000;
 const ATTRIBUTE_SIZE: usize = 8;
@@ -31,7 +39,7 @@ struct OamAttributes {
   double_sized_flag: bool,
   obj_disable: bool,
   obj_mode: u16,
-  _obj_mosaic: bool,
+  obj_mosaic: bool,
   palette_flag: bool,
   obj_shape: u16,
   obj_size: u16,
@@ -96,13 +104,17 @@ pub struct Engine2d<const IS_ENGINE_B: bool> {
   pub bgcnt: [BgControlRegister; 4],
   pub bgxofs: [u16; 4],
   pub bgyofs: [u16; 4],
+  pub mosaic: MosaicRegister,
   pub bg_props: [BgProps; 2],
   bg_lines: [[Option<Color>; SCREEN_WIDTH as usize]; 4],
   obj_lines: [ObjectPixel; SCREEN_WIDTH as usize],
   pub master_brightness: MasterBrightnessRegister,
   pub palette_ram: [u8; 0x400],
   pub debug_on: bool,
+  pub layer_debug: LayerDebug,
   pub pixel_alphas: [bool; SCREEN_WIDTH as usize],
+  pub gba_mode: bool,
+  pub gba_dispcnt: u16
 }
 
 impl<const IS_ENGINE_B: bool> Engine2d<IS_ENGINE_B> {
@@ -114,6 +126,7 @@ impl<const IS_ENGINE_B: bool> Engine2d<IS_ENGINE_B> {
       pixels: vec![0; 4 * SCREEN_HEIGHT  as usize * SCREEN_WIDTH as usize].into_boxed_slice(),
       bgxofs: [0; 4],
       bgyofs: [0; 4],
+      mosaic: MosaicRegister::new(),
       bg_props: [BgProps::new(); 2],
       winh: [WindowHorizontalRegister::new(); 2],
       winv: [WindowVerticalRegister::new(); 2],
@@ -127,7 +140,10 @@ impl<const IS_ENGINE_B: bool> Engine2d<IS_ENGINE_B> {
       master_brightness: MasterBrightnessRegister::new(),
       palette_ram: [0; 0x400],
       obj_lines: [ObjectPixel::new(); SCREEN_WIDTH as usize],
-      debug_on: false
+      debug_on: false,
+      layer_debug: LayerDebug::new(),
+      gba_mode: false,
+      gba_dispcnt: 0
     }
   }
 
@@ -149,6 +165,27 @@ impl<const IS_ENGINE_B: bool> Engine2d<IS_ENGINE_B> {
     }
   }
 
+  /*
+    the gba's DISPCNT is translated into its DS equivalent so the regular renderer can be reused:
+    the obj mapping bit moves from bit 6 to bit 4, and the display mode is always normal graphics.
+    the frame select bit is kept in the original value.
+   */
+  pub fn write_gba_dispcnt(&mut self, value: u16) {
+    self.gba_dispcnt = value;
+
+    let mut ds_value = cmp::min(value & 0x7, 5) as u32 | (value & 0xff80) as u32 | 1 << 16;
+
+    if value & (1 << 6) != 0 {
+      ds_value |= DisplayControlRegisterFlags::TILE_OBJ_MAPPINGS.bits();
+    }
+
+    self.dispcnt.write(ds_value, None);
+  }
+
+  pub fn gba_bitmap_mode(&self) -> bool {
+    matches!(self.dispcnt.bg_mode, BgMode::Mode3 | BgMode::Mode4 | BgMode::Mode5)
+  }
+
   pub fn read_register(&self, address: u32) -> u16 {
     match address & 0xff {
       0x08 => self.bgcnt[0].bits(),
@@ -185,7 +222,8 @@ impl<const IS_ENGINE_B: bool> Engine2d<IS_ENGINE_B> {
       0x46 => self.winv[1].val,
       0x48 => self.winin.bits(),
       0x4a => self.winout.bits(),
-      0x4c => 0, // TODO
+      // mosaic is write only
+      0x4c..=0x4e => 0,
       0x50 => self.bldcnt.value,
       0x52 => self.bldalpha.read(),
       0x54 => self.bldy.read(),
@@ -259,7 +297,8 @@ impl<const IS_ENGINE_B: bool> Engine2d<IS_ENGINE_B> {
       0x46 => self.winv[1].write(value),
       0x48 => self.winin = WindowInRegister::from_bits_retain(value),
       0x4a => self.winout = WindowOutRegister::from_bits_retain(value),
-      0x4c..=0x4e => (), // TODO
+      0x4c => self.mosaic.write(val, mask.unwrap_or(0)),
+      0x4e => (),
       0x50 => self.bldcnt.write(value),
       0x52 => self.bldalpha.write(value),
       0x54 => self.bldy.write(value),
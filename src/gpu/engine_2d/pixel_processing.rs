@@ -6,7 +6,7 @@ use crate::gpu::{
     display_control_register::DisplayControlRegisterFlags,
     window_in_register::WindowInRegister,
     window_out_register::WindowOutRegister
-  }, SCREEN_WIDTH
+  }, layer_debug::DebugLayer, SCREEN_WIDTH
 };
 
 use super::{Color, Engine2d};
@@ -39,7 +39,7 @@ impl<const IS_ENGINE_B: bool> Engine2d<IS_ENGINE_B> {
     let mut sorted: Vec<usize> = Vec::new();
 
     for i in 0..=3 {
-      if self.bg_mode_enabled(i) {
+      if self.bg_mode_enabled(i) && self.debug_shows_bg(i) {
         sorted.push(i);
       }
     }
@@ -48,7 +48,7 @@ impl<const IS_ENGINE_B: bool> Engine2d<IS_ENGINE_B> {
 
     let mut occupied = [false; SCREEN_WIDTH as usize];
 
-    if self.dispcnt.windows_enabled() {
+    if self.dispcnt.windows_enabled() && self.layer_debug.shows(DebugLayer::Windows) {
       if self.dispcnt.flags.contains(DisplayControlRegisterFlags::DISPLAY_WINDOW0) {
         let mut sorted_window_layers: Vec<usize> = Vec::new();
         if y >= self.winv[0].y1 && y < self.winv[0].y2 {
@@ -126,6 +126,16 @@ impl<const IS_ENGINE_B: bool> Engine2d<IS_ENGINE_B> {
     }
   }
 
+  fn debug_shows_bg(&self, bg_index: usize) -> bool {
+    let layer = if bg_index == 0 && self.is_bg0_3d() {
+      DebugLayer::Bg3d
+    } else {
+      DebugLayer::from_bg(bg_index)
+    };
+
+    self.layer_debug.shows(layer)
+  }
+
   fn display_window_obj(&self, window_type: &WindowType) -> bool {
     match window_type {
       WindowType::Zero => {
@@ -161,7 +171,7 @@ impl<const IS_ENGINE_B: bool> Engine2d<IS_ENGINE_B> {
 
     let obj_layer = Layer::new(4, self.obj_lines[x as usize].priority as usize);
 
-    if self.dispcnt.flags.contains(DisplayControlRegisterFlags::DISPLAY_OBJ) && self.display_window_obj(&window_type) {
+    if self.dispcnt.flags.contains(DisplayControlRegisterFlags::DISPLAY_OBJ) && self.display_window_obj(&window_type) && self.layer_debug.shows(DebugLayer::Obj) {
       if top_layer.is_none() || obj_layer.priority <= top_layer.unwrap().priority {
         bottom_layer = top_layer;
         top_layer = Some(obj_layer);
@@ -197,20 +207,24 @@ impl<const IS_ENGINE_B: bool> Engine2d<IS_ENGINE_B> {
       let top_layer = top_layer.unwrap();
       // do further processing if needed
 
+      let blending = self.layer_debug.shows(DebugLayer::Blending);
+
       if top_layer.index == 4 {
-        if self.obj_lines[x as usize].is_transparent && bottom_layer.is_some() && self.bldcnt.bg_second_pixels[bottom_layer.unwrap().index] {
+        if blending && self.obj_lines[x as usize].is_transparent && bottom_layer.is_some() && self.bldcnt.bg_second_pixels[bottom_layer.unwrap().index] {
           let bottom_layer = bottom_layer.unwrap();
 
           if let Some(color2) = self.bg_lines[bottom_layer.index][x as usize] {
             top_layer_color = self.blend_colors(top_layer_color, color2, self.bldalpha.eva as u16, self.bldalpha.evb as u16);
           }
         }
-      } else if self.bldcnt.bg_first_pixels[top_layer.index] && self.should_apply_effects(&window_type) {
+      } else if blending && self.bldcnt.bg_first_pixels[top_layer.index] && self.should_apply_effects(&window_type) {
         top_layer_color = self.process_pixel(x as usize, top_layer_color, bottom_layer);
       }
 
       // lastly apply master brightness
-      top_layer_color = self.master_brightness.apply_effect(top_layer_color);
+      if self.layer_debug.shows(DebugLayer::MasterBrightness) {
+        top_layer_color = self.master_brightness.apply_effect(top_layer_color);
+      }
 
       self.pixel_alphas[x as usize] = true;
       self.set_pixel(x as usize, y as usize, top_layer_color.convert());
@@ -8,7 +8,7 @@ use crate::gpu::
       DisplayControlRegisterFlags,
       DisplayMode
     }
-  }, vram::VRam, SCREEN_HEIGHT, SCREEN_WIDTH
+  }, display_fifo::DisplayFifo, vram::VRam, GBA_SCREEN_HEIGHT, GBA_SCREEN_WIDTH, SCREEN_HEIGHT, SCREEN_WIDTH
 };
 
 #[derive(PartialEq, Copy, Clone)]
@@ -54,7 +54,7 @@ impl<const IS_ENGINE_B: bool> Engine2d<IS_ENGINE_B> {
     let y_offset = bbox_height / 2;
     let x_offset: i16 = bbox_width as i16 / 2;
 
-    let iy = y as i16 - (y_coordinate + y_offset as i16);
+    let iy = self.obj_mosaic_y(&obj_attributes, y, y_coordinate) - (y_coordinate + y_offset as i16);
 
     for ix in (-x_offset)..(x_offset) {
       let x = x_coordinate + x_offset + ix;
@@ -71,6 +71,8 @@ impl<const IS_ENGINE_B: bool> Engine2d<IS_ENGINE_B> {
         continue;
       }
 
+      let ix = self.obj_mosaic_x(&obj_attributes, x, x_coordinate) - (x_coordinate + x_offset);
+
       let transformed_x = (dx * ix + dmx * iy) >> 8;
       let transformed_y = (dy * ix + dmy * iy) >> 8;
 
@@ -127,7 +129,7 @@ impl<const IS_ENGINE_B: bool> Engine2d<IS_ENGINE_B> {
     }
 
     if self.bg_mode_enabled(0) {
-      if !IS_ENGINE_B && (self.dispcnt.bg_mode == BgMode::Mode6 || self.dispcnt.flags.contains(DisplayControlRegisterFlags::BG_3D_SELECTION)) {
+      if self.is_bg0_3d() {
         self.render_3d_line(y, frame_buffer);
       } else {
         self.render_text_line(0, y, vram);
@@ -212,6 +214,99 @@ impl<const IS_ENGINE_B: bool> Engine2d<IS_ENGINE_B> {
     self.finalize_scanline(y);
   }
 
+  pub fn render_gba_line(&mut self, y: u16, vram: &VRam) {
+    if self.dispcnt.flags.contains(DisplayControlRegisterFlags::DISPLAY_OBJ) {
+      self.render_objects(y, vram);
+    }
+
+    match self.dispcnt.bg_mode {
+      BgMode::Mode0 | BgMode::Mode1 => {
+        for i in 0..2 {
+          if self.bg_mode_enabled(i) {
+            self.render_text_line(i, y, vram);
+          }
+        }
+
+        if self.bg_mode_enabled(2) {
+          if self.dispcnt.bg_mode == BgMode::Mode0 {
+            self.render_text_line(2, y, vram);
+          } else {
+            self.render_affine_line(2, y, vram, AffineType::Normal);
+          }
+        }
+
+        if self.bg_mode_enabled(3) {
+          self.render_text_line(3, y, vram);
+        }
+      }
+      BgMode::Mode2 => {
+        for i in 2..4 {
+          if self.bg_mode_enabled(i) {
+            self.render_affine_line(i, y, vram, AffineType::Normal);
+          }
+        }
+      }
+      _ => {
+        if self.bg_mode_enabled(2) {
+          self.render_gba_bitmap_line(y, vram);
+        }
+      }
+    }
+
+    self.finalize_scanline(y);
+  }
+
+  /*
+    mode 3 is a single 240x160 16bpp frame, mode 4 has two 240x160 8bpp frames and mode 5
+    has two 160x128 16bpp frames. the second frame starts at A000h and is picked with
+    the frame select bit of DISPCNT.
+   */
+  fn render_gba_bitmap_line(&mut self, y: u16, vram: &VRam) {
+    let (width, height) = if self.dispcnt.bg_mode == BgMode::Mode5 {
+      (160, 128)
+    } else {
+      (GBA_SCREEN_WIDTH as i32, GBA_SCREEN_HEIGHT as i32)
+    };
+
+    let frame_base = if self.dispcnt.bg_mode != BgMode::Mode3 && self.gba_dispcnt & (1 << 4) != 0 {
+      0xa000
+    } else {
+      0
+    };
+
+    let (dx, dy) = (self.bg_props[0].dx, self.bg_props[0].dy);
+
+    let (mut ref_x, mut ref_y) = self.bg_mosaic_reference_point(2, y);
+
+    self.bg_props[0].internal_x += self.bg_props[0].dmx as i32;
+    self.bg_props[0].internal_y += self.bg_props[0].dmy as i32;
+
+    for x in 0..GBA_SCREEN_WIDTH as usize {
+      let transformed_x = ref_x >> 8;
+      let transformed_y = ref_y >> 8;
+
+      ref_x += dx as i32;
+      ref_y += dy as i32;
+
+      if transformed_x < 0 || transformed_x >= width || transformed_y < 0 || transformed_y >= height {
+        self.bg_lines[2][x] = None;
+        continue;
+      }
+
+      let index = (transformed_y * width + transformed_x) as u32;
+
+      self.bg_lines[2][x] = if self.dispcnt.bg_mode == BgMode::Mode4 {
+        let palette_index = vram.read_engine_a_bg::<u8>(frame_base + index);
+
+        self.get_bg_palette_color(palette_index as usize, 0)
+      } else {
+        Some(Color::from(vram.read_engine_a_bg::<u16>(frame_base + 2 * index)))
+      };
+    }
+
+    self.apply_bg_mosaic(2);
+  }
+
   fn render_extended_line(&mut self, bg_index: usize, y: u16, vram: &VRam) {
     if self.bgcnt[bg_index].contains(BgControlRegister::PALETTES) {
       if self.bgcnt[bg_index].character_base_block() & 0b1 != 0 {
@@ -265,7 +360,7 @@ impl<const IS_ENGINE_B: bool> Engine2d<IS_ENGINE_B> {
     (dx, dmx, dy, dmy)
   }
 
-  fn get_attributes(&self, i: usize) -> OamAttributes {
+  pub(super) fn get_attributes(&self, i: usize) -> OamAttributes {
     let oam_address = i * ATTRIBUTE_SIZE;
 
     let attribute1 = self.oam_read_16(oam_address);
@@ -301,7 +396,7 @@ impl<const IS_ENGINE_B: bool> Engine2d<IS_ENGINE_B> {
       double_sized_flag,
       obj_disable,
       obj_mode,
-      _obj_mosaic: obj_mosaic,
+      obj_mosaic,
       palette_flag,
       obj_shape,
       x_coordinate,
@@ -316,7 +411,25 @@ impl<const IS_ENGINE_B: bool> Engine2d<IS_ENGINE_B> {
 
   }
 
+  pub fn is_bg0_3d(&self) -> bool {
+    !IS_ENGINE_B && !self.gba_mode && (self.dispcnt.bg_mode == BgMode::Mode6 || self.dispcnt.flags.contains(DisplayControlRegisterFlags::BG_3D_SELECTION))
+  }
+
   pub fn bg_mode_enabled(&self, bg_index: usize) -> bool {
+    // on the gba, which backgrounds exist at all depends on the bg mode
+    if self.gba_mode {
+      let available = match self.dispcnt.bg_mode {
+        BgMode::Mode0 => true,
+        BgMode::Mode1 => bg_index < 3,
+        BgMode::Mode2 => bg_index >= 2,
+        _ => bg_index == 2
+      };
+
+      if !available {
+        return false;
+      }
+    }
+
     match bg_index {
       0 => self.dispcnt.flags.contains(DisplayControlRegisterFlags::DISPLAY_BG0),
       1 => self.dispcnt.flags.contains(DisplayControlRegisterFlags::DISPLAY_BG1),
@@ -341,7 +454,7 @@ impl<const IS_ENGINE_B: bool> Engine2d<IS_ENGINE_B> {
     let (background_width, background_height) = self.bgcnt[bg_index].get_screen_dimensions();
 
     let x_in_bg = (x + x_offset) % background_width;
-    let y_in_bg = (y + y_offset) % background_height;
+    let y_in_bg = (self.bg_mosaic_y(bg_index, y) + y_offset) % background_height;
 
     let mut x_tile_number = (x_in_bg as u32 / 8) % 32;
 
@@ -412,9 +525,11 @@ impl<const IS_ENGINE_B: bool> Engine2d<IS_ENGINE_B> {
         screen_index ^= 1;
       }
     }
+
+    self.apply_bg_mosaic(bg_index);
   }
 
-  fn get_boundary_and_offset(&self, x_pos_in_sprite: u32, y_pos_in_sprite: u32, bit_depth: u32, obj_width: u32) -> (u32, u32) {
+  pub(super) fn get_boundary_and_offset(&self, x_pos_in_sprite: u32, y_pos_in_sprite: u32, bit_depth: u32, obj_width: u32) -> (u32, u32) {
     if !self.dispcnt.flags.contains(DisplayControlRegisterFlags::TILE_OBJ_MAPPINGS) {
       (
         32 as u32,
@@ -447,7 +562,7 @@ impl<const IS_ENGINE_B: bool> Engine2d<IS_ENGINE_B> {
     }
   }
 
-  fn get_bg_extended_palette_color(&self, bg_index: usize, palette_index: usize, palette_bank: usize, vram: &VRam) -> Option<Color> {
+  pub(super) fn get_bg_extended_palette_color(&self, bg_index: usize, palette_index: usize, palette_bank: usize, vram: &VRam) -> Option<Color> {
     let slot = if bg_index < 2 && self.bgcnt[bg_index].contains(BgControlRegister::DISPLAY_AREA_OVERFLOW) {
       bg_index + 2
     } else {
@@ -469,15 +584,15 @@ impl<const IS_ENGINE_B: bool> Engine2d<IS_ENGINE_B> {
     }
   }
 
-  fn get_bg_palette_color(&self, index: usize, palette_bank: usize) -> Option<Color> {
+  pub(super) fn get_bg_palette_color(&self, index: usize, palette_bank: usize) -> Option<Color> {
     self.get_palette_color(index, palette_bank, 0)
   }
 
-  fn get_obj_palette_color(&self, index: usize, palette_bank: usize) -> Option<Color> {
+  pub(super) fn get_obj_palette_color(&self, index: usize, palette_bank: usize) -> Option<Color> {
     self.get_palette_color(index, palette_bank, OBJ_PALETTE_OFFSET)
   }
 
-  fn get_obj_extended_palette(&self, index: u32, palette_bank: u32, vram: &VRam) -> Option<Color> {
+  pub(super) fn get_obj_extended_palette(&self, index: u32, palette_bank: u32, vram: &VRam) -> Option<Color> {
     let address = (palette_bank * 256 + index) * 2;
 
     let color = if !IS_ENGINE_B {
@@ -493,7 +608,7 @@ impl<const IS_ENGINE_B: bool> Engine2d<IS_ENGINE_B> {
     }
   }
 
-  fn get_obj_pixel_index_bpp8(&self, address: u32, tile_x: u16, tile_y: u16, x_flip: bool, y_flip: bool, vram: &VRam) -> u8 {
+  pub(super) fn get_obj_pixel_index_bpp8(&self, address: u32, tile_x: u16, tile_y: u16, x_flip: bool, y_flip: bool, vram: &VRam) -> u8 {
     let tile_x = if x_flip { 7 - tile_x } else { tile_x };
     let tile_y = if y_flip { 7 - tile_y } else { tile_y };
 
@@ -504,7 +619,7 @@ impl<const IS_ENGINE_B: bool> Engine2d<IS_ENGINE_B> {
     }
   }
 
-  fn get_obj_pixel_index_bpp4(&self, address: u32, tile_x: u16, tile_y: u16, x_flip: bool, y_flip: bool, vram: &VRam) -> u8 {
+  pub(super) fn get_obj_pixel_index_bpp4(&self, address: u32, tile_x: u16, tile_y: u16, x_flip: bool, y_flip: bool, vram: &VRam) -> u8 {
     let tile_x = if x_flip { 7 - tile_x } else { tile_x };
     let tile_y = if y_flip { 7 - tile_y } else { tile_y };
 
@@ -523,7 +638,7 @@ impl<const IS_ENGINE_B: bool> Engine2d<IS_ENGINE_B> {
     }
   }
 
-  fn get_bg_pixel_index_bpp8(&self, address: u32, tile_x: u16, tile_y: u16, x_flip: bool, y_flip: bool, vram: &VRam) -> u8 {
+  pub(super) fn get_bg_pixel_index_bpp8(&self, address: u32, tile_x: u16, tile_y: u16, x_flip: bool, y_flip: bool, vram: &VRam) -> u8 {
     let tile_x = if x_flip { 7 - tile_x } else { tile_x };
     let tile_y = if y_flip { 7 - tile_y } else { tile_y };
 
@@ -534,7 +649,7 @@ impl<const IS_ENGINE_B: bool> Engine2d<IS_ENGINE_B> {
     }
   }
 
-  fn get_bg_pixel_index_bpp4(&self, address: u32, tile_x: u16, tile_y: u16, x_flip: bool, y_flip: bool, vram: &VRam) -> u8 {
+  pub(super) fn get_bg_pixel_index_bpp4(&self, address: u32, tile_x: u16, tile_y: u16, x_flip: bool, y_flip: bool, vram: &VRam) -> u8 {
     let tile_x = if x_flip { 7 - tile_x } else { tile_x };
     let tile_y = if y_flip { 7 - tile_y } else { tile_y };
 
@@ -553,14 +668,16 @@ impl<const IS_ENGINE_B: bool> Engine2d<IS_ENGINE_B> {
     }
   }
 
-  fn get_obj_coordinates(&mut self, x: u16, y: u16) -> (i16, i16) {
+  pub(super) fn get_obj_coordinates(&self, x: u16, y: u16) -> (i16, i16) {
     let return_x: i16 = if x >= SCREEN_WIDTH {
       x as i16 - 512
     } else {
       x as i16
     };
 
-    let return_y: i16 = if y >= SCREEN_HEIGHT {
+    let screen_height = if self.gba_mode { GBA_SCREEN_HEIGHT } else { SCREEN_HEIGHT };
+
+    let return_y: i16 = if y >= screen_height {
       y as i16 - 256
     } else {
       y as i16
@@ -569,12 +686,12 @@ impl<const IS_ENGINE_B: bool> Engine2d<IS_ENGINE_B> {
     (return_x, return_y)
   }
 
-  fn render_bitmap_object(&mut self, x: usize, x_pos_in_sprite: u32, y_pos_in_sprite: u32, obj_width: u32, obj_attributes: &OamAttributes, vram: &VRam) {
+  pub(super) fn get_bitmap_object_address(&self, x_pos_in_sprite: u32, y_pos_in_sprite: u32, obj_width: u32, obj_attributes: &OamAttributes) -> Option<u32> {
     // 1d object
     let (tile_base, width) = if self.dispcnt.flags.contains(DisplayControlRegisterFlags::BITMAP_OBJ_MAPPING) {
       // means the object is a square which isnt allowed in 1d mode
       if self.dispcnt.flags.contains(DisplayControlRegisterFlags::BITMAP_OBJ_2D_DIMENSION) {
-        return;
+        return None;
       }
       let boundary = if self.dispcnt.flags.contains(DisplayControlRegisterFlags::BITMAP_OBJ_1D_BOUNDARY) {
         256
@@ -596,7 +713,13 @@ impl<const IS_ENGINE_B: bool> Engine2d<IS_ENGINE_B> {
       ((obj_attributes.tile_number & mask_x) * 0x10 + (obj_attributes.tile_number & !mask_x) * 0x80, width)
     };
 
-    let tile_address = tile_base as u32 + 2 * (x_pos_in_sprite + y_pos_in_sprite * width);
+    Some(tile_base as u32 + 2 * (x_pos_in_sprite + y_pos_in_sprite * width))
+  }
+
+  fn render_bitmap_object(&mut self, x: usize, x_pos_in_sprite: u32, y_pos_in_sprite: u32, obj_width: u32, obj_attributes: &OamAttributes, vram: &VRam) {
+    let Some(tile_address) = self.get_bitmap_object_address(x_pos_in_sprite, y_pos_in_sprite, obj_width, obj_attributes) else {
+      return;
+    };
 
     let color_raw = if !IS_ENGINE_B {
       vram.read_engine_a_obj::<u16>(tile_address)
@@ -629,6 +752,8 @@ impl<const IS_ENGINE_B: bool> Engine2d<IS_ENGINE_B> {
       return;
     }
 
+    let y_pos_in_sprite = self.obj_mosaic_y(&obj_attributes, y, y_coordinate) - y_coordinate;
+
     let tile_number = obj_attributes.tile_number;
 
     let bit_depth = if obj_attributes.palette_flag {
@@ -658,6 +783,8 @@ impl<const IS_ENGINE_B: bool> Engine2d<IS_ENGINE_B> {
         continue;
       }
 
+      let x = (self.obj_mosaic_x(&obj_attributes, screen_x, x_coordinate) - x_coordinate) as u32;
+
       let x_pos_in_sprite = if obj_attributes.horizontal_flip {
         obj_width - x - 1
       } else {
@@ -708,7 +835,7 @@ impl<const IS_ENGINE_B: bool> Engine2d<IS_ENGINE_B> {
     }
   }
 
-  pub fn render_line(&mut self, y: u16, vram: &mut VRam, frame_buffer: &[Pixel3d]) {
+  pub fn render_line(&mut self, y: u16, vram: &mut VRam, frame_buffer: &[Pixel3d], display_fifo: &DisplayFifo) {
     match self.dispcnt.display_mode {
       DisplayMode::Mode0 => {
         let color = Color {
@@ -722,6 +849,7 @@ impl<const IS_ENGINE_B: bool> Engine2d<IS_ENGINE_B> {
           self.set_pixel(x as usize, y as usize, color);
         }
       },
+      DisplayMode::Mode1 if self.gba_mode => self.render_gba_line(y, vram),
       DisplayMode::Mode1 => self.render_normal_line(y, vram, frame_buffer),
       DisplayMode::Mode2 => {
         for x in 0..SCREEN_WIDTH {
@@ -735,7 +863,13 @@ impl<const IS_ENGINE_B: bool> Engine2d<IS_ENGINE_B> {
           self.set_pixel(x as usize, y as usize, color);
         }
       }
-      DisplayMode::Mode3 => todo!()
+      DisplayMode::Mode3 => {
+        for x in 0..SCREEN_WIDTH {
+          let color = Color::to_rgb24(display_fifo.line[x as usize]);
+
+          self.set_pixel(x as usize, y as usize, color);
+        }
+      }
     }
   }
 
@@ -770,7 +904,7 @@ impl<const IS_ENGINE_B: bool> Engine2d<IS_ENGINE_B> {
     tilemap_base + tilemap_number as u32
   }
 
-  fn get_tile_base_addresses(&self, bg_index: usize) -> (u32, u32) {
+  pub(super) fn get_tile_base_addresses(&self, bg_index: usize) -> (u32, u32) {
     if !IS_ENGINE_B {
       (self.bgcnt[bg_index].screen_base_block() as u32 * 0x800 + self.dispcnt.screen_base * 0x1_0000, self.bgcnt[bg_index].character_base_block() as u32 * 0x4000 + self.dispcnt.character_base * 0x1_0000)
     } else {
@@ -789,7 +923,7 @@ impl<const IS_ENGINE_B: bool> Engine2d<IS_ENGINE_B> {
       512 << (self.bgcnt[bg_index].screen_size() & 0b1)
     };
 
-    let (mut ref_x, mut ref_y) = (self.bg_props[bg_index - 2].internal_x, self.bg_props[bg_index - 2].internal_y);
+    let (mut ref_x, mut ref_y) = self.bg_mosaic_reference_point(bg_index, y);
 
     self.bg_props[bg_index - 2].internal_x += self.bg_props[bg_index - 2].dmx as i32;
     self.bg_props[bg_index - 2].internal_y += self.bg_props[bg_index - 2].dmy as i32;
@@ -900,6 +1034,58 @@ impl<const IS_ENGINE_B: bool> Engine2d<IS_ENGINE_B> {
         }
       };
     }
+
+    self.apply_bg_mosaic(bg_index);
+  }
+
+  // the line a mosaic bg is drawn from, which is the first line of the current block
+  fn bg_mosaic_y(&self, bg_index: usize, y: u16) -> u16 {
+    if self.bgcnt[bg_index].contains(BgControlRegister::MOSAIC) {
+      self.mosaic.bg_line.min(y)
+    } else {
+      y
+    }
+  }
+
+  // affine bgs step their reference point every line, so mosaic winds it back to the start of the block
+  fn bg_mosaic_reference_point(&self, bg_index: usize, y: u16) -> (i32, i32) {
+    let bg_props = &self.bg_props[bg_index - 2];
+
+    let lines = (y - self.bg_mosaic_y(bg_index, y)) as i32;
+
+    (bg_props.internal_x - lines * bg_props.dmx as i32, bg_props.internal_y - lines * bg_props.dmy as i32)
+  }
+
+  // every pixel in a block gets the color of the block's leftmost pixel
+  fn apply_bg_mosaic(&mut self, bg_index: usize) {
+    let width = self.mosaic.bg_width() as usize;
+
+    if !self.bgcnt[bg_index].contains(BgControlRegister::MOSAIC) || width == 1 {
+      return;
+    }
+
+    let line = &mut self.bg_lines[bg_index];
+
+    for x in 0..SCREEN_WIDTH as usize {
+      line[x] = line[x - x % width];
+    }
+  }
+
+  // objects sample from the start of the block too, but never from before their own top left corner
+  fn obj_mosaic_y(&self, obj_attributes: &OamAttributes, y: u16, y_coordinate: i16) -> i16 {
+    if obj_attributes.obj_mosaic {
+      (self.mosaic.obj_line.min(y) as i16).max(y_coordinate)
+    } else {
+      y as i16
+    }
+  }
+
+  fn obj_mosaic_x(&self, obj_attributes: &OamAttributes, x: i16, x_coordinate: i16) -> i16 {
+    if obj_attributes.obj_mosaic {
+      (x - x % self.mosaic.obj_width() as i16).max(x_coordinate)
+    } else {
+      x
+    }
   }
 
   pub fn on_end_vblank(&mut self) {
@@ -907,5 +1093,7 @@ impl<const IS_ENGINE_B: bool> Engine2d<IS_ENGINE_B> {
       bg_prop.internal_x = bg_prop.x;
       bg_prop.internal_y = bg_prop.y;
     }
+
+    self.mosaic.on_end_vblank();
   }
 }
\ No newline at end of file
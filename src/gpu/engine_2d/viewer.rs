@@ -0,0 +1,294 @@
+use crate::{
+  gpu::{
+    registers::{
+      bg_control_register::BgControlRegister,
+      display_control_register::{BgMode, DisplayControlRegisterFlags}
+    },
+    viewer::{BgKind, ObjectInfo, ObjectMode, TilemapView, ViewerImage},
+    vram::VRam,
+    GBA_SCREEN_HEIGHT,
+    GBA_SCREEN_WIDTH,
+    SCREEN_HEIGHT,
+    SCREEN_WIDTH
+  },
+  number::Number
+};
+
+use super::{Color, Engine2d, OamAttributes};
+
+impl<const IS_ENGINE_B: bool> Engine2d<IS_ENGINE_B> {
+  // what a bg is in the current mode, going by the same tables render_normal_line and render_gba_line use
+  pub fn bg_kind(&self, bg_index: usize) -> Option<BgKind> {
+    if self.gba_mode {
+      return match (self.dispcnt.bg_mode, bg_index) {
+        (BgMode::Mode0, _) | (BgMode::Mode1, 0 | 1) => Some(BgKind::Text),
+        (BgMode::Mode1, 2) | (BgMode::Mode2, 2 | 3) => Some(BgKind::Affine),
+        (BgMode::Mode3 | BgMode::Mode4 | BgMode::Mode5, 2) => Some(BgKind::GbaBitmap),
+        _ => None
+      };
+    }
+
+    if bg_index == 0 && self.is_bg0_3d() {
+      return Some(BgKind::ThreeD);
+    }
+
+    match (self.dispcnt.bg_mode, bg_index) {
+      (BgMode::Mode6, 2) => Some(BgKind::Large),
+      (BgMode::Mode6, _) => None,
+      (_, 0 | 1) | (BgMode::Mode0, _) | (BgMode::Mode1 | BgMode::Mode3, 2) => Some(BgKind::Text),
+      (BgMode::Mode1, 3) | (BgMode::Mode2, _) | (BgMode::Mode4, 2) => Some(BgKind::Affine),
+      _ => Some(self.extended_bg_kind(bg_index))
+    }
+  }
+
+  fn extended_bg_kind(&self, bg_index: usize) -> BgKind {
+    if !self.bgcnt[bg_index].contains(BgControlRegister::PALETTES) {
+      BgKind::Extended
+    } else if self.bgcnt[bg_index].character_base_block() & 0b1 != 0 {
+      BgKind::ExtendedDirect
+    } else {
+      BgKind::Extended8bpp
+    }
+  }
+
+  // the whole bg plane, or None if the bg doesn't exist in this mode or comes from the 3d engine
+  pub fn bg_tilemap(&self, bg_index: usize, vram: &VRam) -> Option<TilemapView> {
+    let kind = self.bg_kind(bg_index)?;
+
+    let (width, height) = match kind {
+      BgKind::ThreeD => return None,
+      BgKind::Text => {
+        let (width, height) = self.bgcnt[bg_index].get_screen_dimensions();
+
+        (width as usize, height as usize)
+      }
+      BgKind::Large => {
+        let size = 512 << (self.bgcnt[bg_index].screen_size() & 0b1);
+
+        (size, size)
+      }
+      BgKind::GbaBitmap if self.dispcnt.bg_mode == BgMode::Mode5 => (160, 128),
+      BgKind::GbaBitmap => (GBA_SCREEN_WIDTH as usize, GBA_SCREEN_HEIGHT as usize),
+      _ => {
+        let size = 128 << self.bgcnt[bg_index].screen_size();
+
+        (size, size)
+      }
+    };
+
+    let mut image = ViewerImage::new(width, height);
+
+    for y in 0..height as u32 {
+      for x in 0..width as u32 {
+        let color = self.get_viewer_bg_color(kind, bg_index, x, y, width as u32, vram);
+
+        image.set_pixel(x as usize, y as usize, color.map(|mut color| color.convert()));
+      }
+    }
+
+    let (scroll_x, scroll_y) = if kind == BgKind::Text {
+      (self.bgxofs[bg_index] as i32, self.bgyofs[bg_index] as i32)
+    } else {
+      (self.bg_props[bg_index - 2].x >> 8, self.bg_props[bg_index - 2].y >> 8)
+    };
+
+    let (screen_width, screen_height) = if self.gba_mode {
+      (GBA_SCREEN_WIDTH as usize, GBA_SCREEN_HEIGHT as usize)
+    } else {
+      (SCREEN_WIDTH as usize, SCREEN_HEIGHT as usize)
+    };
+
+    Some(TilemapView {
+      kind,
+      image,
+      scroll_x,
+      scroll_y,
+      screen_width,
+      screen_height
+    })
+  }
+
+  fn read_viewer_bg<T: Number>(address: u32, vram: &VRam) -> T {
+    if !IS_ENGINE_B {
+      vram.read_engine_a_bg::<T>(address)
+    } else {
+      vram.read_engine_b_bg::<T>(address)
+    }
+  }
+
+  fn get_viewer_bg_color(&self, kind: BgKind, bg_index: usize, x: u32, y: u32, width: u32, vram: &VRam) -> Option<Color> {
+    let (tilemap_base, tile_base) = self.get_tile_base_addresses(bg_index);
+
+    match kind {
+      BgKind::Text => {
+        let screen_index = match self.bgcnt[bg_index].screen_size() {
+          0 => 0,
+          1 => x / 256,
+          2 => y / 256,
+          3 => (x / 256) + (y / 256) * 2,
+          _ => unreachable!("not possible")
+        };
+
+        let tilemap_number = (x / 8) % 32 + ((y / 8) % 32) * 32;
+
+        let attributes = Self::read_viewer_bg::<u16>(tilemap_base + 0x800 * screen_index + 2 * tilemap_number, vram);
+
+        let is_bpp8 = self.bgcnt[bg_index].contains(BgControlRegister::PALETTES);
+
+        self.get_viewer_tile_color(bg_index, attributes, is_bpp8, x, y, vram)
+      }
+      BgKind::Extended => {
+        let attributes = Self::read_viewer_bg::<u16>(tilemap_base + 2 * ((x / 8) + (y / 8) * (width / 8)), vram);
+
+        self.get_viewer_tile_color(bg_index, attributes, true, x, y, vram)
+      }
+      BgKind::Affine => {
+        let tile_number = Self::read_viewer_bg::<u8>(tilemap_base + (x / 8) + (y / 8) * (width / 8), vram);
+
+        let palette_index = self.get_bg_pixel_index_bpp8(tile_base + tile_number as u32 * 64, (x % 8) as u16, (y % 8) as u16, false, false, vram);
+
+        self.get_bg_palette_color(palette_index as usize, 0)
+      }
+      BgKind::Extended8bpp | BgKind::Large => {
+        let palette_index = Self::read_viewer_bg::<u8>(y * width + x, vram);
+
+        self.get_bg_palette_color(palette_index as usize, 0)
+      }
+      BgKind::ExtendedDirect => {
+        let color_raw = Self::read_viewer_bg::<u16>(2 * (y * width + x), vram);
+
+        if color_raw == 0 {
+          None
+        } else {
+          Some(Color::from(color_raw))
+        }
+      }
+      BgKind::GbaBitmap => {
+        let frame_base = if self.dispcnt.bg_mode != BgMode::Mode3 && self.gba_dispcnt & (1 << 4) != 0 {
+          0xa000
+        } else {
+          0
+        };
+
+        if self.dispcnt.bg_mode == BgMode::Mode4 {
+          let palette_index = Self::read_viewer_bg::<u8>(frame_base + y * width + x, vram);
+
+          self.get_bg_palette_color(palette_index as usize, 0)
+        } else {
+          Some(Color::from(Self::read_viewer_bg::<u16>(frame_base + 2 * (y * width + x), vram)))
+        }
+      }
+      BgKind::ThreeD => None
+    }
+  }
+
+  fn get_viewer_tile_color(&self, bg_index: usize, attributes: u16, is_bpp8: bool, x: u32, y: u32, vram: &VRam) -> Option<Color> {
+    let (_, tile_base) = self.get_tile_base_addresses(bg_index);
+
+    let x_flip = (attributes >> 10) & 0x1 == 1;
+    let y_flip = (attributes >> 11) & 0x1 == 1;
+    let palette_number = (attributes >> 12) & 0xf;
+    let tile_number = attributes & 0x3ff;
+
+    let (tile_x, tile_y) = ((x % 8) as u16, (y % 8) as u16);
+
+    if is_bpp8 {
+      let palette_index = self.get_bg_pixel_index_bpp8(tile_base + tile_number as u32 * 64, tile_x, tile_y, x_flip, y_flip, vram);
+
+      if self.dispcnt.flags.contains(DisplayControlRegisterFlags::BG_EXTENDED_PALETTES) {
+        self.get_bg_extended_palette_color(bg_index, palette_index as usize, palette_number as usize, vram)
+      } else {
+        self.get_bg_palette_color(palette_index as usize, 0)
+      }
+    } else {
+      let palette_index = self.get_bg_pixel_index_bpp4(tile_base + tile_number as u32 * 32, tile_x, tile_y, x_flip, y_flip, vram);
+
+      self.get_bg_palette_color(palette_index as usize, palette_number as usize)
+    }
+  }
+
+  // all 128 oam entries, including the hidden ones
+  pub fn objects(&self, vram: &VRam) -> Vec<ObjectInfo> {
+    (0..128)
+      .map(|i| {
+        let obj_attributes = self.get_attributes(i);
+
+        let (width, height) = obj_attributes.get_object_dimensions();
+        let (x, y) = self.get_obj_coordinates(obj_attributes.x_coordinate, obj_attributes.y_coordinate);
+
+        let mut preview = ViewerImage::new(width as usize, height as usize);
+
+        for y_pos_in_sprite in 0..height {
+          for x_pos_in_sprite in 0..width {
+            let color = self.get_viewer_obj_color(&obj_attributes, x_pos_in_sprite, y_pos_in_sprite, width, vram);
+
+            preview.set_pixel(x_pos_in_sprite as usize, y_pos_in_sprite as usize, color.map(|mut color| color.convert()));
+          }
+        }
+
+        ObjectInfo {
+          index: i,
+          x,
+          y,
+          width,
+          height,
+          affine: obj_attributes.rotation_flag,
+          double_size: obj_attributes.double_sized_flag,
+          hidden: obj_attributes.obj_disable,
+          mode: ObjectMode::new(obj_attributes.obj_mode),
+          mosaic: obj_attributes.obj_mosaic,
+          bpp8: obj_attributes.palette_flag,
+          tile_number: obj_attributes.tile_number,
+          priority: obj_attributes.priority,
+          palette_number: obj_attributes.palette_number,
+          horizontal_flip: obj_attributes.horizontal_flip,
+          vertical_flip: obj_attributes.vertical_flip,
+          affine_group: obj_attributes.rotation_param_selection,
+          preview
+        }
+      })
+      .collect()
+  }
+
+  fn get_viewer_obj_color(&self, obj_attributes: &OamAttributes, x_pos_in_sprite: u32, y_pos_in_sprite: u32, obj_width: u32, vram: &VRam) -> Option<Color> {
+    if obj_attributes.obj_mode == 3 {
+      let address = self.get_bitmap_object_address(x_pos_in_sprite, y_pos_in_sprite, obj_width, obj_attributes)?;
+
+      let color_raw = if !IS_ENGINE_B {
+        vram.read_engine_a_obj::<u16>(address)
+      } else {
+        vram.read_engine_b_obj::<u16>(address)
+      };
+
+      return if color_raw == 0 {
+        None
+      } else {
+        Some(Color::from(color_raw))
+      };
+    }
+
+    let bit_depth = if obj_attributes.palette_flag { 8 } else { 4 };
+
+    let (boundary, offset) = self.get_boundary_and_offset(x_pos_in_sprite, y_pos_in_sprite, bit_depth, obj_width);
+
+    let tile_address = obj_attributes.tile_number as u32 * boundary + offset * bit_depth * 8;
+
+    let (tile_x, tile_y) = ((x_pos_in_sprite % 8) as u16, (y_pos_in_sprite % 8) as u16);
+
+    let palette_index = if bit_depth == 8 {
+      self.get_obj_pixel_index_bpp8(tile_address, tile_x, tile_y, false, false, vram)
+    } else {
+      self.get_obj_pixel_index_bpp4(tile_address, tile_x, tile_y, false, false, vram)
+    };
+
+    if palette_index == 0 {
+      None
+    } else if bit_depth == 8 && self.dispcnt.flags.contains(DisplayControlRegisterFlags::OBJ_EXTENDED_PALETTES) {
+      self.get_obj_extended_palette(palette_index as u32, obj_attributes.palette_number as u32, vram)
+    } else if bit_depth == 8 {
+      self.get_obj_palette_color(palette_index as usize, 0)
+    } else {
+      self.get_obj_palette_color(palette_index as usize, obj_attributes.palette_number as usize)
+    }
+  }
+}
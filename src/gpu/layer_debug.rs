@@ -0,0 +1,109 @@
+/*
+  Debug switches for hiding parts of the picture, so a rendering bug can be narrowed down to the layer
+  causing it. None of this touches the emulated registers, it only changes what gets composed.
+ */
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum GpuEngine {
+  A,
+  B
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum DebugLayer {
+  Bg0 = 0,
+  Bg1 = 1,
+  Bg2 = 2,
+  Bg3 = 3,
+  Obj = 4,
+  Bg3d = 5,
+  Windows = 6,
+  Blending = 7,
+  MasterBrightness = 8
+}
+
+impl DebugLayer {
+  pub const ALL: [DebugLayer; 9] = [
+    DebugLayer::Bg0,
+    DebugLayer::Bg1,
+    DebugLayer::Bg2,
+    DebugLayer::Bg3,
+    DebugLayer::Obj,
+    DebugLayer::Bg3d,
+    DebugLayer::Windows,
+    DebugLayer::Blending,
+    DebugLayer::MasterBrightness
+  ];
+
+  pub fn from_bg(bg_index: usize) -> Self {
+    match bg_index {
+      0 => DebugLayer::Bg0,
+      1 => DebugLayer::Bg1,
+      2 => DebugLayer::Bg2,
+      3 => DebugLayer::Bg3,
+      _ => unreachable!()
+    }
+  }
+
+  pub fn name(&self) -> &'static str {
+    match self {
+      DebugLayer::Bg0 => "BG0",
+      DebugLayer::Bg1 => "BG1",
+      DebugLayer::Bg2 => "BG2",
+      DebugLayer::Bg3 => "BG3",
+      DebugLayer::Obj => "OBJ",
+      DebugLayer::Bg3d => "3D",
+      DebugLayer::Windows => "Windows",
+      DebugLayer::Blending => "Blending",
+      DebugLayer::MasterBrightness => "Master brightness"
+    }
+  }
+
+  // windows, blending and master brightness are effects rather than something that can be drawn on its own
+  pub fn can_solo(&self) -> bool {
+    (*self as usize) <= DebugLayer::Bg3d as usize
+  }
+}
+
+#[derive(Copy, Clone, Default)]
+pub struct LayerDebug {
+  disabled: [bool; 9],
+  solo: Option<DebugLayer>
+}
+
+impl LayerDebug {
+  pub fn new() -> Self {
+    Self {
+      disabled: [false; 9],
+      solo: None
+    }
+  }
+
+  pub fn is_disabled(&self, layer: DebugLayer) -> bool {
+    self.disabled[layer as usize]
+  }
+
+  pub fn set_disabled(&mut self, layer: DebugLayer, disabled: bool) {
+    self.disabled[layer as usize] = disabled;
+  }
+
+  pub fn solo(&self) -> Option<DebugLayer> {
+    self.solo
+  }
+
+  pub fn set_solo(&mut self, layer: Option<DebugLayer>) {
+    self.solo = layer.filter(|layer| layer.can_solo());
+  }
+
+  // soloing a layer hides the other layers but leaves the effects alone
+  pub fn shows(&self, layer: DebugLayer) -> bool {
+    if self.is_disabled(layer) {
+      return false;
+    }
+
+    match self.solo {
+      Some(solo) if layer.can_solo() => solo == layer,
+      _ => true
+    }
+  }
+}
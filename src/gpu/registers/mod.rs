@@ -15,4 +15,5 @@ pub mod color_effects_register;
 pub mod display_capture_control_register;
 pub mod display_3d_control_register;
 pub mod geometry_status_register;
-pub mod clear_color_register;
\ No newline at end of file
+pub mod clear_color_register;
+pub mod mosaic_register;
\ No newline at end of file
@@ -0,0 +1,71 @@
+/*
+  MOSAIC is write only. Sizes are stored as written, so 0 means blocks of 1 pixel.
+
+  horizontally, blocks line up with the left edge of the screen. vertically, each of bg and obj has
+  a 4 bit counter that's bumped at the end of every line. once the counter reaches the block size
+  it starts over and the next line becomes the one that's repeated. the counters restart at line 0.
+ */
+#[derive(Copy, Clone, Default)]
+pub struct MosaicRegister {
+  pub val: u16,
+  pub bg_line: u16,
+  pub obj_line: u16,
+  bg_counter: u16,
+  obj_counter: u16
+}
+
+impl MosaicRegister {
+  pub fn new() -> Self {
+    Self {
+      val: 0,
+      bg_line: 0,
+      obj_line: 0,
+      bg_counter: 0,
+      obj_counter: 0
+    }
+  }
+
+  // byte writes only touch their own half, since there's no reading the old value back
+  pub fn write(&mut self, val: u16, mask: u16) {
+    self.val = (self.val & mask) | val;
+  }
+
+  pub fn bg_width(&self) -> u16 {
+    (self.val & 0xf) + 1
+  }
+
+  pub fn bg_height(&self) -> u16 {
+    ((self.val >> 4) & 0xf) + 1
+  }
+
+  pub fn obj_width(&self) -> u16 {
+    ((self.val >> 8) & 0xf) + 1
+  }
+
+  pub fn obj_height(&self) -> u16 {
+    ((self.val >> 12) & 0xf) + 1
+  }
+
+  pub fn on_end_line(&mut self, line: u16) {
+    if self.bg_counter + 1 >= self.bg_height() {
+      self.bg_counter = 0;
+      self.bg_line = line + 1;
+    } else {
+      self.bg_counter = (self.bg_counter + 1) & 0xf;
+    }
+
+    if self.obj_counter + 1 >= self.obj_height() {
+      self.obj_counter = 0;
+      self.obj_line = line + 1;
+    } else {
+      self.obj_counter = (self.obj_counter + 1) & 0xf;
+    }
+  }
+
+  pub fn on_end_vblank(&mut self) {
+    self.bg_line = 0;
+    self.obj_line = 0;
+    self.bg_counter = 0;
+    self.obj_counter = 0;
+  }
+}
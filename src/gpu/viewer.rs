@@ -0,0 +1,291 @@
+/*
+  Decoders for looking at vram, palettes and oam from a debugger. Nothing here touches emulated
+  state, it all just reads it and hands back plain rgba8 images so any frontend can show them.
+ */
+use super::{
+  color::Color,
+  layer_debug::GpuEngine,
+  vram::{Bank, BANK_SIZES},
+  GPU
+};
+
+pub const TILES_PER_ROW: usize = 32;
+
+const OBJ_PALETTE_OFFSET: usize = 0x200;
+
+pub struct ViewerImage {
+  pub width: usize,
+  pub height: usize,
+  pub pixels: Vec<u8>
+}
+
+impl ViewerImage {
+  pub fn new(width: usize, height: usize) -> Self {
+    Self {
+      width,
+      height,
+      pixels: vec![0; 4 * width * height]
+    }
+  }
+
+  // colors are expected to already be 8 bits per channel, None stays transparent
+  pub fn set_pixel(&mut self, x: usize, y: usize, color: Option<Color>) {
+    if let Some(color) = color {
+      let i = 4 * (x + y * self.width);
+
+      self.pixels[i..i + 4].copy_from_slice(&[color.r, color.g, color.b, 0xff]);
+    }
+  }
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum TilePalette {
+  Grayscale,
+  Bg(usize),
+  Obj(usize)
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum BgKind {
+  Text,
+  Affine,
+  Extended,
+  Extended8bpp,
+  ExtendedDirect,
+  Large,
+  GbaBitmap,
+  ThreeD
+}
+
+impl BgKind {
+  pub fn name(&self) -> &'static str {
+    match self {
+      BgKind::Text => "Text",
+      BgKind::Affine => "Affine",
+      BgKind::Extended => "Extended (16 bit tilemap)",
+      BgKind::Extended8bpp => "Extended (256 color bitmap)",
+      BgKind::ExtendedDirect => "Extended (direct color bitmap)",
+      BgKind::Large => "Large bitmap",
+      BgKind::GbaBitmap => "GBA bitmap",
+      BgKind::ThreeD => "3D"
+    }
+  }
+}
+
+/*
+  the whole bg plane in bg coordinates. the scroll rectangle is where the screen sits in it. for
+  affine bgs that's only the reference point, the rectangle doesn't show rotation or scaling.
+ */
+pub struct TilemapView {
+  pub kind: BgKind,
+  pub image: ViewerImage,
+  pub scroll_x: i32,
+  pub scroll_y: i32,
+  pub screen_width: usize,
+  pub screen_height: usize
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum ObjectMode {
+  Normal,
+  SemiTransparent,
+  Window,
+  Bitmap
+}
+
+impl ObjectMode {
+  pub fn new(value: u16) -> Self {
+    match value {
+      0 => ObjectMode::Normal,
+      1 => ObjectMode::SemiTransparent,
+      2 => ObjectMode::Window,
+      3 => ObjectMode::Bitmap,
+      _ => unreachable!()
+    }
+  }
+
+  pub fn name(&self) -> &'static str {
+    match self {
+      ObjectMode::Normal => "Normal",
+      ObjectMode::SemiTransparent => "Semi-transparent",
+      ObjectMode::Window => "Window",
+      ObjectMode::Bitmap => "Bitmap"
+    }
+  }
+}
+
+// an oam entry with its attributes decoded. the preview is the sprite as stored, without flips or affine
+pub struct ObjectInfo {
+  pub index: usize,
+  pub x: i16,
+  pub y: i16,
+  pub width: u32,
+  pub height: u32,
+  pub affine: bool,
+  pub double_size: bool,
+  pub hidden: bool,
+  pub mode: ObjectMode,
+  pub mosaic: bool,
+  pub bpp8: bool,
+  pub tile_number: u16,
+  pub priority: u16,
+  pub palette_number: u16,
+  pub horizontal_flip: bool,
+  pub vertical_flip: bool,
+  pub affine_group: u16,
+  pub preview: ViewerImage
+}
+
+pub struct BankMapping {
+  pub bank: Bank,
+  pub enabled: bool,
+  pub mst: u8,
+  pub offset: u8,
+  pub size: usize,
+  pub destination: String
+}
+
+impl GPU {
+  // every tile in a bank, read straight out of the bank so it doesn't matter where it's mapped
+  pub fn bank_tiles(&self, bank: Bank, bpp8: bool, engine: GpuEngine, palette: TilePalette) -> ViewerImage {
+    let data = &self.vram.banks[bank as usize];
+
+    let tile_size = if bpp8 { 64 } else { 32 };
+    let num_tiles = data.len() / tile_size;
+
+    let mut image = ViewerImage::new(TILES_PER_ROW * 8, (num_tiles / TILES_PER_ROW) * 8);
+
+    for tile in 0..num_tiles {
+      let tile_address = tile * tile_size;
+
+      let (tile_x, tile_y) = ((tile % TILES_PER_ROW) * 8, (tile / TILES_PER_ROW) * 8);
+
+      for y in 0..8 {
+        for x in 0..8 {
+          let index = if bpp8 {
+            data[tile_address + x + y * 8] as usize
+          } else {
+            let byte = data[tile_address + x / 2 + y * 4];
+
+            (if x & 0b1 == 1 { byte >> 4 } else { byte & 0xf }) as usize
+          };
+
+          image.set_pixel(tile_x + x, tile_y + y, self.viewer_palette_color(index, bpp8, engine, palette));
+        }
+      }
+    }
+
+    image
+  }
+
+  fn viewer_palette_color(&self, index: usize, bpp8: bool, engine: GpuEngine, palette: TilePalette) -> Option<Color> {
+    if index == 0 {
+      return None;
+    }
+
+    let (palette_bank, offset) = match palette {
+      TilePalette::Grayscale => {
+        let value = if bpp8 { index as u8 } else { index as u8 * 0x11 };
+
+        return Some(Color { r: value, g: value, b: value, alpha: None });
+      }
+      TilePalette::Bg(palette_bank) => (palette_bank, 0),
+      TilePalette::Obj(palette_bank) => (palette_bank, OBJ_PALETTE_OFFSET)
+    };
+
+    let palette_bank = if bpp8 { 0 } else { palette_bank & 0xf };
+
+    Some(self.viewer_palette_ram_color(engine, offset + 2 * index + 32 * palette_bank))
+  }
+
+  fn viewer_palette_ram_color(&self, engine: GpuEngine, address: usize) -> Color {
+    let palette_ram = match engine {
+      GpuEngine::A => &self.engine_a.palette_ram,
+      GpuEngine::B => &self.engine_b.palette_ram
+    };
+
+    Color::to_rgb24(palette_ram[address] as u16 | (palette_ram[address + 1] as u16) << 8)
+  }
+
+  // the bg or obj palette as a 16x16 image, one pixel per color
+  pub fn palette_image(&self, engine: GpuEngine, obj: bool) -> ViewerImage {
+    let offset = if obj { OBJ_PALETTE_OFFSET } else { 0 };
+
+    let mut image = ViewerImage::new(16, 16);
+
+    for i in 0..256 {
+      image.set_pixel(i % 16, i / 16, Some(self.viewer_palette_ram_color(engine, offset + 2 * i)));
+    }
+
+    image
+  }
+
+  pub fn bg_tilemap(&self, engine: GpuEngine, bg_index: usize) -> Option<TilemapView> {
+    match engine {
+      GpuEngine::A => self.engine_a.bg_tilemap(bg_index, &self.vram),
+      GpuEngine::B => self.engine_b.bg_tilemap(bg_index, &self.vram)
+    }
+  }
+
+  pub fn objects(&self, engine: GpuEngine) -> Vec<ObjectInfo> {
+    match engine {
+      GpuEngine::A => self.engine_a.objects(&self.vram),
+      GpuEngine::B => self.engine_b.objects(&self.vram)
+    }
+  }
+
+  pub fn bank_mappings(&self) -> Vec<BankMapping> {
+    self.vramcnt
+      .iter()
+      .enumerate()
+      .map(|(i, vramcnt)| {
+        let bank = Bank::new(i);
+
+        BankMapping {
+          bank,
+          enabled: vramcnt.vram_enable,
+          mst: vramcnt.vram_mst,
+          offset: vramcnt.vram_offset,
+          size: BANK_SIZES[i],
+          destination: Self::describe_mapping(bank, vramcnt.vram_mst, vramcnt.vram_offset as u32)
+        }
+      })
+      .collect()
+  }
+
+  // where a bank ends up for a given mst and offset, following gbatek's vramcnt table
+  fn describe_mapping(bank: Bank, mst: u8, offset: u32) -> String {
+    use Bank::*;
+
+    let lcdc_addresses = [0x680_0000, 0x682_0000, 0x684_0000, 0x686_0000, 0x688_0000, 0x689_0000, 0x689_4000, 0x689_8000, 0x68a_0000];
+
+    // offset for F and G, which are small enough to be placed at 16k steps
+    let small_offset = 0x4000 * (offset & 0b1) + 0x1_0000 * ((offset >> 1) & 0b1);
+
+    match (mst, bank) {
+      (0, _) => format!("LCDC at {:x}", lcdc_addresses[bank as usize]),
+      (1, BankA | BankB | BankC | BankD) => format!("Engine A BG at {:x}", 0x600_0000 + 0x2_0000 * offset),
+      (1, BankE) => "Engine A BG at 6000000".to_string(),
+      (1, BankF | BankG) => format!("Engine A BG at {:x}", 0x600_0000 + small_offset),
+      (1, BankH) => "Engine B BG at 6200000".to_string(),
+      (1, BankI) => "Engine B BG at 6208000".to_string(),
+      (2, BankA | BankB) => format!("Engine A OBJ at {:x}", 0x640_0000 + 0x2_0000 * (offset & 0b1)),
+      (2, BankC | BankD) => format!("ARM7 WRAM at {:x}", 0x600_0000 + 0x2_0000 * (offset & 0b1)),
+      (2, BankE) => "Engine A OBJ at 6400000".to_string(),
+      (2, BankF | BankG) => format!("Engine A OBJ at {:x}", 0x640_0000 + small_offset),
+      (2, BankH) => "Engine B BG extended palette slots 0-3".to_string(),
+      (2, BankI) => "Engine B OBJ at 6600000".to_string(),
+      (3, BankA | BankB | BankC | BankD) => format!("Texture slot {}", offset),
+      (3, BankE) => "Texture palette slots 0-3".to_string(),
+      (3, BankF | BankG) => format!("Texture palette slot {}", (offset & 0b1) + ((offset >> 1) & 0b1) * 4),
+      (3, BankI) => "Engine B OBJ extended palette".to_string(),
+      (4, BankC) => "Engine B BG at 6200000".to_string(),
+      (4, BankD) => "Engine B OBJ at 6600000".to_string(),
+      (4, BankE) => "Engine A BG extended palette slots 0-3".to_string(),
+      (4, BankF | BankG) if offset & 0b1 == 0 => "Engine A BG extended palette slots 0-1".to_string(),
+      (4, BankF | BankG) => "Engine A BG extended palette slots 2-3".to_string(),
+      (5, BankF | BankG) => "Engine A OBJ extended palette".to_string(),
+      _ => "Invalid".to_string()
+    }
+  }
+}
@@ -7,4 +7,7 @@ pub mod util;
 pub mod gpu;
 pub mod scheduler;
 pub mod apu;
+pub mod mic;
+pub mod elf;
+pub mod local_wireless;
 pub mod number;
\ No newline at end of file
@@ -0,0 +1,114 @@
+use crate::{
+  cpu::bus::wifi::WIFI_TICK_CYCLES,
+  nds::Nds,
+  util
+};
+
+/*
+  A pretend patch of air shared by consoles running in the same process. Each console runs a slice
+  of cycles in turn, and whatever frames went out during a slice are heard by everyone else before
+  the next one starts. The consoles never drift more than a slice apart, and running the same inputs
+  always gives the same result.
+ */
+
+// one slice per wifi tick keeps the link latency at a millisecond
+pub const SLICE_CYCLES: usize = WIFI_TICK_CYCLES;
+
+const FIRMWARE_MAC_ADDRESS: usize = 0x36;
+const FIRMWARE_WIFI_CRC: usize = 0x2a;
+const FIRMWARE_WIFI_LENGTH: usize = 0x2c;
+
+#[derive(Default)]
+pub struct LocalWireless {
+  in_flight: Vec<(usize, Vec<u8>)>
+}
+
+impl LocalWireless {
+  pub fn new() -> Self {
+    Self {
+      in_flight: Vec::new()
+    }
+  }
+
+  // runs every console until each one has finished a frame. a console that finishes early waits for the others
+  pub fn run_frame(&mut self, consoles: &mut [&mut Nds]) {
+    let frame_starts: Vec<usize> = consoles.iter().map(|nds| nds.arm7_cpu.cycles).collect();
+    let mut finished = vec![false; consoles.len()];
+
+    while finished.iter().any(|finished| !finished) {
+      self.deliver_frames(consoles);
+
+      for (i, nds) in consoles.iter_mut().enumerate() {
+        if finished[i] {
+          continue;
+        }
+
+        let slice_end = nds.bus.borrow().scheduler.cycles + SLICE_CYCLES;
+
+        while nds.bus.borrow().scheduler.cycles < slice_end {
+          finished[i] = nds.step();
+          nds.bus.borrow_mut().frame_cycles = nds.arm7_cpu.cycles - frame_starts[i];
+
+          if finished[i] {
+            break;
+          }
+        }
+      }
+
+      self.collect_frames(consoles);
+    }
+  }
+
+  fn collect_frames(&mut self, consoles: &mut [&mut Nds]) {
+    for (i, nds) in consoles.iter_mut().enumerate() {
+      let bus = &mut *nds.bus.borrow_mut();
+
+      while let Some(frame) = bus.arm7.wifi.outgoing.pop_front() {
+        self.in_flight.push((i, frame));
+      }
+    }
+  }
+
+  // everybody except the sender hears the frame
+  fn deliver_frames(&mut self, consoles: &mut [&mut Nds]) {
+    for (sender, frame) in self.in_flight.drain(..) {
+      for (i, nds) in consoles.iter_mut().enumerate() {
+        if i == sender {
+          continue;
+        }
+
+        let bus = &mut *nds.bus.borrow_mut();
+
+        bus.arm7.wifi.receive_frame(&frame);
+        bus.check_wifi_interrupt();
+      }
+    }
+  }
+}
+
+pub fn firmware_mac_address(firmware: &[u8]) -> [u8; 6] {
+  let mut mac_address = [0; 6];
+
+  if firmware.len() >= FIRMWARE_MAC_ADDRESS + 6 {
+    mac_address.copy_from_slice(&firmware[FIRMWARE_MAC_ADDRESS..FIRMWARE_MAC_ADDRESS + 6]);
+  }
+
+  mac_address
+}
+
+// every console on the link needs its own mac address, so extra consoles get a patched copy of the firmware
+pub fn set_firmware_mac_address(firmware: &mut [u8], mac_address: [u8; 6]) {
+  if firmware.len() < FIRMWARE_MAC_ADDRESS + mac_address.len() {
+    return;
+  }
+
+  firmware[FIRMWARE_MAC_ADDRESS..FIRMWARE_MAC_ADDRESS + mac_address.len()].copy_from_slice(&mac_address);
+
+  let length = (firmware[FIRMWARE_WIFI_LENGTH] as usize | (firmware[FIRMWARE_WIFI_LENGTH + 1] as usize) << 8)
+    .min(firmware.len() - FIRMWARE_WIFI_LENGTH);
+
+  let crc = util::crc16(0, &firmware[FIRMWARE_WIFI_LENGTH..FIRMWARE_WIFI_LENGTH + length]);
+
+  firmware[FIRMWARE_WIFI_CRC] = crc as u8;
+  firmware[FIRMWARE_WIFI_CRC + 1] = (crc >> 8) as u8;
+}
@@ -0,0 +1,205 @@
+use std::{
+  fs,
+  path::Path,
+  sync::{
+    Arc,
+    Mutex
+  }
+};
+
+/*
+  Anything that can feed the touchscreen controller's mic channel. Every frame the active source
+  fills a frame's worth of samples, which the aux channel then reads from based on where in the
+  frame the conversion happens.
+ */
+
+// the mic buffer holds one frame of samples at this rate
+pub const MIC_SAMPLE_RATE: u32 = 44100;
+
+const BLOW_AMPLITUDE: i32 = 0x6000;
+
+pub trait MicSource {
+  fn fill(&mut self, buffer: &mut [i16]);
+}
+
+// copies the next chunk out of a ring buffer of samples, wrapping around to the start when it runs out
+pub fn copy_from_ring(samples: &[i16], read_pos: &mut usize, buffer: &mut [i16]) {
+  if samples.is_empty() {
+    buffer.fill(0);
+    return;
+  }
+
+  for sample in buffer.iter_mut() {
+    if *read_pos >= samples.len() {
+      *read_pos = 0;
+    }
+
+    *sample = samples[*read_pos];
+    *read_pos += 1;
+  }
+}
+
+// host audio written in by the frontend's capture device
+pub struct LiveMic {
+  samples: Arc<Mutex<[i16; 2048]>>,
+  read_pos: usize
+}
+
+impl LiveMic {
+  pub fn new(samples: Arc<Mutex<[i16; 2048]>>) -> Self {
+    Self {
+      samples,
+      read_pos: 0
+    }
+  }
+}
+
+impl MicSource for LiveMic {
+  fn fill(&mut self, buffer: &mut [i16]) {
+    let samples = self.samples.lock().unwrap();
+
+    copy_from_ring(&*samples, &mut self.read_pos, buffer);
+  }
+}
+
+pub struct SilentMic;
+
+impl MicSource for SilentMic {
+  fn fill(&mut self, buffer: &mut [i16]) {
+    buffer.fill(0);
+  }
+}
+
+// loops a wav file forever, mixed down to mono and resampled to the mic's sample rate
+pub struct WavMic {
+  samples: Vec<i16>,
+  read_pos: usize
+}
+
+impl WavMic {
+  pub fn open(path: &Path) -> Option<Self> {
+    match fs::read(path) {
+      Ok(bytes) => Self::from_bytes(&bytes),
+      Err(_) => None
+    }
+  }
+
+  // only uncompressed 8 and 16 bit pcm is supported
+  pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+    if bytes.len() < 12 || &bytes[0..4] != b"RIFF" || &bytes[8..12] != b"WAVE" {
+      return None;
+    }
+
+    let read_16 = |address: usize| bytes[address] as u16 | (bytes[address + 1] as u16) << 8;
+    let read_32 = |address: usize| read_16(address) as u32 | (read_16(address + 2) as u32) << 16;
+
+    let mut format = None;
+    let mut data = None;
+
+    let mut offset = 12;
+
+    while offset + 8 <= bytes.len() {
+      let chunk_id = &bytes[offset..offset + 4];
+      let chunk_size = read_32(offset + 4) as usize;
+
+      let start = offset + 8;
+      let end = (start + chunk_size).min(bytes.len());
+
+      match chunk_id {
+        b"fmt " if chunk_size >= 16 && end >= start + 16 => {
+          // format, channels, sample rate, bits per sample
+          format = Some((read_16(start), read_16(start + 2) as usize, read_32(start + 4), read_16(start + 14)));
+        }
+        b"data" => data = Some(&bytes[start..end]),
+        _ => ()
+      }
+
+      // chunks are padded to an even size
+      offset = start + chunk_size + (chunk_size & 0b1);
+    }
+
+    let (audio_format, channels, sample_rate, bits_per_sample) = format?;
+    let data = data?;
+
+    if audio_format != 1 || channels == 0 || sample_rate == 0 {
+      return None;
+    }
+
+    let frames: Vec<i16> = match bits_per_sample {
+      8 => data
+        .chunks_exact(channels)
+        .map(|frame| {
+          let sum: i32 = frame.iter().map(|sample| (*sample as i32 - 0x80) << 8).sum();
+
+          (sum / channels as i32) as i16
+        })
+        .collect(),
+      16 => data
+        .chunks_exact(channels * 2)
+        .map(|frame| {
+          let sum: i32 = frame.chunks_exact(2).map(|sample| i16::from_le_bytes([sample[0], sample[1]]) as i32).sum();
+
+          (sum / channels as i32) as i16
+        })
+        .collect(),
+      _ => return None
+    };
+
+    if frames.is_empty() {
+      return None;
+    }
+
+    let length = (frames.len() as u64 * MIC_SAMPLE_RATE as u64 / sample_rate as u64).max(1) as usize;
+
+    let samples = (0..length)
+      .map(|i| frames[((i as u64 * sample_rate as u64 / MIC_SAMPLE_RATE as u64) as usize).min(frames.len() - 1)])
+      .collect();
+
+    Some(Self {
+      samples,
+      read_pos: 0
+    })
+  }
+}
+
+impl MicSource for WavMic {
+  fn fill(&mut self, buffer: &mut [i16]) {
+    copy_from_ring(&self.samples, &mut self.read_pos, buffer);
+  }
+}
+
+// blowing into the mic shows up as loud noise, which is all mic puzzles look for
+pub struct BlowMic {
+  seed: u32
+}
+
+// the xorshift seed can't be zero, so this can't just be derived
+impl Default for BlowMic {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl BlowMic {
+  pub fn new() -> Self {
+    Self {
+      seed: 0x1234_5678
+    }
+  }
+
+  fn next_noise(&mut self) -> i32 {
+    self.seed ^= self.seed << 13;
+    self.seed ^= self.seed >> 17;
+    self.seed ^= self.seed << 5;
+
+    (self.seed >> 16) as i32 - 0x8000
+  }
+}
+
+impl MicSource for BlowMic {
+  fn fill(&mut self, buffer: &mut [i16]) {
+    for sample in buffer.iter_mut() {
+      *sample = (self.next_noise() * BLOW_AMPLITUDE / 0x8000) as i16;
+    }
+  }
+}
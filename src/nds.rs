@@ -1,5 +1,5 @@
 use std::{
-  cell::RefCell, collections::VecDeque, fs, path::PathBuf, rc::Rc, sync::{
+  cell::RefCell, collections::VecDeque, fs, path::{Path, PathBuf}, rc::Rc, sync::{
     Arc,
     Mutex
   }
@@ -7,17 +7,26 @@ use std::{
 
 use crate::{
   cpu::{
-    bus::{cartridge::Header, Bus},
+    bus::{debug_output::DebugCallback, dldi::SdCard, gba::GBA_BIOS_SIZE, slot2::RumbleCallback, wifi::WIFI_TICK_CYCLES, Bus, HaltMode},
+    registers::real_time_clock_register::CYCLES_PER_TICK,
     CPU
   },
+  elf::{self, ElfFile, SymbolTable},
+  mic::{BlowMic, LiveMic, MicSource},
   scheduler::EventType
 };
 
+// one frame, 263 lines of 2130 cycles
+const SLEEP_STEP_CYCLES: usize = 560_190;
+
 pub struct Nds {
   pub arm9_cpu: CPU<true>,
   pub arm7_cpu: CPU<false>,
   pub bus: Rc<RefCell<Bus>>,
-  pub mic_samples: Arc<Mutex<[i16; 2048]>>
+  pub mic_samples: Arc<Mutex<[i16; 2048]>>,
+  pub mic_blowing: bool,
+  mic_source: Box<dyn MicSource>,
+  blow_source: BlowMic
 }
 
 impl Nds {
@@ -44,7 +53,10 @@ impl Nds {
       arm9_cpu: CPU::new(bus.clone()),
       arm7_cpu: CPU::new(bus.clone()),
       bus,
-      mic_samples
+      mic_source: Box::new(LiveMic::new(mic_samples.clone())),
+      mic_samples,
+      mic_blowing: false,
+      blow_source: BlowMic::new()
     };
 
     nds.arm7_cpu.reload_pipeline32();
@@ -57,8 +69,8 @@ impl Nds {
     {
       let ref mut bus = *self.bus.borrow_mut();
 
-      bus.cartridge.header = Header::from(&rom);
-      bus.cartridge.rom = rom.clone();
+      bus.cartridge.load_rom(rom);
+      bus.patch_dldi();
     }
 
     if skip_bios {
@@ -71,6 +83,154 @@ impl Nds {
     }
   }
 
+  // boots homebrew straight from the elf files the toolchain builds, without packing them into a rom with ndstool first
+  pub fn init_elf(&mut self, arm9_elf: &[u8], arm7_elf: Option<&[u8]>) -> bool {
+    let Some((rom, arm9_symbols, arm7_symbols)) = Self::build_elf_rom(arm9_elf, arm7_elf) else {
+      return false;
+    };
+
+    self.init(&rom, true);
+    self.set_symbols(arm9_symbols, arm7_symbols);
+
+    true
+  }
+
+  pub fn reset_elf(&mut self, arm9_elf: &[u8], arm7_elf: Option<&[u8]>) -> bool {
+    let Some((rom, arm9_symbols, arm7_symbols)) = Self::build_elf_rom(arm9_elf, arm7_elf) else {
+      return false;
+    };
+
+    self.reset(&rom);
+    self.set_symbols(arm9_symbols, arm7_symbols);
+
+    true
+  }
+
+  fn build_elf_rom(arm9_elf: &[u8], arm7_elf: Option<&[u8]>) -> Option<(Vec<u8>, SymbolTable, SymbolTable)> {
+    let arm9 = ElfFile::parse(arm9_elf, true)?;
+
+    let arm7 = match arm7_elf {
+      Some(bytes) => Some(ElfFile::parse(bytes, false)?),
+      None => None
+    };
+
+    let rom = elf::build_rom(&arm9, arm7.as_ref());
+
+    Some((rom, arm9.symbols, arm7.map(|arm7| arm7.symbols).unwrap_or_default()))
+  }
+
+  // images are read and written in place, while directories are packed into a fat image that's thrown away on exit
+  pub fn insert_sd_card(&mut self, path: &Path) -> bool {
+    let Some(sd_card) = SdCard::open(path) else {
+      println!("[WARN] couldn't open sd card at {}", path.display());
+      return false;
+    };
+
+    self.bus.borrow_mut().sd_card = Some(sd_card);
+
+    true
+  }
+
+  fn set_symbols(&mut self, arm9_symbols: SymbolTable, arm7_symbols: SymbolTable) {
+    let ref mut bus = *self.bus.borrow_mut();
+
+    bus.arm9.symbols = arm9_symbols;
+    bus.arm7.symbols = arm7_symbols;
+  }
+
+  // inserts a gba cartridge into slot 2, for games that read data from their gba counterparts
+  pub fn load_gba_rom(&mut self, rom: Vec<u8>, save_path: Option<PathBuf>, save_bytes: Option<Vec<u8>>) {
+    self.bus.borrow_mut().load_gba_rom(rom, save_path, save_bytes);
+  }
+
+  // called with every message homebrew prints through the no$gba debug registers, and whether it came from the arm9
+  pub fn set_debug_print_callback(&mut self, callback: DebugCallback) {
+    self.bus.borrow_mut().debug_output.set_callback(callback);
+  }
+
+  // called whenever a rumble pak in slot 2 switches its motor on or off
+  pub fn set_rumble_callback(&mut self, callback: RumbleCallback) {
+    self.bus.borrow_mut().set_rumble_callback(callback);
+  }
+
+  // arguments handed to homebrew on direct boot. the first one is the program's own path, like on any other platform
+  pub fn set_argv(&mut self, argv: Vec<String>) {
+    self.bus.borrow_mut().argv = argv;
+  }
+
+  pub fn load_gba_bios(&mut self, bytes: Vec<u8>) -> bool {
+    if bytes.len() != GBA_BIOS_SIZE {
+      println!("[WARN] GBA bios should be {} bytes, got {}", GBA_BIOS_SIZE, bytes.len());
+      return false;
+    }
+
+    self.bus.borrow_mut().gba_bios = bytes;
+
+    true
+  }
+
+  // games call into the bios for every swi and interrupt, so there's no running them without one
+  pub fn has_gba_bios(&self) -> bool {
+    if self.bus.borrow().gba_bios.is_empty() {
+      println!("[WARN] GBA mode needs a GBA bios");
+      return false;
+    }
+
+    true
+  }
+
+  // boots straight into GBA mode with the given cartridge, without going through the DS side at all
+  pub fn init_gba(&mut self, rom: Vec<u8>, save_path: Option<PathBuf>, save_bytes: Option<Vec<u8>>, skip_bios: bool) -> bool {
+    if !self.has_gba_bios() {
+      return false;
+    }
+
+    self.load_gba_rom(rom, save_path, save_bytes);
+
+    self.enter_gba_mode(skip_bios);
+
+    true
+  }
+
+  fn enter_gba_mode(&mut self, skip_bios: bool) {
+    self.bus.borrow_mut().enter_gba_mode();
+
+    // keep the cycle count in sync with the scheduler
+    let cycles = self.arm7_cpu.cycles;
+
+    self.arm7_cpu = CPU::new(self.bus.clone());
+    self.arm7_cpu.cycles = cycles;
+
+    if skip_bios {
+      self.arm7_cpu.skip_gba_bios();
+    }
+
+    self.arm7_cpu.reload_pipeline32();
+  }
+
+  pub fn reset_gba(&mut self, skip_bios: bool) -> bool {
+    if !self.has_gba_bios() {
+      return false;
+    }
+
+    {
+      let ref mut bus = *self.bus.borrow_mut();
+
+      bus.arm7.apu.audio_buffer.lock().unwrap().drain(..);
+
+      let bus_rc = Rc::new(RefCell::new(bus.reset()));
+
+      self.arm9_cpu = CPU::new(bus_rc.clone());
+      self.arm7_cpu = CPU::new(bus_rc);
+    }
+
+    self.bus = self.arm9_cpu.bus.clone();
+
+    self.enter_gba_mode(skip_bios);
+
+    true
+  }
+
   pub fn reset(&mut self, rom: &Vec<u8>) {
     {
       let ref mut bus = *self.bus.borrow_mut();
@@ -79,8 +239,8 @@ impl Nds {
 
       let mut new_bus = bus.reset();
 
-      new_bus.cartridge.rom = rom.clone();
-      new_bus.cartridge.header = Header::from(rom);
+      new_bus.cartridge.load_rom(rom);
+      new_bus.patch_dldi();
 
       new_bus.skip_bios();
 
@@ -99,18 +259,79 @@ impl Nds {
     self.bus = self.arm9_cpu.bus.clone();
   }
 
+  pub fn set_mic_source(&mut self, source: Box<dyn MicSource>) {
+    self.mic_source = source;
+  }
+
+  // holding the blow hotkey takes over from whatever the mic source is
+  pub fn set_mic_blowing(&mut self, blowing: bool) {
+    self.mic_blowing = blowing;
+  }
+
+  pub fn update_mic(&mut self) {
+    let ref mut bus = *self.bus.borrow_mut();
+
+    if self.mic_blowing {
+      bus.touchscreen.update_mic(&mut self.blow_source);
+    } else {
+      bus.touchscreen.update_mic(self.mic_source.as_mut());
+    }
+  }
+
+  pub fn set_lid_closed(&mut self, closed: bool) {
+    self.bus.borrow_mut().set_lid_closed(closed);
+  }
+
   pub fn step(&mut self) -> bool {
-    let (cycles, scheduler_cycles) = {
+    self.bus.borrow_mut().check_keypad_interrupts();
+
+    // nothing but the rtc runs while asleep, see check_sleep. the lid and keys are checked by the frontend in between
+    // frames, so time moves on at most a frame at once
+    let asleep = self.bus.borrow_mut().check_sleep();
+
+    let (cycles, scheduler_cycles, gba_mode) = {
       let ref mut bus = *self.bus.borrow_mut();
       let cycles = bus.scheduler.get_cycles_to_next_event();
       let scheduler_cycles = bus.scheduler.cycles;
-      (cycles, scheduler_cycles)
+      (cycles, scheduler_cycles, bus.gba_mode)
     };
 
-    let actual_target = std::cmp::min(scheduler_cycles + 30, cycles);
+    let actual_target = if asleep {
+      // an empty queue reports 0 cycles to the next event
+      let target = scheduler_cycles + SLEEP_STEP_CYCLES;
+
+      if cycles != 0 { std::cmp::min(target, cycles) } else { target }
+    } else {
+      std::cmp::min(scheduler_cycles + 30, cycles)
+    };
+
+    if asleep {
+      // skip straight to the next event
+      self.arm9_cpu.cycles = actual_target * 2;
+      self.arm7_cpu.cycles = actual_target;
+    }
+
+    if !asleep {
+      // the arm9 is switched off entirely in gba mode
+      if !gba_mode {
+        self.arm9_cpu.step(actual_target * 2);
+      }
+      self.arm7_cpu.step(actual_target);
+    }
 
-    self.arm9_cpu.step(actual_target * 2);
-    self.arm7_cpu.step(actual_target);
+    if !gba_mode && self.bus.borrow().arm7.haltcnt == HaltMode::GbaMode {
+      let has_cartridge = self.bus.borrow().gba_cartridge.is_some();
+
+      if !has_cartridge {
+        println!("[WARN] GBA mode requested without a GBA cartridge inserted");
+      }
+
+      if has_cartridge && self.has_gba_bios() {
+        self.enter_gba_mode(false);
+      } else {
+        self.bus.borrow_mut().arm7.haltcnt = HaltMode::None;
+      }
+    }
 
     let ref mut bus = *self.bus.borrow_mut();
 
@@ -129,6 +350,10 @@ impl Nds {
 
           timers.t[timer_id].handle_overflow(&mut bus.arm7.interrupt_request, &mut bus.scheduler, cycles_left);
           timers.handle_overflow(timer_id, &mut bus.arm7.dma, &mut bus.arm7.interrupt_request, &mut bus.scheduler, cycles_left);
+
+          if bus.gba_mode {
+            bus.arm7.apu.gba_sound.handle_timer_overflow(timer_id, &mut bus.arm7.dma);
+          }
         }
         EventType::Timer9(timer_id) => {
           let timers = &mut bus.arm9.timers;
@@ -143,6 +368,20 @@ impl Nds {
         EventType::StepAudio(channel_id) => bus.step_audio(channel_id, cycles_left),
         EventType::ResetAudio(channel_id) => bus.arm7.apu.channels[channel_id].reset_audio(),
         EventType::GenerateSample => bus.arm7.apu.generate_samples(&mut bus.scheduler, cycles_left),
+        EventType::RtcTick => {
+          bus.scheduler.schedule(EventType::RtcTick, CYCLES_PER_TICK - cycles_left);
+
+          // the ds rtc can't be reached in gba mode
+          if !bus.gba_mode {
+            bus.arm7.rtc.tick(&mut bus.arm7.interrupt_request);
+          }
+        }
+        EventType::WifiTick => {
+          bus.scheduler.schedule(EventType::WifiTick, WIFI_TICK_CYCLES - cycles_left);
+
+          bus.arm7.wifi.tick(&bus.scheduler);
+          bus.check_wifi_interrupt();
+        }
         EventType::CheckGeometryFifo => {
           if bus.gpu.engine3d.should_run_dmas() {
             bus.arm9.dma.notify_geometry_fifo_event();
@@ -152,6 +391,112 @@ impl Nds {
       }
     }
 
-    bus.gpu.frame_finished
+    // the gpu is stopped, so every step while asleep stands in for a frame
+    asleep || bus.gpu.frame_finished
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use chrono::NaiveDate;
+
+  use crate::cpu::registers::{
+    interrupt_enable_register::InterruptEnableRegister,
+    interrupt_request_register::InterruptRequestRegister,
+    real_time_clock_register::RealTimeClockRegister
+  };
+
+  use crate::scheduler::Scheduler;
+
+  use super::*;
+
+  const RTC_STATUS_REGISTER1: u8 = 0;
+  const RTC_STATUS_REGISTER2: u8 = 1;
+  const RTC_DATE_TIME: u8 = 2;
+  const RTC_ALARM1: u8 = 4;
+
+  // bit bangs a write command over the rtc's serial lines: command byte msb first, data bytes lsb first
+  fn write_rtc(rtc: &mut RealTimeClockRegister, param: u8, data: &[u8]) {
+    let mut set_lines = |cs: bool, sck: bool, bit: bool| {
+      rtc.write(0x70 | (cs as u16) << 2 | (sck as u16) << 1 | bit as u16);
+    };
+
+    set_lines(false, true, false);
+    set_lines(true, true, false);
+
+    let command = 0x60 | param << 1;
+
+    let bits = (0..8).rev().map(|i| (command >> i) & 0b1 == 1)
+      .chain(data.iter().flat_map(|byte| (0..8).map(move |i| (byte >> i) & 0b1 == 1)));
+
+    for bit in bits {
+      set_lines(true, false, bit);
+      set_lines(true, true, bit);
+    }
+
+    set_lines(false, true, false);
+  }
+
+  #[test]
+  fn rtc_takes_the_whole_date_at_once() {
+    let mut scheduler = Scheduler::new();
+    let mut rtc = RealTimeClockRegister::new(&mut scheduler);
+
+    rtc.set_clock(NaiveDate::from_ymd_opt(2024, 1, 31).unwrap().and_hms_opt(0, 0, 0).unwrap());
+
+    // 24 hour mode, then 2025-02-28 13:45:30 in bcd. february 31st doesn't exist, so the month can't be taken on its own
+    write_rtc(&mut rtc, RTC_STATUS_REGISTER1, &[0b10]);
+    write_rtc(&mut rtc, RTC_DATE_TIME, &[0x25, 0x02, 0x28, 0, 0x13, 0x45, 0x30]);
+
+    assert_eq!(rtc.clock(), NaiveDate::from_ymd_opt(2025, 2, 28).unwrap().and_hms_opt(13, 45, 30).unwrap());
+  }
+
+  #[test]
+  fn rtc_alarm_wakes_the_console_from_sleep() {
+    let mut nds = Nds::new(
+      None,
+      Some(vec![0; 0x4_0000]),
+      vec![0; 0x4000],
+      vec![0; 0x1000],
+      Arc::new(Mutex::new(VecDeque::new())),
+      Arc::new(Mutex::new([0; 2048]))
+    );
+
+    {
+      let ref mut bus = *nds.bus.borrow_mut();
+
+      let clock = NaiveDate::from_ymd_opt(2026, 1, 1).unwrap().and_hms_opt(11, 59, 58).unwrap();
+
+      bus.arm7.rtc.set_clock(clock);
+
+      // alarm 1 at minute 00, as a bcd value with its compare bit set
+      write_rtc(&mut bus.arm7.rtc, RTC_STATUS_REGISTER2, &[0b0100]);
+      write_rtc(&mut bus.arm7.rtc, RTC_ALARM1, &[0, 0, 0x80]);
+
+      bus.arm7.interrupt_enable.insert(InterruptEnableRegister::SIO_RTC);
+
+      bus.set_lid_closed(true);
+      bus.write_haltcnt(0xc0);
+    }
+
+    let mut frames = 0;
+    let vcount = nds.bus.borrow().gpu.vcount;
+
+    while nds.bus.borrow().arm7.haltcnt == HaltMode::Sleep && frames < 5 * 60 {
+      if nds.step() {
+        nds.bus.borrow_mut().gpu.frame_finished = false;
+        frames += 1;
+      }
+
+      // the gpu stays put until the console wakes up
+      if nds.bus.borrow().arm7.haltcnt == HaltMode::Sleep {
+        assert_eq!(nds.bus.borrow().gpu.vcount, vcount);
+      }
+    }
+
+    let bus = nds.bus.borrow();
+
+    assert!(bus.arm7.interrupt_request.contains(InterruptRequestRegister::SIO_RTC));
+    assert!(bus.arm7.haltcnt == HaltMode::None);
   }
-}
\ No newline at end of file
+}
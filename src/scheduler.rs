@@ -13,19 +13,24 @@ pub enum EventType {
   StepAudio(usize),
   ResetAudio(usize),
   GenerateSample,
-  CheckGeometryFifo
+  CheckGeometryFifo,
+  RtcTick,
+  WifiTick
 }
 
 pub struct Scheduler {
   pub cycles: usize,
-  pub queue: PriorityQueue<EventType, Reverse<usize>>
+  pub queue: PriorityQueue<EventType, Reverse<usize>>,
+  // events taken out of the queue while the console sleeps, along with how many cycles they had left
+  paused: Option<Vec<(EventType, usize)>>
 }
 
 impl Scheduler {
   pub fn new() -> Self {
     Self {
       cycles: 0,
-      queue: PriorityQueue::new()
+      queue: PriorityQueue::new(),
+      paused: None
     }
   }
 
@@ -42,7 +47,7 @@ impl Scheduler {
   }
 
   pub fn get_next_event(&mut self) -> Option<(EventType, usize)> {
-    let (_, Reverse(cycles)) = self.queue.peek().unwrap();
+    let (_, Reverse(cycles)) = self.queue.peek()?;
 
     if self.cycles >= *cycles {
       let cycles_left = self.cycles - *cycles;
@@ -53,6 +58,33 @@ impl Scheduler {
     None
   }
 
+  // holds every event except the ones in keep until resume_events, as if no time passed for them in between
+  pub fn pause_events(&mut self, keep: &[EventType]) {
+    if self.paused.is_some() {
+      return;
+    }
+
+    let paused: Vec<(EventType, usize)> = self.queue
+      .iter()
+      .filter(|(event_type, _)| !keep.contains(event_type))
+      .map(|(event_type, Reverse(cycles))| (*event_type, cycles.saturating_sub(self.cycles)))
+      .collect();
+
+    for (event_type, _) in &paused {
+      self.queue.remove(event_type);
+    }
+
+    self.paused = Some(paused);
+  }
+
+  pub fn resume_events(&mut self) {
+    if let Some(paused) = self.paused.take() {
+      for (event_type, cycles_left) in paused {
+        self.schedule(event_type, cycles_left);
+      }
+    }
+  }
+
   pub fn rebase_cycles(&mut self) -> usize {
     let to_subtract = self.cycles;
 
@@ -1,4 +1,19 @@
 
 pub fn read_word(bytes: &Vec<u8>, offset: usize) -> u32 {
   (bytes[offset] as u32) | (bytes[offset + 1] as u32) << 8 | (bytes[offset + 2] as u32) << 16 | (bytes[offset + 3] as u32) << 24
+}
+
+// the crc16 the ds uses for its headers and firmware, starting from the given initial value
+pub fn crc16(initial: u16, bytes: &[u8]) -> u16 {
+  let mut crc = initial;
+
+  for byte in bytes {
+    crc ^= *byte as u16;
+
+    for _ in 0..8 {
+      crc = if crc & 0b1 == 1 { (crc >> 1) ^ 0xa001 } else { crc >> 1 };
+    }
+  }
+
+  crc
 }
\ No newline at end of file
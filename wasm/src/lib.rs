@@ -113,6 +113,17 @@ impl WasmEmulator {
     bus.arm7.extkeyin.remove(ExternalKeyInputRegister::PEN_DOWN);
   }
 
+  pub fn touch_screen_subpixel(&mut self, x: f32, y: f32) {
+    let ref mut bus = *self.nds.bus.borrow_mut();
+
+    bus.touchscreen.touch_screen_subpixel(x, y);
+    bus.arm7.extkeyin.remove(ExternalKeyInputRegister::PEN_DOWN);
+  }
+
+  pub fn set_touch_pressure(&mut self, pressure: f32) {
+    self.nds.bus.borrow_mut().touchscreen.set_pressure(pressure);
+  }
+
   pub fn release_screen(&mut self) {
     let ref mut bus = *self.nds.bus.borrow_mut();
 
@@ -130,6 +141,8 @@ impl WasmEmulator {
       BackupType::None => false,
       BackupType::Eeprom(eeprom) => eeprom.backup_file.has_written,
       BackupType::Flash(flash) => flash.backup_file.has_written,
+      BackupType::Infrared(infrared) => infrared.flash.backup_file.has_written,
+      BackupType::Nand(nand) => nand.backup_file.has_written,
     }
   }
 
@@ -140,6 +153,8 @@ impl WasmEmulator {
       BackupType::None => unreachable!(),
       BackupType::Eeprom(eeprom) => eeprom.backup_file.buffer.as_ptr(),
       BackupType::Flash(flash) => flash.backup_file.buffer.as_ptr(),
+      BackupType::Infrared(infrared) => infrared.flash.backup_file.buffer.as_ptr(),
+      BackupType::Nand(nand) => nand.backup_file.buffer.as_ptr(),
     }
   }
 
@@ -150,6 +165,8 @@ impl WasmEmulator {
       BackupType::None => unreachable!(),
       BackupType::Eeprom(eeprom) => eeprom.backup_file.buffer.len(),
       BackupType::Flash(flash) => flash.backup_file.buffer.len(),
+      BackupType::Infrared(infrared) => infrared.flash.backup_file.buffer.len(),
+      BackupType::Nand(nand) => nand.backup_file.buffer.len(),
     }
   }
 
@@ -168,6 +185,8 @@ impl WasmEmulator {
       BackupType::None => unreachable!(),
       BackupType::Eeprom(eeprom) => eeprom.backup_file.has_written = val,
       BackupType::Flash(flash) => flash.backup_file.has_written = val,
+      BackupType::Infrared(infrared) => infrared.flash.backup_file.has_written = val,
+      BackupType::Nand(nand) => nand.backup_file.has_written = val,
     }
   }
 
@@ -218,6 +237,22 @@ impl WasmEmulator {
     bus.gpu.powcnt1.contains(PowerControlRegister1::TOP_A)
   }
 
+  pub fn set_battery_low(&mut self, low: bool) {
+    self.nds.bus.borrow_mut().power_manager.set_battery_low(low);
+  }
+
+  pub fn top_brightness(&self) -> f32 {
+    self.nds.bus.borrow().power_manager.top_brightness()
+  }
+
+  pub fn bottom_brightness(&self) -> f32 {
+    self.nds.bus.borrow().power_manager.bottom_brightness()
+  }
+
+  pub fn power_off_requested(&self) -> bool {
+    self.nds.bus.borrow_mut().power_manager.take_power_off_request()
+  }
+
   pub fn press_screen(&mut self) {
     self.nds.bus.borrow_mut().arm7.extkeyin.remove(ExternalKeyInputRegister::PEN_DOWN);
   }